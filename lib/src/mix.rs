@@ -1,6 +1,10 @@
 use ark_bn254::Fr;
 use light_poseidon::{Poseidon, PoseidonBytesHasher};
 
+use crate::secret::Secret;
+use crate::tree::{MerklePath, TREE_DEPTH};
+use crate::utils::{try_hash1, try_hash2};
+
 /// Verifies a Merkle‐proof of depth `LEVEL` with Circom‐compatible Poseidon(2).
 /// Panics if the reconstructed root doesn’t match `root`.
 pub fn merkle_check<const LEVEL: usize>(
@@ -21,3 +25,160 @@ pub fn merkle_check<const LEVEL: usize>(
     }
     assert!(node == root, "Merkle check failed");
 }
+
+/// Byte length of the values `mixer-program` commits.
+pub const PUBLIC_VALUES_LEN: usize = 5 * 32 + 3 * 8;
+
+/// Mint of native SOL pools in the public values.
+pub const NATIVE_MINT: [u8; 32] = [0; 32];
+
+/// Public values committed by `mixer-program`, in commit order. SP1 serializes
+/// them with bincode: raw 32-byte arrays and little-endian `u64`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicValues {
+    pub root: [u8; 32],
+    pub nullifier_hash: [u8; 32],
+    /// Wallet for SOL pools, its associated token account for token pools.
+    pub recipient: [u8; 32],
+    pub relayer: [u8; 32],
+    pub fee: u64,
+    pub refund: u64,
+    /// SPL token mint, [`NATIVE_MINT`] for SOL.
+    pub mint: [u8; 32],
+    /// Deposit amount in base units of `mint`.
+    pub amount: u64,
+}
+
+impl PublicValues {
+    pub fn decode(bytes: &[u8]) -> Option<PublicValues> {
+        if bytes.len() != PUBLIC_VALUES_LEN {
+            return None;
+        }
+        let word = |i: usize| -> [u8; 32] { bytes[i * 32..(i + 1) * 32].try_into().unwrap() };
+        let int = |off: usize| u64::from_le_bytes(bytes[off..off + 8].try_into().unwrap());
+        Some(PublicValues {
+            root: word(0),
+            nullifier_hash: word(1),
+            recipient: word(2),
+            relayer: word(3),
+            fee: int(128),
+            refund: int(136),
+            mint: bytes[144..176].try_into().unwrap(),
+            amount: int(176),
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(PUBLIC_VALUES_LEN);
+        out.extend_from_slice(&self.root);
+        out.extend_from_slice(&self.nullifier_hash);
+        out.extend_from_slice(&self.recipient);
+        out.extend_from_slice(&self.relayer);
+        out.extend_from_slice(&self.fee.to_le_bytes());
+        out.extend_from_slice(&self.refund.to_le_bytes());
+        out.extend_from_slice(&self.mint);
+        out.extend_from_slice(&self.amount.to_le_bytes());
+        out
+    }
+}
+
+/// Everything `mixer-program` reads from stdin, public inputs first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawalInputs {
+    pub root: [u8; 32],
+    pub nullifier_hash: [u8; 32],
+    pub recipient: [u8; 32],
+    pub relayer: [u8; 32],
+    pub fee: u64,
+    pub refund: u64,
+    pub mint: [u8; 32],
+    pub amount: u64,
+    pub nullifier: Secret<[u8; 32]>,
+    pub secret: Secret<[u8; 32]>,
+    pub path_elements: [[u8; 32]; TREE_DEPTH],
+    pub path_indices: [u8; TREE_DEPTH],
+}
+
+/// What a withdrawal pays out: the public values besides the root and the
+/// nullifier hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Payout {
+    pub recipient: [u8; 32],
+    pub relayer: [u8; 32],
+    pub fee: u64,
+    pub refund: u64,
+    pub mint: [u8; 32],
+    pub amount: u64,
+}
+
+impl WithdrawalInputs {
+    /// Inputs withdrawing the note `(nullifier, secret)`, whose commitment is
+    /// the leaf of `path`. This is all a client needs to prove locally.
+    pub fn for_note(
+        nullifier: Secret<[u8; 32]>,
+        secret: Secret<[u8; 32]>,
+        path: &MerklePath,
+        payout: Payout,
+    ) -> Result<Self, String> {
+        let commitment = try_hash2(nullifier.expose(), secret.expose())
+            .map_err(|e| format!("hashing the note: {}", e))?;
+        if !path.verify(commitment) {
+            return Err(format!(
+                "the note is not leaf {} under this root",
+                path.leaf_index
+            ));
+        }
+        let nullifier_hash =
+            try_hash1(nullifier.expose()).map_err(|e| format!("hashing the note: {}", e))?;
+        Ok(WithdrawalInputs {
+            root: path.root,
+            nullifier_hash,
+            recipient: payout.recipient,
+            relayer: payout.relayer,
+            fee: payout.fee,
+            refund: payout.refund,
+            mint: payout.mint,
+            amount: payout.amount,
+            nullifier,
+            secret,
+            path_elements: path.path_elements,
+            path_indices: path.path_indices,
+        })
+    }
+
+    /// The public values a successful run of the program commits for these inputs.
+    pub fn public_values(&self) -> PublicValues {
+        PublicValues {
+            root: self.root,
+            nullifier_hash: self.nullifier_hash,
+            recipient: self.recipient,
+            relayer: self.relayer,
+            fee: self.fee,
+            refund: self.refund,
+            mint: self.mint,
+            amount: self.amount,
+        }
+    }
+}
+
+/// The program input for `inputs`, written in the exact order `mixer-program`
+/// reads it.
+#[cfg(feature = "sp1")]
+pub fn withdrawal_stdin(inputs: &WithdrawalInputs) -> sp1_sdk::SP1Stdin {
+    let mut stdin = sp1_sdk::SP1Stdin::new();
+    stdin.write(&inputs.root);
+    stdin.write(&inputs.nullifier_hash);
+    stdin.write(&inputs.recipient);
+    stdin.write(&inputs.relayer);
+    stdin.write(&inputs.fee);
+    stdin.write(&inputs.refund);
+    stdin.write(&inputs.mint);
+    stdin.write(&inputs.amount);
+
+    stdin.write(inputs.nullifier.expose());
+    stdin.write(inputs.secret.expose());
+
+    stdin.write(&inputs.path_elements);
+    stdin.write(&inputs.path_indices);
+    stdin
+}
@@ -0,0 +1,288 @@
+use ark_bn254::Fr;
+use light_poseidon::{Poseidon, PoseidonBytesHasher};
+
+pub const TREE_DEPTH: usize = 20;
+
+/// Root of an empty subtree at each level, starting with the empty leaf.
+pub const ZERO_HASHES: [[u8; 32]; TREE_DEPTH] = [
+    [
+        28, 225, 101, 203, 17, 36, 237, 58, 10, 148, 180, 226, 18, 170, 247, 232, 7, 159, 73, 178,
+        251, 239, 145, 107, 194, 144, 197, 147, 253, 169, 9, 42,
+    ],
+    [
+        44, 37, 33, 144, 170, 41, 196, 255, 177, 217, 13, 209, 96, 22, 63, 31, 189, 226, 225, 107,
+        60, 59, 217, 73, 104, 85, 87, 161, 98, 46, 25, 23,
+    ],
+    [
+        199, 192, 139, 62, 101, 110, 114, 88, 10, 223, 158, 90, 92, 156, 242, 121, 110, 186, 213,
+        73, 160, 199, 139, 93, 59, 126, 247, 199, 180, 171, 213, 4,
+    ],
+    [
+        195, 14, 203, 76, 69, 4, 58, 41, 155, 50, 70, 4, 20, 111, 183, 33, 118, 162, 254, 210, 250,
+        13, 199, 140, 212, 199, 234, 11, 169, 89, 165, 14,
+    ],
+    [
+        213, 209, 90, 217, 231, 76, 215, 254, 77, 109, 54, 56, 172, 83, 223, 190, 193, 157, 101,
+        68, 174, 242, 152, 39, 120, 128, 239, 49, 155, 47, 245, 38,
+    ],
+    [
+        240, 84, 3, 71, 171, 53, 201, 28, 190, 149, 211, 115, 45, 246, 189, 74, 50, 130, 179, 241,
+        13, 241, 220, 214, 84, 86, 24, 240, 92, 124, 162, 47,
+    ],
+    [
+        241, 195, 14, 235, 69, 82, 145, 169, 122, 158, 38, 203, 218, 80, 135, 166, 104, 169, 105,
+        163, 220, 45, 188, 80, 35, 38, 28, 98, 57, 139, 192, 1,
+    ],
+    [
+        56, 192, 212, 159, 83, 188, 37, 134, 9, 245, 223, 94, 83, 72, 113, 241, 166, 202, 248, 76,
+        6, 24, 24, 181, 13, 5, 248, 85, 163, 179, 57, 42,
+    ],
+    [
+        151, 168, 4, 21, 164, 64, 162, 185, 81, 25, 79, 39, 170, 241, 159, 101, 157, 166, 48, 202,
+        8, 110, 32, 219, 252, 108, 223, 95, 75, 71, 248, 2,
+    ],
+    [
+        70, 149, 214, 96, 127, 240, 140, 215, 118, 64, 43, 48, 52, 112, 145, 51, 143, 95, 194, 7,
+        84, 125, 84, 225, 114, 148, 96, 162, 136, 133, 92, 37,
+    ],
+    [
+        130, 131, 208, 217, 183, 159, 92, 94, 35, 33, 196, 166, 113, 52, 196, 195, 96, 224, 90,
+        148, 86, 92, 171, 15, 144, 220, 203, 144, 48, 171, 1, 11,
+    ],
+    [
+        141, 151, 69, 209, 91, 138, 65, 189, 97, 90, 100, 40, 12, 249, 148, 165, 249, 226, 43, 108,
+        147, 173, 71, 107, 4, 128, 174, 222, 71, 9, 149, 21,
+    ],
+    [
+        173, 114, 239, 45, 155, 6, 118, 201, 139, 149, 249, 136, 77, 38, 31, 154, 181, 196, 252,
+        251, 160, 19, 140, 62, 107, 168, 69, 242, 142, 246, 249, 29,
+    ],
+    [
+        17, 83, 128, 73, 34, 8, 223, 220, 113, 124, 66, 191, 201, 148, 152, 106, 170, 154, 56, 58,
+        48, 215, 173, 163, 219, 20, 249, 195, 17, 95, 94, 33,
+    ],
+    [
+        205, 216, 87, 136, 166, 161, 105, 162, 246, 238, 20, 213, 195, 163, 233, 4, 157, 147, 128,
+        26, 2, 105, 145, 61, 108, 230, 63, 180, 126, 157, 223, 18,
+    ],
+    [
+        175, 146, 71, 147, 69, 244, 233, 250, 163, 242, 9, 0, 149, 126, 33, 4, 12, 249, 153, 19,
+        99, 47, 223, 234, 189, 144, 210, 226, 33, 239, 51, 39,
+    ],
+    [
+        226, 97, 86, 241, 231, 249, 243, 205, 181, 78, 133, 95, 163, 78, 21, 148, 226, 156, 146,
+        90, 204, 133, 121, 90, 23, 96, 139, 170, 212, 227, 93, 27,
+    ],
+    [
+        25, 175, 151, 81, 90, 87, 246, 118, 51, 91, 229, 95, 50, 81, 156, 254, 8, 10, 122, 198,
+        227, 101, 77, 141, 223, 35, 38, 196, 78, 33, 208, 34,
+    ],
+    [
+        88, 209, 97, 10, 32, 208, 96, 164, 43, 49, 59, 43, 116, 173, 157, 144, 180, 83, 217, 22,
+        21, 45, 49, 106, 39, 223, 133, 234, 157, 100, 95, 28,
+    ],
+    [
+        173, 32, 70, 199, 47, 67, 249, 150, 239, 20, 221, 152, 219, 177, 16, 193, 121, 156, 212,
+        216, 9, 218, 218, 11, 122, 25, 59, 228, 61, 23, 128, 43,
+    ],
+];
+
+/// The layers of the tree of height `depth` over `leaves`, from the leaves up
+/// to the root. Each layer holds the nodes that have a leaf below them; the
+/// rest are `ZERO_HASHES` of their level.
+pub fn tree_layers(leaves: &[[u8; 32]], depth: usize) -> Vec<Vec<[u8; 32]>> {
+    let mut layers: Vec<Vec<[u8; 32]>> = vec![leaves.to_vec()];
+    let mut hasher = Poseidon::<Fr>::new_circom(2).unwrap();
+
+    for d in 0..depth {
+        let mut next = Vec::with_capacity((layers[d].len()).div_ceil(2));
+        for pair in layers[d].chunks(2) {
+            let l = pair[0];
+            let r = if pair.len() == 2 {
+                pair[1]
+            } else {
+                ZERO_HASHES[d]
+            };
+            next.push(hasher.hash_bytes_le(&[&l, &r]).unwrap());
+        }
+        layers.push(next);
+    }
+    layers
+}
+
+/// Fills `siblings` with those of `leaf_index` in `layers`, bottom first.
+fn siblings_in(layers: &[Vec<[u8; 32]>], leaf_index: usize, siblings: &mut [[u8; 32]]) {
+    for (d, sibling) in siblings.iter_mut().enumerate() {
+        let sib_idx = (leaf_index >> d) ^ 1;
+        *sibling = layers[d].get(sib_idx).copied().unwrap_or(ZERO_HASHES[d]);
+    }
+}
+
+/// Path bits of `leaf_index`: 0 where the node is a left child.
+fn path_bits<const DEPTH: usize>(leaf_index: u64) -> [u8; DEPTH] {
+    let mut bits = [0u8; DEPTH];
+    for (d, bit) in bits.iter_mut().enumerate() {
+        *bit = ((leaf_index >> d) & 1) as u8;
+    }
+    bits
+}
+
+/// Builds the tree over `leaves` (padded with `ZERO_HASHES`) and returns the
+/// siblings, path bits and root for `leaf_index`.
+pub fn compute_merkle_proof<const DEPTH: usize>(
+    leaves: &[[u8; 32]],
+    leaf_index: usize,
+) -> ([[u8; 32]; DEPTH], [u8; DEPTH], [u8; 32]) {
+    assert!(leaf_index < leaves.len(), "index out of range");
+    let layers = tree_layers(leaves, DEPTH);
+    let mut siblings = [[0u8; 32]; DEPTH];
+    siblings_in(&layers, leaf_index, &mut siblings);
+    (siblings, path_bits(leaf_index as u64), layers[DEPTH][0])
+}
+
+/// Root and `filled_subtrees` of the tree over `leaves`, as the program's
+/// incremental tree keeps them: at each level, the last left-hand node on the
+/// way up from the newest leaf.
+pub fn tree_state(leaves: &[[u8; 32]]) -> ([u8; 32], [[u8; 32]; TREE_DEPTH]) {
+    let layers = tree_layers(leaves, TREE_DEPTH);
+    let mut filled = ZERO_HASHES;
+    for (d, node) in filled.iter_mut().enumerate() {
+        if let Some(last) = layers[d].len().checked_sub(1) {
+            *node = layers[d][last & !1];
+        }
+    }
+    let root = match layers[TREE_DEPTH].first() {
+        Some(root) => *root,
+        None => {
+            let empty = ZERO_HASHES[TREE_DEPTH - 1];
+            let mut hasher = Poseidon::<Fr>::new_circom(2).unwrap();
+            hasher.hash_bytes_le(&[&empty, &empty]).unwrap()
+        }
+    };
+    (root, filled)
+}
+
+/// The authentication path of one leaf: everything `mixer-program` needs
+/// besides the note and the payout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerklePath {
+    pub leaf_index: u64,
+    pub root: [u8; 32],
+    pub path_elements: [[u8; 32]; TREE_DEPTH],
+    /// 0 where the node is a left child, 1 where it is a right one.
+    pub path_indices: [u8; TREE_DEPTH],
+}
+
+impl MerklePath {
+    /// The path of `leaves[leaf_index]` in the tree over `leaves`.
+    pub fn new(leaves: &[[u8; 32]], leaf_index: usize) -> Self {
+        let (path_elements, path_indices, root) =
+            compute_merkle_proof::<TREE_DEPTH>(leaves, leaf_index);
+        MerklePath {
+            leaf_index: leaf_index as u64,
+            root,
+            path_elements,
+            path_indices,
+        }
+    }
+
+    /// The root reached from `leaf` along this path.
+    pub fn root_from(&self, leaf: [u8; 32]) -> [u8; 32] {
+        let mut hasher = Poseidon::<Fr>::new_circom(2).unwrap();
+        let mut node = leaf;
+        for (sibling, bit) in self.path_elements.iter().zip(self.path_indices) {
+            node = if bit == 0 {
+                hasher.hash_bytes_le(&[&node, sibling]).unwrap()
+            } else {
+                hasher.hash_bytes_le(&[sibling, &node]).unwrap()
+            };
+        }
+        node
+    }
+
+    /// Whether `leaf` sits at `leaf_index` under `root`.
+    pub fn verify(&self, leaf: [u8; 32]) -> bool {
+        let bits_match =
+            (0..TREE_DEPTH).all(|d| self.path_indices[d] as u64 == (self.leaf_index >> d) & 1);
+        bits_match && self.leaf_index >> TREE_DEPTH == 0 && self.root_from(leaf) == self.root
+    }
+}
+
+/// Height of the subtrees served by `GET /api/pools/{pool_id}/tree/subtree`:
+/// 256 leaves each. It is fixed so that every client asks for the same
+/// granularity.
+pub const SUBTREE_HEIGHT: usize = 8;
+
+/// One subtree of height `height`: its leaves and the siblings from its root
+/// up to the tree root. A client fetches the subtree holding its deposit and
+/// computes the path locally, so the server learns the subtree but not the
+/// leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtreeSnapshot {
+    pub height: usize,
+    /// Position of the subtree among those of its height.
+    pub index: u64,
+    /// The subtree's leaves so far, at most `2^height`.
+    pub leaves: Vec<[u8; 32]>,
+    /// `TREE_DEPTH - height` siblings of the subtree root, bottom first.
+    pub siblings: Vec<[u8; 32]>,
+    pub root: [u8; 32],
+}
+
+impl SubtreeSnapshot {
+    /// Subtree `index` of the tree over `leaves`, `None` if it has no leaves.
+    pub fn new(leaves: &[[u8; 32]], height: usize, index: u64) -> Option<Self> {
+        assert!(height <= TREE_DEPTH, "subtree higher than the tree");
+        let size = 1usize << height;
+        let start = usize::try_from(index).ok()?.checked_mul(size)?;
+        if start >= leaves.len() {
+            return None;
+        }
+        let layers = tree_layers(leaves, TREE_DEPTH);
+        let mut siblings = [[0u8; 32]; TREE_DEPTH];
+        siblings_in(&layers, start, &mut siblings);
+        Some(SubtreeSnapshot {
+            height,
+            index,
+            leaves: leaves[start..leaves.len().min(start + size)].to_vec(),
+            siblings: siblings[height..].to_vec(),
+            root: layers[TREE_DEPTH][0],
+        })
+    }
+
+    /// The subtree holding leaf `leaf_index` at [`SUBTREE_HEIGHT`].
+    pub fn index_of(leaf_index: u64) -> u64 {
+        leaf_index >> SUBTREE_HEIGHT
+    }
+
+    /// The path of `leaf`, checked against `root`.
+    pub fn path(&self, leaf: [u8; 32]) -> Result<MerklePath, String> {
+        if self.height > TREE_DEPTH
+            || self.leaves.len() > 1 << self.height
+            || self.siblings.len() != TREE_DEPTH - self.height
+        {
+            return Err("malformed subtree snapshot".to_string());
+        }
+        let position = self
+            .leaves
+            .iter()
+            .position(|l| *l == leaf)
+            .ok_or("leaf is not in this subtree")?;
+        let layers = tree_layers(&self.leaves, self.height);
+        let mut path_elements = [[0u8; 32]; TREE_DEPTH];
+        siblings_in(&layers, position, &mut path_elements[..self.height]);
+        path_elements[self.height..].copy_from_slice(&self.siblings);
+
+        let leaf_index = (self.index << self.height) | position as u64;
+        let path = MerklePath {
+            leaf_index,
+            root: self.root,
+            path_elements,
+            path_indices: path_bits(leaf_index),
+        };
+        if !path.verify(leaf) {
+            return Err("subtree snapshot does not hash to its root".to_string());
+        }
+        Ok(path)
+    }
+}
@@ -1,4 +1,5 @@
 use ark_bn254::Fr;
+use ark_ff::PrimeField;
 use light_poseidon::{Poseidon, PoseidonBytesHasher};
 use num_bigint::BigUint;
 use rand::RngCore;
@@ -29,6 +30,26 @@ pub fn hash2(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
     pose.hash_bytes_le(&[a, b]).unwrap()
 }
 
+/// Like [`hash1`], but fails instead of panicking when `input` is not below
+/// the field modulus.
+pub fn try_hash1(input: &[u8; 32]) -> Result<[u8; 32], String> {
+    let mut pose = Poseidon::<Fr>::new_circom(1).map_err(|e| e.to_string())?;
+    pose.hash_bytes_le(&[input]).map_err(|e| e.to_string())
+}
+
+/// Like [`hash2`], but fails instead of panicking when an input is not below
+/// the field modulus.
+pub fn try_hash2(a: &[u8; 32], b: &[u8; 32]) -> Result<[u8; 32], String> {
+    let mut pose = Poseidon::<Fr>::new_circom(2).map_err(|e| e.to_string())?;
+    pose.hash_bytes_le(&[a, b]).map_err(|e| e.to_string())
+}
+
+/// Whether the little-endian `bytes` are below the BN254 scalar modulus, as
+/// the inputs of [`hash1`] and [`hash2`] must be.
+pub fn is_field_element(bytes: &[u8; 32]) -> bool {
+    BigUint::from_bytes_le(bytes) < BigUint::from(Fr::MODULUS)
+}
+
 /// Convert a byte‐array or BigUint to hex string with `0x` and fixed length
 pub fn to_hex32(bytes: &[u8; 32]) -> String {
     let mut s = hex::encode(bytes);
@@ -56,22 +77,18 @@ pub fn create_random_commitment() -> (
     preimage.extend_from_slice(&nf31);
     preimage.extend_from_slice(&sec31);
 
-    let nullifier_hash = hash1(&{
-        let mut tmp = [0u8; 32];
-        tmp[0..31].copy_from_slice(&nf31);
-        tmp
-    });
-    let commitment = hash2(
-        &{
-            let mut t = [0u8; 32];
-            t[0..31].copy_from_slice(&nf31);
-            t
-        },
-        &{
-            let mut t = [0u8; 32];
-            t[0..31].copy_from_slice(&sec31);
-            t
-        },
-    );
+    let (commitment, nullifier_hash) = note_hashes(&nullifier, &secret);
     (nullifier, secret, preimage, commitment, nullifier_hash)
 }
+
+/// Commitment `H(nullifier, secret)` and nullifier hash `H(nullifier)` of a note,
+/// with both values encoded as 32-byte little-endian field elements.
+pub fn note_hashes(nullifier: &BigUint, secret: &BigUint) -> ([u8; 32], [u8; 32]) {
+    let to32 = |x: &BigUint| -> [u8; 32] {
+        let mut t = [0u8; 32];
+        t[0..31].copy_from_slice(&to_31_bytes(x.clone()));
+        t
+    };
+    let (nf, sec) = (to32(nullifier), to32(secret));
+    (hash2(&nf, &sec), hash1(&nf))
+}
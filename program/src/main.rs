@@ -19,6 +19,8 @@ fn main() {
     let relayer: [u8; 32] = read::<[u8; 32]>(); // Solana Pubkey
     let fee: u64 = read::<u64>();
     let refund: u64 = read::<u64>();
+    let mint: [u8; 32] = read::<[u8; 32]>(); // SPL mint, zeros for SOL
+    let amount: u64 = read::<u64>(); // pool denomination in base units of `mint`
 
     // Private inputs
     let nullifier: [u8; 32] = read::<[u8; 32]>(); // 32-byte nullifier
@@ -54,4 +56,10 @@ fn main() {
 
     commit(&fee);
     commit(&refund);
+
+    // The commitment does not bind the pool: `mint` and `amount` are only
+    // committed, and the on-chain program must check them against the pool
+    // it pays out of, as it does the root.
+    commit(&mint);
+    commit(&amount);
 }
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sp1_sdk::SP1_CIRCUIT_VERSION;
+use std::path::Path;
+
+use crate::solana::{self, SolanaVerifyingKey};
+use crate::verify::normalize_vkey_hash;
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const VKEY_HASH_FILE: &str = "vkey_hash.txt";
+pub const ELF_HASH_FILE: &str = "elf_sha256.txt";
+pub const SP1_VERSION_FILE: &str = "sp1_version.txt";
+pub const SOLANA_VK_FILE: &str = "mixer_vk.rs";
+pub const SOLIDITY_VK_FILE: &str = "MixerVerifyingKey.sol";
+
+/// Describes the verifier artifacts generated for one build of `mixer-program`.
+///
+/// Deployments compare a stored manifest against the current ELF to detect
+/// circuit drift before updating the on-chain program.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerifierManifest {
+    pub program: String,
+    pub vkey_hash: String,
+    pub elf_sha256: String,
+    pub sp1_version: String,
+    pub groth16_vk_sha256: String,
+    pub files: ArtifactFiles,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactFiles {
+    pub vkey_hash: String,
+    pub elf_sha256: String,
+    pub sp1_version: String,
+    pub solana_vk: String,
+    pub solidity_vk: String,
+}
+
+impl VerifierManifest {
+    pub fn new(elf: &[u8], vkey_hash: &str, groth16_vk: &[u8]) -> Result<Self, String> {
+        Ok(VerifierManifest {
+            program: "mixer-program".to_string(),
+            vkey_hash: normalize_vkey_hash(vkey_hash)?,
+            elf_sha256: hex::encode(Sha256::digest(elf)),
+            sp1_version: SP1_CIRCUIT_VERSION.to_string(),
+            groth16_vk_sha256: hex::encode(Sha256::digest(groth16_vk)),
+            files: ArtifactFiles {
+                vkey_hash: VKEY_HASH_FILE.to_string(),
+                elf_sha256: ELF_HASH_FILE.to_string(),
+                sp1_version: SP1_VERSION_FILE.to_string(),
+                solana_vk: SOLANA_VK_FILE.to_string(),
+                solidity_vk: SOLIDITY_VK_FILE.to_string(),
+            },
+        })
+    }
+
+    /// Lists every field where `current` differs from this (stored) manifest.
+    pub fn drift(&self, current: &VerifierManifest) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut cmp = |name: &str, stored: &str, now: &str| {
+            if stored != now {
+                out.push(format!("{}: manifest {} != current {}", name, stored, now));
+            }
+        };
+        cmp("vkey_hash", &self.vkey_hash, &current.vkey_hash);
+        cmp("elf_sha256", &self.elf_sha256, &current.elf_sha256);
+        cmp("sp1_version", &self.sp1_version, &current.sp1_version);
+        cmp(
+            "groth16_vk_sha256",
+            &self.groth16_vk_sha256,
+            &current.groth16_vk_sha256,
+        );
+        out
+    }
+}
+
+pub fn read_manifest(path: &Path) -> Result<VerifierManifest, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("parsing {}: {}", path.display(), e))
+}
+
+/// Reads the manifest at `path` and lists where it drifted from `current`,
+/// see [`VerifierManifest::drift`].
+pub fn check(path: &Path, current: &VerifierManifest) -> Result<Vec<String>, String> {
+    Ok(read_manifest(path)?.drift(current))
+}
+
+/// Writes the vkey hash, ELF hash, SP1 version, Solana and Solidity verifying
+/// keys and the manifest itself into `dir`.
+pub fn write_artifacts(
+    dir: &Path,
+    manifest: &VerifierManifest,
+    groth16_vk: &[u8],
+) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("creating {}: {}", dir.display(), e))?;
+
+    let vk = solana::to_solana_vk(groth16_vk)?;
+    let files = [
+        (
+            &manifest.files.vkey_hash,
+            format!("{}\n", manifest.vkey_hash),
+        ),
+        (
+            &manifest.files.elf_sha256,
+            format!("{}\n", manifest.elf_sha256),
+        ),
+        (
+            &manifest.files.sp1_version,
+            format!("{}\n", manifest.sp1_version),
+        ),
+        (
+            &manifest.files.solana_vk,
+            solana::solana_vk_rust_source(&vk, &manifest.vkey_hash)?,
+        ),
+        (
+            &manifest.files.solidity_vk,
+            solidity_vk_source(&vk, &manifest.vkey_hash),
+        ),
+    ];
+    for (name, contents) in files {
+        write_file(&dir.join(name), &contents)?;
+    }
+
+    let json = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+    write_file(&dir.join(MANIFEST_FILE), &format!("{}\n", json))
+}
+
+/// Renders the verifying key as Solidity constants. Points use the EIP-197
+/// encoding of the bn254 precompiles (G2 coordinates imaginary part first).
+pub fn solidity_vk_source(vk: &SolanaVerifyingKey, vkey_hash: &str) -> String {
+    let word = |b: &[u8]| format!("0x{}", hex::encode(b));
+    let g1 = |out: &mut String, name: &str, p: &[u8]| {
+        out.push_str(&format!(
+            "    uint256 internal constant {}_X = {};\n",
+            name,
+            word(&p[..32])
+        ));
+        out.push_str(&format!(
+            "    uint256 internal constant {}_Y = {};\n",
+            name,
+            word(&p[32..])
+        ));
+    };
+    let g2 = |out: &mut String, name: &str, p: &[u8]| {
+        for (i, part) in ["X_1", "X_0", "Y_1", "Y_0"].iter().enumerate() {
+            out.push_str(&format!(
+                "    uint256 internal constant {}_{} = {};\n",
+                name,
+                part,
+                word(&p[i * 32..(i + 1) * 32])
+            ));
+        }
+    };
+
+    let mut out = String::new();
+    out.push_str("// SPDX-License-Identifier: MIT\n");
+    out.push_str("// Generated by `cargo run --bin vkey -- generate`. Do not edit.\n");
+    out.push_str("pragma solidity ^0.8.20;\n\n");
+    out.push_str("library MixerVerifyingKey {\n");
+    out.push_str("    /// SP1 vkey hash of `mixer-program`.\n");
+    out.push_str(&format!(
+        "    bytes32 internal constant PROGRAM_VKEY = {};\n\n",
+        vkey_hash
+    ));
+    out.push_str("    // SP1 Groth16 verifying key.\n");
+    g1(&mut out, "ALPHA", &vk.vk_alpha_g1);
+    g2(&mut out, "BETA", &vk.vk_beta_g2);
+    g2(&mut out, "GAMMA", &vk.vk_gamma_g2);
+    g2(&mut out, "DELTA", &vk.vk_delta_g2);
+    for (i, ic) in vk.vk_ic.iter().enumerate() {
+        g1(&mut out, &format!("IC{}", i), ic);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    std::fs::write(path, contents).map_err(|e| format!("writing {}: {}", path.display(), e))
+}
@@ -0,0 +1,277 @@
+//! Generates proof fixtures for the mixer circuit that can be replayed by a Solana
+//! Anchor test and an EVM verifier test.
+//!
+//! You can run this script using the following command:
+//! ```shell
+//! RUST_LOG=info cargo run --release --bin evm -- --prover mock --deposits 8 --leaf-index 3
+//! ```
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use clap::Parser;
+use mixer_lib::mix::{PublicValues, WithdrawalInputs, NATIVE_MINT};
+use mixer_lib::tree::{compute_merkle_proof, TREE_DEPTH};
+use mixer_script::prover::{withdrawal_stdin, MixerProver, ProverBackend};
+use mixer_script::solana::{self, SolanaProof};
+use mixer_script::utils::biguint_to_32_le_bytes;
+use mixer_script::verify;
+use serde::{Deserialize, Serialize};
+use sp1_sdk::{include_elf, HashableKey, SP1ProofWithPublicValues, SP1VerifyingKey};
+use spl_associated_token_account_client::address::get_associated_token_address;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The ELF (executable and linkable format) file for the Succinct RISC-V zkVM.
+pub const MIXER_ELF: &[u8] = include_elf!("mixer-program");
+
+/// The arguments for the fixture command.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct EVMArgs {
+    /// Number of synthetic deposits to put in the tree.
+    #[arg(long, default_value = "8")]
+    deposits: usize,
+    /// Leaf index of the deposit to withdraw.
+    #[arg(long, default_value = "0")]
+    leaf_index: usize,
+    #[arg(long, value_enum, default_value = "mock")]
+    prover: ProverBackend,
+    /// Recipient public key (base58). A random key is used when omitted.
+    #[arg(long)]
+    recipient: Option<String>,
+    /// Relayer public key (base58). A random key is used when omitted.
+    #[arg(long)]
+    relayer: Option<String>,
+    #[arg(long, default_value = "0")]
+    fee: u64,
+    #[arg(long, default_value = "0")]
+    refund: u64,
+    /// SPL token mint (base58). Recipient and relayer become their associated
+    /// token accounts. Native SOL when omitted.
+    #[arg(long)]
+    mint: Option<String>,
+    /// Pool denomination in base units of the mint.
+    #[arg(long, default_value = "0")]
+    amount: u64,
+    #[arg(long, default_value = "fixtures")]
+    out_dir: PathBuf,
+}
+
+/// A fixture for the Solana Anchor verifier test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SolanaProofFixture {
+    vkey: String,
+    public_values: String,
+    proof: String,
+    /// `None` for mock proofs, which carry no Groth16 points.
+    solana: Option<SolanaProof>,
+    root: String,
+    nullifier_hash: String,
+    recipient: String, // base58
+    relayer: String,   // base58
+    fee: u64,
+    refund: u64,
+    mint: Option<String>, // base58, `None` for SOL
+    amount: u64,
+    commitment: String,
+    leaf_index: usize,
+}
+
+/// A fixture that can be used to test the verification of SP1 zkVM proofs inside Solidity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EvmProofFixture {
+    vkey: String,
+    public_values: String,
+    proof: String,
+    root: String,
+    nullifier_hash: String,
+    recipient: String, // bytes32
+    relayer: String,   // bytes32
+    fee: u64,
+    refund: u64,
+    mint: String, // bytes32
+    amount: u64,
+}
+
+fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+    dotenv::dotenv().ok();
+
+    // Parse the command line arguments.
+    let args = EVMArgs::parse();
+    assert!(
+        args.leaf_index < args.deposits,
+        "leaf index {} out of range for {} deposits",
+        args.leaf_index,
+        args.deposits
+    );
+
+    let pubkey = |s: &Option<String>| {
+        s.as_deref()
+            .map(|k| Pubkey::from_str(k).expect("invalid base58 pubkey"))
+            .unwrap_or_else(Pubkey::new_unique)
+            .to_bytes()
+    };
+    let mint = args
+        .mint
+        .as_deref()
+        .map(|m| Pubkey::from_str(m).expect("invalid base58 mint"));
+    let account = |wallet: [u8; 32]| match &mint {
+        Some(mint) => {
+            get_associated_token_address(&Pubkey::new_from_array(wallet), mint).to_bytes()
+        }
+        None => wallet,
+    };
+    let recipient = account(pubkey(&args.recipient));
+    let relayer = account(pubkey(&args.relayer));
+
+    // Synthetic deposits; only the withdrawn note's secrets are kept.
+    let mut leaves = Vec::with_capacity(args.deposits);
+    let mut note = None;
+    for i in 0..args.deposits {
+        let (nullifier, secret, _preimage, commitment, nullifier_hash) =
+            mixer_lib::utils::create_random_commitment();
+        leaves.push(commitment);
+        if i == args.leaf_index {
+            note = Some((nullifier, secret, commitment, nullifier_hash));
+        }
+    }
+    let (nullifier, secret, commitment, nullifier_hash) = note.unwrap();
+
+    let (path_elements, path_indices, root) =
+        compute_merkle_proof::<TREE_DEPTH>(&leaves, args.leaf_index);
+    mixer_lib::mix::merkle_check::<TREE_DEPTH>(root, commitment, &path_elements, &path_indices);
+
+    let inputs = WithdrawalInputs {
+        root,
+        nullifier_hash,
+        recipient,
+        relayer,
+        fee: args.fee,
+        refund: args.refund,
+        mint: mint.map_or(NATIVE_MINT, |m| m.to_bytes()),
+        amount: args.amount,
+        nullifier: biguint_to_32_le_bytes(&nullifier).into(),
+        secret: biguint_to_32_le_bytes(&secret).into(),
+        path_elements,
+        path_indices,
+    };
+
+    println!("Deposits: {}", args.deposits);
+    println!("Leaf index: {}", args.leaf_index);
+    println!("Prover: {:?}", args.prover);
+
+    // Setup the prover client and the program.
+    let client = MixerProver::new(args.prover).expect("failed to build prover");
+    let (pk, vk) = client.setup(MIXER_ELF);
+
+    // Generate the proof.
+    let proof = client
+        .prove_groth16(&pk, &withdrawal_stdin(&inputs))
+        .expect("failed to generate proof");
+
+    if args.prover != ProverBackend::Mock {
+        verify::verify_groth16(
+            &proof.bytes(),
+            proof.public_values.as_slice(),
+            &vk.bytes32(),
+        )
+        .expect("failed to verify proof");
+    }
+
+    create_proof_fixtures(
+        &proof,
+        &vk,
+        &inputs,
+        commitment,
+        args.leaf_index,
+        &args.out_dir,
+    );
+}
+
+/// Create the Solana and EVM fixtures for the given proof.
+fn create_proof_fixtures(
+    proof: &SP1ProofWithPublicValues,
+    vk: &SP1VerifyingKey,
+    inputs: &WithdrawalInputs,
+    commitment: [u8; 32],
+    leaf_index: usize,
+    out_dir: &Path,
+) {
+    // Deserialize the public values.
+    let bytes = proof.public_values.as_slice();
+    let decoded = PublicValues::decode(bytes).expect("unexpected public values layout");
+    assert_eq!(decoded, inputs.public_values(), "public values mismatch");
+
+    let hex0x = |b: &[u8]| format!("0x{}", hex::encode(b));
+    let proof_bytes = proof.bytes();
+
+    let solana = if proof_bytes.is_empty() {
+        None
+    } else {
+        Some(
+            solana::to_solana_proof(&proof_bytes, bytes, &vk.bytes32())
+                .expect("failed to encode Solana proof"),
+        )
+    };
+
+    let solana_fixture = SolanaProofFixture {
+        vkey: vk.bytes32(),
+        public_values: hex0x(bytes),
+        proof: hex0x(&proof_bytes),
+        solana,
+        root: hex0x(&decoded.root),
+        nullifier_hash: hex0x(&decoded.nullifier_hash),
+        recipient: Pubkey::new_from_array(decoded.recipient).to_string(),
+        relayer: Pubkey::new_from_array(decoded.relayer).to_string(),
+        fee: decoded.fee,
+        refund: decoded.refund,
+        mint: (decoded.mint != NATIVE_MINT)
+            .then(|| Pubkey::new_from_array(decoded.mint).to_string()),
+        amount: decoded.amount,
+        commitment: hex0x(&commitment),
+        leaf_index,
+    };
+
+    let evm_fixture = EvmProofFixture {
+        vkey: vk.bytes32(),
+        public_values: hex0x(bytes),
+        proof: hex0x(&proof_bytes),
+        root: hex0x(&decoded.root),
+        nullifier_hash: hex0x(&decoded.nullifier_hash),
+        recipient: hex0x(&decoded.recipient),
+        relayer: hex0x(&decoded.relayer),
+        fee: decoded.fee,
+        refund: decoded.refund,
+        mint: hex0x(&decoded.mint),
+        amount: decoded.amount,
+    };
+
+    // The verification key is used to verify that the proof corresponds to the execution of the
+    // program on the given input.
+    //
+    // Note that the verification key stays the same regardless of the input.
+    println!("Verification Key: {}", evm_fixture.vkey);
+
+    // The public values are the values which are publicly committed to by the zkVM.
+    println!("Public Values: {}", evm_fixture.public_values);
+
+    // The proof proves to the verifier that the program was executed with some inputs that led to
+    // the give public values.
+    println!("Proof Bytes: {}", evm_fixture.proof);
+
+    // Save the fixtures.
+    std::fs::create_dir_all(out_dir).expect("failed to create fixture path");
+    std::fs::write(
+        out_dir.join("solana-fixture.json"),
+        serde_json::to_string_pretty(&solana_fixture).unwrap(),
+    )
+    .expect("failed to write Solana fixture");
+    std::fs::write(
+        out_dir.join("evm-fixture.json"),
+        serde_json::to_string_pretty(&evm_fixture).unwrap(),
+    )
+    .expect("failed to write EVM fixture");
+    println!("Fixtures written to {}", out_dir.display());
+}
@@ -1,530 +1,527 @@
+//! The `mixer` command line: the proof API server and everything it does,
+//! from the terminal.
+//!
+//! ```shell
+//! cargo run --release                                  # serve, as `serve`
+//! cargo run --release -- note new --amount 0.1
+//! cargo run --release -- note decode --note solana-mixer-sol-...
+//! cargo run --release -- tree sync --pool sol-0.1 --out sol-0.1.snapshot
+//! cargo run --release -- tree proof --pool sol-0.1 --commitment 0x...
+//! cargo run --release -- prove --note ... --recipient ... --relayer ... --out proof.json
+//! cargo run --release -- verify proof.json
+//! cargo run --release -- vkey generate --out-dir artifacts
+//! ```
+//!
+//! Commands read the server's configuration from the environment and `.env`.
 use anchor_client::solana_sdk::pubkey::Pubkey;
-use axum::{
-    extract::ws::{Message, WebSocket, WebSocketUpgrade},
-    extract::Json,
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{get, post},
-    Router,
+use clap::{Parser, Subcommand, ValueEnum};
+use mixer_lib::mix::WithdrawalInputs;
+use mixer_lib::tree::MerklePath;
+use mixer_script::cli::{self, RemoteServer, VkeyCommand};
+use mixer_script::directory;
+use mixer_script::idl::EventDecoder;
+use mixer_script::indexer::{self, DepositIndex};
+use mixer_script::note::{format_units, Note};
+use mixer_script::pools::{PoolConfig, SOL_DECIMALS};
+use mixer_script::prover::{withdrawal_stdin, ProverBackend, ZeroizingStdin};
+use mixer_script::server::{
+    self, AppState, DepositDetails, GenerateDepositDetailsRequest, MerklePathResponse,
+    ProveResponse, ServerConfig,
 };
-use hex::{encode as hex_encode, FromHex};
-use num_bigint::BigUint;
-use regex::Regex;
-use serde::{Deserialize, Serialize};
-use sp1_sdk::{include_elf, HashableKey, Prover, ProverClient, SP1PublicValues, SP1Stdin};
-mod utils;
-use dotenv;
+use mixer_script::snapshot::{SnapshotFormat, TreeSnapshot};
+use mixer_script::solana::ProofFormat;
+use mixer_script::utils::to_hex32;
+use mixer_script::verify;
+use serde::Serialize;
 use serde_json::json;
-use solana_poseidon::{hashv, Endianness, Parameters};
-use std::str::FromStr;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::net::TcpListener;
-use utils::*;
 
-use std::{any::Any, net::SocketAddr};
-use tower::ServiceBuilder;
-use tower_http::{
-    catch_panic::{CatchPanic, CatchPanicLayer},
-    cors::CorsLayer,
-};
-
-use ark_bn254::Fr;
-use light_poseidon::{Poseidon, PoseidonBytesHasher};
-
-pub const MIXER_ELF: &[u8] = include_elf!("mixer-program");
-const MERKLE_LEVELS: usize = 20;
-
-#[derive(Deserialize, Serialize, Debug)]
-pub struct ProveRequest {
-    // ─── Public inputs ─────
-    pub root: String,           // hex, 32 bytes
-    pub nullifier_hash: String, // hex, 32 bytes
-    pub recipient: String,      // hex, 32 bytes (Solana Pubkey)
-    pub relayer: String,        // hex, 32 bytes
-    pub fee: u64,
-    pub refund: u64,
-    // ─── Private inputs ──────
-    pub nullifier: String, // hex, 32 bytes
-    pub secret: String,    // hex, 32 bytes
-    // ─── Merkle path ────────
-    pub path_elements: Vec<String>, // each hex, 32 bytes
-    pub path_indices: Vec<u8>,      // each 0 or 1
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ProveResponse {
-    pub proof: String, // hex-encoded Groth16 proof
-    pub public_inputs: SP1PublicValues,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the proof API server. The default without a command.
+    Serve {
+        #[arg(long, default_value = "0.0.0.0:3001")]
+        addr: SocketAddr,
+    },
+    /// Create and read deposit notes.
+    #[command(subcommand)]
+    Note(NoteCommand),
+    /// Rebuild a pool's Merkle tree from the chain.
+    #[command(subcommand)]
+    Tree(TreeCommand),
+    /// Prove the withdrawal of a note.
+    Prove(ProveArgs),
+    /// Verify a proof written by `prove`.
+    Verify {
+        file: PathBuf,
+        /// Program vkey hash, the current ELF's when unset.
+        #[arg(long)]
+        vkey_hash: Option<String>,
+    },
+    /// Print the program vkey hash, or write and check verifier artifacts.
+    Vkey {
+        #[command(subcommand)]
+        command: Option<VkeyCommand>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct ProveResponseCustom {
-    proof: String,
-    public_inputs: PublicInputsWrapper,
+#[derive(Subcommand, Debug)]
+enum NoteCommand {
+    /// Generate a note: `--amount` SOL, or `--units` of `--mint` or lamports.
+    New {
+        #[arg(long, conflicts_with_all = ["units", "mint"])]
+        amount: Option<f64>,
+        #[arg(long, required_unless_present = "amount")]
+        units: Option<u64>,
+        /// Base58 SPL token mint.
+        #[arg(long)]
+        mint: Option<String>,
+    },
+    /// Print a note's asset, amount, commitment and nullifier hash.
+    Decode {
+        #[command(flatten)]
+        note: NoteArg,
+        /// Also check the note against this configured pool.
+        #[arg(long)]
+        pool: Option<String>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct PublicInputsWrapper {
-    buffer: BufferData,
+#[derive(Subcommand, Debug)]
+enum TreeCommand {
+    /// Replay a pool's history and print its tree.
+    Sync {
+        #[command(flatten)]
+        pool: PoolArgs,
+        /// Also write the finalized tree as a snapshot.
+        #[arg(long)]
+        out: Option<PathBuf>,
+        #[arg(long, value_enum, default_value = "binary")]
+        format: SnapshotFormat,
+    },
+    /// Print the Merkle path of a deposit.
+    Proof {
+        #[command(flatten)]
+        pool: PoolArgs,
+        /// Hex commitment of the deposit.
+        #[arg(long)]
+        commitment: String,
+    },
+    /// Check a snapshot file's checksum and root, and print its tree.
+    Inspect { file: PathBuf },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct BufferData {
-    data: Vec<u8>,
+#[derive(clap::Args, Debug)]
+struct ProveArgs {
+    #[command(flatten)]
+    note: NoteArg,
+    /// Wallet the withdrawal pays.
+    #[arg(long)]
+    recipient: Pubkey,
+    /// Wallet the fee pays.
+    #[arg(long)]
+    relayer: Pubkey,
+    #[arg(long, default_value_t = 0)]
+    fee: u64,
+    #[arg(long, default_value_t = 0)]
+    refund: u64,
+    #[command(flatten)]
+    pool: PoolArgs,
+    /// Prove here, from the chain, or on a prover server, which only gets
+    /// the nullifier and secret sealed to its input key.
+    #[arg(long, value_enum, default_value = "local")]
+    backend: Backend,
+    /// Prover server for `--backend remote`.
+    #[arg(
+        long,
+        env = "MIXER_SERVER_URL",
+        default_value = "http://localhost:3001"
+    )]
+    server: String,
+    /// Prover for `--backend local`, `SP1_PROVER` when unset.
+    #[arg(long, value_enum)]
+    prover: Option<ProverBackend>,
+    #[arg(long, value_enum, default_value = "sp1")]
+    format: ProofFormat,
+    /// Only execute the program and print its cycles and public values.
+    #[arg(long)]
+    dry_run: bool,
+    /// Write the proof to a file instead of printing it.
+    #[arg(long)]
+    out: Option<PathBuf>,
 }
 
-async fn prove_mix(Json(req): Json<ProveRequest>) -> impl IntoResponse {
-    // Parse & validate all the hex inputs
-    macro_rules! hex32 {
-
-    ([ $($byte:expr),* ]) => {{
-
-        let arr: [u8;32] = [ $($byte),* ];
-        arr
-    }};
-    ($s:expr) => {{
-        let s: &str = &$s;
-        let s = s.strip_prefix("0x").unwrap_or(s);
-        let vec = Vec::from_hex(s)
-            .map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid hex “{}”: {}", s, e)))?;
-        if vec.len() != 32 {
-            return Err((StatusCode::BAD_REQUEST, format!("hex length != 32: “{}”", s)));
-        }
-        let mut arr = [0u8;32];
-        arr.copy_from_slice(&vec);
-        arr
-    }};
-}
-
-    let root_arr = hex32!(req.root);
-    let null_hash_arr = hex32!(req.nullifier_hash);
-    let recipient_arr = hex32!(req.recipient);
-    let relayer_arr = hex32!(req.relayer);
-    let nullifier_arr = hex32!(req.nullifier);
-    let secret_arr = hex32!(req.secret);
-
-    eprintln!(
-        "Decoded value: {:?}",
-        [
-            root_arr,
-            null_hash_arr,
-            recipient_arr,
-            relayer_arr,
-            nullifier_arr,
-            secret_arr
-        ]
-    );
-
-    if req.path_elements.len() != MERKLE_LEVELS || req.path_indices.len() != MERKLE_LEVELS {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            format!(
-                "Expected {} path elements & indices, got {} elems and {} idxs",
-                MERKLE_LEVELS,
-                req.path_elements.len(),
-                req.path_indices.len()
-            ),
-        ));
-    }
-    let mut path_elems = [[0u8; 32]; MERKLE_LEVELS];
-    for (i, hexstr) in req.path_elements.iter().enumerate() {
-        path_elems[i] = hex32!(hexstr);
-    }
-    let path_idxs = req.path_indices.clone();
-
-    sp1_sdk::utils::setup_logger();
-    dotenv::dotenv().ok();
-
-    let sp1_private_key_env = std::env::var("NETWORK_PRIVATE_KEY_SP1");
-    let sp1_private_key = sp1_private_key_env.unwrap().to_string();
-
-    let sp1_rpc_url = "https://rpc.production.succinct.xyz";
-
-    let client = ProverClient::builder()
-        .network()
-        .private_key(&sp1_private_key)
-        .rpc_url(sp1_rpc_url)
-        .build();
-
-    let mut stdin = SP1Stdin::new();
-
-    // Write inputs in the exact order the circuit reads them:
-    stdin.write(&root_arr);
-    stdin.write(&null_hash_arr);
-    stdin.write(&recipient_arr);
-    stdin.write(&relayer_arr);
-    stdin.write(&req.fee);
-    stdin.write(&req.refund);
-
-    stdin.write(&nullifier_arr);
-    stdin.write(&secret_arr);
-
-    let elem: [[u8; 32]; MERKLE_LEVELS] = path_elems;
-    stdin.write(&elem);
-
-    let idx_arr: [u8; MERKLE_LEVELS] = path_idxs.try_into().expect("wrong number of indices");
-    stdin.write(&idx_arr);
-
-    let (pk, vk) = client.setup(MIXER_ELF);
-    let proof = match client.prove(&pk, &stdin).groth16().run() {
-        Ok(p) => p,
-        Err(e) => {
-            let msg = format!("❌ proof generation failed: {}", e);
-            return Err((StatusCode::INTERNAL_SERVER_ERROR, msg));
-        }
-    };
-    /* if let Err(e) = client.verify(&proof, &vk) {
-        let msg = format!("❌ proof verification failed: {}", e);
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, msg));
-    }*/
-
-    let proof_hex = hex_encode(proof.bytes());
-    Ok((
-        StatusCode::OK,
-        Json(ProveResponse {
-            proof: proof_hex,
-            public_inputs: proof.public_values,
-        }),
-    ))
+#[derive(clap::Args, Debug)]
+struct NoteArg {
+    /// The deposit note. Set `MIXER_NOTE` instead to keep it out of the shell
+    /// history.
+    #[arg(long, env = "MIXER_NOTE", hide_env_values = true)]
+    note: String,
 }
 
-fn notmain() {
-    let prover = ProverClient::builder().cpu().build();
-    let (_, vk) = prover.setup(MIXER_ELF);
-    eprintln!("VK key {}", vk.bytes32());
+#[derive(clap::Args, Debug)]
+struct PoolArgs {
+    /// Pool id. May be omitted when only one pool is configured.
+    #[arg(long)]
+    pool: Option<String>,
+    /// Tree snapshot to replay from, the pool's `snapshot` when unset.
+    #[arg(long)]
+    snapshot: Option<PathBuf>,
 }
 
-#[derive(Serialize)]
-pub struct DepositDetails {
-    pub nullifier: String,
-    pub secret: String,
-    pub note: String,
-    pub commitment: [u8; 32],
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Backend {
+    Local,
+    Remote,
 }
 
-#[derive(Deserialize)]
-pub struct GenerateDepositDetailsRequest {
-    pub amount: f64,
+/// Prints `e` and exits non-zero.
+fn fail(e: impl std::fmt::Display) -> ! {
+    eprintln!("{}", e);
+    std::process::exit(1)
 }
 
-async fn generate_deposit_details(
-    Json(req): Json<GenerateDepositDetailsRequest>,
-) -> (StatusCode, Json<DepositDetails>) {
-    let (nullifier, secret, preimage, commitment, nullifier_hash) =
-        mixer_lib::utils::create_random_commitment();
-
-    // build the note string as: solana-mixer-1-<nullifierHex>:<secretHex>
-    let note = format!(
-        "solana-mixer-{}-{}:{}",
-        req.amount,
-        hex::encode(nullifier.to_bytes_le()),
-        hex::encode(secret.to_bytes_le())
-    );
-
-    println!("note = {}", note);
-
-    (
-        StatusCode::OK,
-        Json(DepositDetails {
-            nullifier: hex::encode(nullifier.to_bytes_le()),
-            secret: hex::encode(secret.to_bytes_le()),
-            note,
-            commitment,
-        }),
-    )
+fn print_json(value: &impl Serialize) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
 }
 
-#[derive(Deserialize)]
-pub struct DecodeNoteDetailsRequest {
-    pub note: String,
-    pub program_pubkey: String,
+fn config() -> ServerConfig {
+    ServerConfig::from_env()
+        .unwrap_or_else(|e| fail(format!("invalid server configuration: {}", e)))
 }
 
-#[derive(Serialize)]
-pub struct DecodeNoteDetailsResponse {
-    pub nullifier_str: String,
-    pub secret_str: String,
-    pub amount: f64,
-    pub state_pubkey: String,
+fn pool_configs(config: &ServerConfig) -> Vec<PoolConfig> {
+    let pools = config
+        .pools()
+        .unwrap_or_else(|e| fail(format!("invalid pool configuration: {}", e)));
+    pools.iter().map(|p| p.config.clone()).collect()
 }
 
-async fn decode_note_details(Json(req): Json<DecodeNoteDetailsRequest>) -> impl IntoResponse {
-    let re = Regex::new(
-        r"^solana-mixer-(?P<amount>\d+(?:\.\d+)?)-(?P<nullifier>[0-9A-Fa-f]+):(?P<secret>[0-9A-Fa-f]+)$"
-    ).unwrap();
-
-    let caps = match re.captures(&req.note) {
-        Some(c) => c,
-        None => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(DecodeNoteDetailsResponse {
-                    nullifier_str: "".to_string(),
-                    secret_str: "".to_string(),
-                    amount: 0.0,
-                    state_pubkey: "".to_string(),
-                }),
-            );
-        }
-    };
-
-    let amt: f64 = caps.name("amount").unwrap().as_str().parse().unwrap();
-
-    let nullifier_bytes = hex::decode(caps.name("nullifier").unwrap().as_str()).unwrap();
-    let secret_bytes = hex::decode(caps.name("secret").unwrap().as_str()).unwrap();
-
-    let nullifier_bn = BigUint::from_bytes_le(&nullifier_bytes);
-    let secret_bn = BigUint::from_bytes_le(&secret_bytes);
-
-    let nullifier_str = nullifier_bn.to_string();
-    let secret_str = secret_bn.to_string();
-
-    let state_pubkey = utils::get_pubkeys_utils(req.program_pubkey);
-
-    (
-        StatusCode::OK,
-        Json(DecodeNoteDetailsResponse {
-            nullifier_str,
-            secret_str,
-            amount: amt,
-            state_pubkey: state_pubkey.to_string(),
-        }),
-    )
+/// Replays the history of the pool picked by `args`.
+async fn sync(config: &ServerConfig, pool: &PoolConfig, args: &PoolArgs) -> DepositIndex {
+    let snapshot = args
+        .snapshot
+        .as_deref()
+        .or(pool.snapshot.as_deref().map(Path::new));
+    cli::sync_pool(config, pool, snapshot)
+        .await
+        .unwrap_or_else(|e| fail(format!("pool {}: {}", pool.id, e)))
 }
 
-async fn get_pubkeys(program_pubkey: String) -> String {
-    utils::get_pubkeys_utils(program_pubkey)
+fn print_tree(snapshot: &TreeSnapshot) {
+    println!("program_id:     {}", snapshot.program_id);
+    println!("next_index:     {}", snapshot.next_index());
+    println!("root:           {}", to_hex32(&snapshot.root));
+    println!(
+        "last_signature: {}",
+        snapshot.last_signature.as_deref().unwrap_or("-")
+    );
+    println!("nullifiers:     {}", snapshot.nullifier_hashes.len());
+    println!("checksum:       {}", hex::encode(snapshot.checksum()));
 }
 
-async fn ws_compute_proof_withdrawal(ws: WebSocketUpgrade) -> impl IntoResponse {
-    ws.on_upgrade(handle_ws)
-}
-#[derive(Deserialize, Debug)]
-pub struct WithdrawalComputeRequest {
-    pub nullifier: String,
-    pub secret: String,
-    pub rpc_url: String,
-    pub program_pubkey: String,
-    pub new_withdrawal_recipient_address: String,
-    pub new_relayer_address: String,
-    pub server_url: String,
+/// Starts live deposit indexing for `pool`.
+fn start_indexer(
+    config: &ServerConfig,
+    pool: &PoolConfig,
+    events: Arc<EventDecoder>,
+) -> Arc<DepositIndex> {
+    let ws_url = config.pool_pubsub_url(pool).unwrap_or_else(|| {
+        fail(format!(
+            "pool {} needs a cluster, SOLANA_RPC_URLS or SOLANA_WS_URL",
+            pool.id
+        ))
+    });
+    let source = config
+        .pool_source(pool)
+        .unwrap_or_else(|e| fail(format!("pool {}: {}", pool.id, e)));
+
+    let index = Arc::new(
+        DepositIndex::new(pool.program_pubkey())
+            .with_min_commitment(config.proof_commitment)
+            .with_events(events),
+    );
+    if let Some(path) = &pool.snapshot {
+        let snapshot = TreeSnapshot::read(Path::new(path))
+            .and_then(|snapshot| index.restore(&snapshot).map(|_| snapshot))
+            .unwrap_or_else(|e| fail(format!("pool {}: {}", pool.id, e)));
+        println!(
+            "Restored {} leaves of pool {} from {}",
+            snapshot.next_index(),
+            pool.id,
+            path
+        );
+    }
+    println!(
+        "Indexing deposits of pool {} ({}) via {}",
+        pool.id, pool.program_id, ws_url
+    );
+    tokio::spawn(indexer::run_log_subscription(
+        index.clone(),
+        ws_url,
+        Arc::new(source),
+    ));
+    index
 }
 
-async fn handle_ws(mut socket: WebSocket) {
-    let msg = match socket.recv().await {
-        Some(Ok(Message::Text(txt))) => txt,
-        _ => return,
-    };
-    let mut req: WithdrawalComputeRequest = match serde_json::from_str(&msg) {
-        Ok(r) => r,
-        Err(e) => {
-            let _ = socket
-                .send(Message::Text(
-                    json!({
-                        "error": format!("Invalid request JSON: {}", e)
-                    })
-                    .to_string(),
-                ))
-                .await;
-            return;
-        }
-    };
-    if req.server_url.is_empty() {
-        req.server_url = "http://localhost:3001".to_string();
+async fn serve(addr: SocketAddr) {
+    sp1_sdk::utils::setup_logger();
+    let vkey = cli::vkey_hash();
+    eprintln!("VK key {}", vkey);
+
+    let config = config();
+    let mut pools = config
+        .pools()
+        .unwrap_or_else(|e| fail(format!("invalid pool configuration: {}", e)));
+    pools
+        .check_vkey(&vkey)
+        .unwrap_or_else(|e| fail(format!("pool vkey mismatch: {}", e)));
+    for pool in pools.iter_mut() {
+        let events = config
+            .load_events(Some(&pool.config))
+            .await
+            .unwrap_or_else(|e| fail(format!("failed to load the mixer IDL: {}", e)));
+        pool.index = Some(start_indexer(&config, &pool.config, events));
     }
-    let nullifier_bn = BigUint::from_str(&req.nullifier).unwrap();
-    let secret_bn = BigUint::from_str(&req.secret).unwrap();
-    let nullifier_bytes = nullifier_bn.to_bytes_le();
-    let secret_bytes = secret_bn.to_bytes_le();
-    let commitment = hashv(
-        Parameters::Bn254X5,
-        Endianness::LittleEndian,
-        &[&nullifier_bytes, &secret_bytes],
-    )
-    .unwrap()
-    .to_bytes();
-
-    let nullifier_hash = hashv(
-        Parameters::Bn254X5,
-        Endianness::LittleEndian,
-        &[&nullifier_bytes],
-    )
-    .unwrap()
-    .to_bytes();
+    let events = config
+        .load_events(None)
+        .await
+        .unwrap_or_else(|e| fail(format!("failed to load the mixer IDL: {}", e)));
+
+    let relayer = config
+        .relayer()
+        .unwrap_or_else(|e| fail(format!("invalid relayer configuration: {}", e)));
+    let directory = config
+        .relayer_directory()
+        .unwrap_or_else(|e| fail(format!("invalid relayer directory: {}", e)));
+    let check_interval = config.relayer_check_interval;
+    let input_key = config
+        .input_key()
+        .unwrap_or_else(|e| fail(format!("invalid input key: {}", e)));
+    let prover = config.prover;
+    let mut state = AppState::new(config, events, Arc::new(pools))
+        .with_input_key(input_key)
+        .with_vkey_hash(&vkey);
+    if let Some(relayer) = relayer {
+        println!("Relaying withdrawals as {}", relayer.pubkey());
+        state = state.with_relayer(relayer);
+    }
+    if let Some(directory) = directory {
+        let directory = Arc::new(directory);
+        println!("Serving a directory of {} relayers", directory.len());
+        tokio::spawn(directory::run_health_checks(
+            directory.clone(),
+            check_interval,
+        ));
+        state = state.with_directory(directory);
+    }
+    let app = server::app(state);
 
     println!(
-        "commitment: {:?}\nnullifier_hash: {:?}",
-        commitment, nullifier_hash
+        "Starting proof API server on {} ({:?} prover)",
+        addr, prover
     );
 
-    let (all_commits, _entries, _indices, leaf_index, found) =
-        match utils::fetch_deposits(commitment, &req.rpc_url, &req.program_pubkey) {
-            Ok(r) => r,
-            Err(e) => {
-                let _ = socket
-                    .send(Message::Text(
-                        json!({
-                            "error": format!("fetch_deposits failed: {}", e)
-                        })
-                        .to_string(),
-                    ))
-                    .await;
-                return;
-            }
-        };
+    let listener = TcpListener::bind(addr)
+        .await
+        .unwrap_or_else(|e| fail(format!("binding {}: {}", addr, e)));
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap_or_else(|e| fail(e));
+}
 
-    if !found {
-        let _ = socket
-            .send(Message::Text(
-                json!({
-                    "error": "Commitment not found in on-chain history"
-                })
-                .to_string(),
-            ))
-            .await;
-        return;
+fn note(command: NoteCommand) {
+    match command {
+        NoteCommand::New {
+            amount,
+            units,
+            mint,
+        } => {
+            let (mint, amount) = GenerateDepositDetailsRequest {
+                amount,
+                units,
+                mint,
+            }
+            .asset()
+            .unwrap_or_else(|e| fail(e));
+            print_json(&DepositDetails::new(&Note::random(mint, amount)));
+        }
+        NoteCommand::Decode { note, pool } => {
+            let note = Note::parse(&note.note).unwrap_or_else(|e| fail(e));
+            let decimals = match &pool {
+                Some(id) => {
+                    let pools = pool_configs(&config());
+                    let pool =
+                        cli::select_pool(&pools, Some(id), Some(&note)).unwrap_or_else(|e| fail(e));
+                    pool.decimals
+                }
+                None if note.mint.is_none() => SOL_DECIMALS,
+                None => 0,
+            };
+            let (commitment, nullifier_hash) = note.hashes();
+            print_json(&json!({
+                "mint": note.mint.map(|m| m.to_string()),
+                "units": note.amount,
+                "amount": format_units(note.amount, decimals),
+                "commitment": to_hex32(&commitment),
+                "nullifier_hash": to_hex32(&nullifier_hash),
+            }));
+        }
     }
+}
 
-    let (siblings, path_indices, root) =
-        utils::compute_merkle_proof::<20>(&all_commits, leaf_index);
-
-    let siblings_array: [[u8; 32]; 20] = siblings;
-    let path_indices_array: [u8; 20] = path_indices;
-    utils::merkle_check::<20>(root, commitment, &siblings_array, &path_indices_array);
-    println!("Root: {:?}", root);
-    let root: [u8; 32] = root;
-    let nullifier_hash: [u8; 32] = nullifier_hash;
-    let recipient: [u8; 32] = Pubkey::from_str(&req.new_withdrawal_recipient_address)
-        .unwrap()
-        .to_bytes();
-    let relayer: [u8; 32] = Pubkey::from_str(&req.new_relayer_address)
-        .unwrap()
-        .to_bytes();
-
-    let fee = 0;
-    let refund = 0;
-    let nullifier: BigUint = nullifier_bn;
-    let secret: BigUint = secret_bn;
-    let path_elems: Vec<[u8; 32]> = siblings.to_vec();
-    let path_inds: Vec<u8> = path_indices.to_vec();
+async fn tree(command: TreeCommand) {
+    match command {
+        TreeCommand::Sync { pool, out, format } => {
+            let config = config();
+            let pools = pool_configs(&config);
+            let selected =
+                cli::select_pool(&pools, pool.pool.as_deref(), None).unwrap_or_else(|e| fail(e));
+            let index = sync(&config, selected, &pool).await;
+            let stats = index.stats();
+            let snapshot = index.snapshot();
+            print_tree(&snapshot);
+            println!("deposits:       {}", stats.deposits);
+            println!("withdrawals:    {}", stats.withdrawals);
+            if let Some(out) = out {
+                snapshot.write(&out, format).unwrap_or_else(|e| fail(e));
+                eprintln!("Snapshot written to {}", out.display());
+            }
+        }
+        TreeCommand::Proof { pool, commitment } => {
+            let commitment = hex::decode(commitment.strip_prefix("0x").unwrap_or(&commitment))
+                .ok()
+                .and_then(|c| <[u8; 32]>::try_from(c).ok())
+                .unwrap_or_else(|| fail("commitment must be 32 bytes of hex"));
+            let config = config();
+            let pools = pool_configs(&config);
+            let selected =
+                cli::select_pool(&pools, pool.pool.as_deref(), None).unwrap_or_else(|e| fail(e));
+            let index = sync(&config, selected, &pool).await;
+            let path = cli::merkle_path(&index, &commitment).unwrap_or_else(|e| fail(e));
+            print_json(&MerklePathResponse::new(&selected.id, &commitment, &path));
+        }
+        TreeCommand::Inspect { file } => match TreeSnapshot::read(&file) {
+            Ok(snapshot) => print_tree(&snapshot),
+            Err(e) => fail(e),
+        },
+    }
+}
 
-    let prove_req = ProveRequest {
-        root: to_hex32(&root),
-        nullifier_hash: to_hex32(&nullifier_hash),
-        recipient: to_hex32(&recipient),
-        relayer: to_hex32(&relayer),
+async fn prove(args: ProveArgs) {
+    let ProveArgs {
+        note,
+        recipient,
+        relayer,
         fee,
         refund,
-        nullifier: to_hex32(&biguint_to_32_le_bytes(&nullifier)),
-        secret: to_hex32(&biguint_to_32_le_bytes(&secret)),
-        path_elements: to_hex_vec(&path_elems),
-        path_indices: path_inds,
+        pool,
+        backend,
+        server,
+        prover,
+        format,
+        dry_run,
+        out,
+    } = args;
+    let note = Note::parse(&note.note).unwrap_or_else(|e| fail(e));
+    let payout = cli::payout(&note, &recipient, &relayer, fee, refund);
+    let (commitment, _) = note.hashes();
+    let for_note = |path: MerklePath| {
+        WithdrawalInputs::for_note(note.nullifier.clone(), note.secret.clone(), &path, payout)
+            .unwrap_or_else(|e| fail(e))
     };
 
-    let client = reqwest::Client::new();
-    let resp = match client
-        .post(format!("{}/api/prove-mix", req.server_url))
-        .json(&prove_req)
-        .send()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            let _ = socket
-                .send(Message::Text(
-                    json!({
-                        "error": format!("Prover HTTP request failed: {}", e)
-                    })
-                    .to_string(),
-                ))
-                .await;
-            return;
-        }
-    };
-    let resp_text = match resp.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            let _ = socket
-                .send(Message::Text(
-                    json!({
-                        "error": format!("Reading prover response failed: {}", e)
-                    })
-                    .to_string(),
-                ))
-                .await;
-            return;
+    let proof: ProveResponse = match backend {
+        Backend::Local => {
+            let mut config = config();
+            if let Some(prover) = prover {
+                config.prover = prover;
+            }
+            let pools = pool_configs(&config);
+            let selected = cli::select_pool(&pools, pool.pool.as_deref(), Some(&note))
+                .unwrap_or_else(|e| fail(e));
+            let index = sync(&config, selected, &pool).await;
+            let path = cli::merkle_path(&index, &commitment).unwrap_or_else(|e| fail(e));
+            let inputs = for_note(path);
+            if dry_run {
+                let report = server::execute_withdrawal(&inputs).unwrap_or_else(|(_, e)| fail(e));
+                return print_json(&report);
+            }
+            let stdin = ZeroizingStdin(withdrawal_stdin(&inputs));
+            drop(inputs);
+            eprintln!("Proving with the {:?} prover", config.prover);
+            server::prove(&config, &stdin, format).unwrap_or_else(|(_, e)| fail(e))
         }
-    };
-
-    let prove_resp: ProveResponseCustom = match serde_json::from_str(&resp_text) {
-        Ok(r) => r,
-        Err(e) => {
-            let _ = socket
-                .send(Message::Text(
-                    json!({
-                        "error": format!("Invalid prover response JSON: {}", e)
-                    })
-                    .to_string(),
-                ))
-                .await;
-            return;
+        Backend::Remote => {
+            let remote = RemoteServer::new(&server);
+            let pools = remote.pools().await.unwrap_or_else(|e| fail(e));
+            let selected = cli::select_pool(&pools, pool.pool.as_deref(), Some(&note))
+                .unwrap_or_else(|e| fail(e));
+            let path = remote
+                .merkle_path(&selected.id, &commitment)
+                .await
+                .unwrap_or_else(|e| fail(e));
+            let inputs = for_note(path);
+            if dry_run {
+                let report = remote.dry_run(&inputs).await.unwrap_or_else(|e| fail(e));
+                return print_json(&report);
+            }
+            eprintln!("Proving on {}", server);
+            remote
+                .prove(&inputs, format)
+                .await
+                .unwrap_or_else(|e| fail(e))
         }
     };
-
-    let proof_bytes = match hex::decode(&prove_resp.proof) {
-        Ok(b) => b,
-        Err(e) => {
-            let _ = socket
-                .send(Message::Text(
-                    json!({
-                        "error": format!("Invalid proof hex: {}", e)
-                    })
-                    .to_string(),
-                ))
-                .await;
-            return;
+    match out {
+        Some(out) => {
+            std::fs::write(&out, serde_json::to_vec_pretty(&proof).unwrap())
+                .unwrap_or_else(|e| fail(format!("writing {}: {}", out.display(), e)));
+            eprintln!("Proof written to {}", out.display());
         }
-    };
-    let public_inputs = prove_resp.public_inputs.buffer.data;
-
-    let result = json!({
-        "proof_bytes": &proof_bytes,
-        "public_inputs": public_inputs,
-    });
-    let _ = socket.send(Message::Text(result.to_string())).await;
+        None => print_json(&proof),
+    }
+}
 
-    let _ = socket.close().await;
+fn verify(file: PathBuf, vkey_hash: Option<String>) {
+    let proof: ProveResponse = std::fs::read(&file)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| fail(format!("reading {}: {}", file.display(), e)));
+    let bytes = hex::decode(proof.proof.strip_prefix("0x").unwrap_or(&proof.proof))
+        .unwrap_or_else(|e| fail(format!("invalid proof hex: {}", e)));
+    let vkey_hash = vkey_hash.unwrap_or_else(cli::vkey_hash);
+    match verify::verify_groth16(&bytes, proof.public_inputs.as_slice(), &vkey_hash) {
+        Ok(()) => println!("valid"),
+        Err(e) => fail(format!("invalid: {}", e)),
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let cors = CorsLayer::new()
-        .allow_origin(tower_http::cors::Any)
-        .allow_methods(tower_http::cors::Any)
-        .allow_headers(tower_http::cors::Any);
-    notmain();
-
-    let panic_layer = CatchPanicLayer::new();
-
-    let app = Router::new()
-        .route("/api/prove-mix", post(prove_mix))
-        .route(
-            "/api/generate-deposit-details",
-            post(generate_deposit_details),
-        )
-        .route("/api/decode-note-details", post(decode_note_details))
-        .route("/api/get-pubkeys", get(get_pubkeys))
-        .route("/ws/compute_withdrawal", get(ws_compute_proof_withdrawal))
-        .layer(
-            ServiceBuilder::new()
-                .layer(panic_layer) // catches any downstream panic → 500
-                .layer(cors),
-        );
-
-    let addr: SocketAddr = "0.0.0.0:3001".parse().unwrap();
-    println!("Starting proof API server on {}", addr);
-
-    let listener = TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+    dotenv::dotenv().ok();
+    let args = Args::parse();
+
+    match args.command {
+        None => serve("0.0.0.0:3001".parse().unwrap()).await,
+        Some(Command::Serve { addr }) => serve(addr).await,
+        Some(Command::Note(command)) => note(command),
+        Some(Command::Tree(command)) => tree(command).await,
+        Some(Command::Prove(args)) => prove(args).await,
+        Some(Command::Verify { file, vkey_hash }) => verify(file, vkey_hash),
+        Some(Command::Vkey { command }) => cli::vkey(command).unwrap_or_else(|e| fail(e)),
+    }
 }
@@ -1,10 +1,24 @@
-use sp1_sdk::{include_elf, HashableKey, Prover, ProverClient};
+//! Verifier artifact generator for `mixer-program`, the same as `mixer vkey`.
+//!
+//! ```shell
+//! cargo run --release --bin vkey                      # print the vkey hash
+//! cargo run --release --bin vkey -- generate --out-dir artifacts
+//! cargo run --release --bin vkey -- check --manifest artifacts/manifest.json
+//! ```
+use clap::Parser;
+use mixer_script::cli::{self, VkeyCommand};
 
-/// The ELF (executable and linkable format) file for the Succinct RISC-V zkVM.
-pub const MIXER_ELF: &[u8] = include_elf!("mixer-program");
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<VkeyCommand>,
+}
 
 fn main() {
-    let prover = ProverClient::builder().cpu().build();
-    let (_, vk) = prover.setup(MIXER_ELF);
-    eprintln!("VK key {}", vk.bytes32());
+    let args = Args::parse();
+    if let Err(e) = cli::vkey(args.command) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
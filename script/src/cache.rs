@@ -0,0 +1,152 @@
+//! Cache of finished proofs, so that a client retrying the same withdrawal
+//! (e.g. after its WebSocket dropped) does not pay for a second proof.
+//!
+//! Entries are keyed by the SHA-256 of the serialized `SP1Stdin`, which covers
+//! the private inputs without keeping them: only the digest and the public
+//! response are stored.
+
+use mixer_lib::mix::PublicValues;
+use sha2::{Digest, Sha256};
+use sp1_sdk::SP1Stdin;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+use crate::server::ProveResponse;
+use crate::solana::ProofFormat;
+
+pub type CacheKey = [u8; 32];
+
+struct Entry {
+    public_values: PublicValues,
+    response: ProveResponse,
+    created: Instant,
+}
+
+/// Bounded, expiring map from input digest to [`ProveResponse`].
+pub struct ProofCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<CacheKey, Entry>>,
+    /// One lock per key being proved, so concurrent retries wait for the
+    /// first proof instead of starting their own.
+    inflight: Mutex<HashMap<CacheKey, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl ProofCache {
+    /// A cache of up to `capacity` proofs, each kept for `ttl`. A capacity of
+    /// `0` disables caching.
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        ProofCache {
+            ttl,
+            capacity,
+            entries: Mutex::default(),
+            inflight: Mutex::default(),
+        }
+    }
+
+    /// Digest of the program inputs and the requested proof format.
+    pub fn key(stdin: &SP1Stdin, format: ProofFormat) -> CacheKey {
+        let serialized = Zeroizing::new(bincode::serialize(stdin).expect("SP1Stdin serializes"));
+        let mut hasher = Sha256::new();
+        hasher.update(&*serialized);
+        hasher.update([format as u8]);
+        hasher.finalize().into()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The cached response for `key`, if it is fresh and was proved for the
+    /// same public values.
+    pub fn get(&self, key: &CacheKey, public_values: &PublicValues) -> Option<ProveResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+        if entry.created.elapsed() >= self.ttl {
+            entries.remove(key);
+            return None;
+        }
+        (entry.public_values == *public_values).then(|| entry.response.clone())
+    }
+
+    /// Stores a proof, evicting expired entries and then the oldest ones to
+    /// stay within the capacity.
+    pub fn insert(&self, key: CacheKey, public_values: PublicValues, response: ProveResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, e| e.created.elapsed() < self.ttl);
+        while entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, e)| e.created)
+                .map(|(k, _)| *k)
+                .unwrap();
+            entries.remove(&oldest);
+        }
+        entries.insert(
+            key,
+            Entry {
+                public_values,
+                response,
+                created: Instant::now(),
+            },
+        );
+    }
+
+    /// Returns the cached response for `key` or runs `prove` and caches its
+    /// result. Callers with the same key wait for a running proof. The flag
+    /// tells whether the response came from the cache.
+    pub async fn get_or_prove<F, Fut, E>(
+        &self,
+        key: CacheKey,
+        public_values: PublicValues,
+        prove: F,
+    ) -> Result<(ProveResponse, bool), E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<ProveResponse, E>>,
+    {
+        if self.capacity == 0 {
+            return prove().await.map(|r| (r, false));
+        }
+        if let Some(hit) = self.get(&key, &public_values) {
+            return Ok((hit, true));
+        }
+        let lock = self
+            .inflight
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .clone();
+        let _guard = lock.lock().await;
+        if let Some(hit) = self.get(&key, &public_values) {
+            return Ok((hit, true));
+        }
+        let result = prove().await;
+        if let Ok(response) = &result {
+            self.insert(key, public_values, response.clone());
+        }
+        self.inflight.lock().unwrap().remove(&key);
+        result.map(|r| (r, false))
+    }
+}
+
+impl std::fmt::Debug for ProofCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProofCache")
+            .field("ttl", &self.ttl)
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
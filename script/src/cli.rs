@@ -0,0 +1,281 @@
+//! What the `mixer` command line does besides serving: picking a pool,
+//! syncing a pool's tree from the chain, proving through a remote server and
+//! managing verifier artifacts.
+//!
+//! Local commands reuse the server's handlers' building blocks, so a proof
+//! made from the terminal is the one `/api/prove-mix` would return.
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use clap::Subcommand;
+use mixer_lib::mix::{Payout, WithdrawalInputs};
+use mixer_lib::tree::MerklePath;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sp1_sdk::{HashableKey, Prover, ProverClient};
+use sp1_verifier::GROTH16_VK_BYTES;
+use spl_associated_token_account_client::address::get_associated_token_address;
+use std::path::{Path, PathBuf};
+
+use crate::artifacts::{self, VerifierManifest, MANIFEST_FILE};
+use crate::indexer::DepositIndex;
+use crate::note::Note;
+use crate::pools::PoolConfig;
+use crate::sealed::{PrivateInputs, SealedInputs};
+use crate::server::{
+    DryRunResponse, InputKeyResponse, MerklePathResponse, ProveRequest, ProveResponse,
+    ServerConfig, MIXER_ELF,
+};
+use crate::snapshot::TreeSnapshot;
+use crate::solana::ProofFormat;
+use crate::utils::{to_hex32, to_hex_vec};
+
+/// The pool named `id`. Without one, the only pool, or else the only pool
+/// that takes `note`. A named pool must take `note` too.
+pub fn select_pool<'a>(
+    pools: &'a [PoolConfig],
+    id: Option<&str>,
+    note: Option<&Note>,
+) -> Result<&'a PoolConfig, String> {
+    let pool = match id {
+        Some(id) => pools
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| format!("unknown pool {}", id))?,
+        None if pools.len() == 1 => &pools[0],
+        None => {
+            let mut fitting = pools
+                .iter()
+                .filter(|p| note.is_some_and(|note| p.check_note(note).is_ok()));
+            match (fitting.next(), fitting.next()) {
+                (Some(pool), None) => pool,
+                _ => return Err("pass --pool to pick one of the configured pools".to_string()),
+            }
+        }
+    };
+    if let Some(note) = note {
+        pool.check_note(note)?;
+    }
+    Ok(pool)
+}
+
+/// Replays `pool`'s history into a fresh index, starting after `snapshot`
+/// when one is given.
+pub async fn sync_pool(
+    config: &ServerConfig,
+    pool: &PoolConfig,
+    snapshot: Option<&Path>,
+) -> Result<DepositIndex, String> {
+    let source = config.pool_source(pool)?;
+    let events = config.load_events(Some(pool)).await?;
+    let index = DepositIndex::new(pool.program_pubkey())
+        .with_min_commitment(config.proof_commitment)
+        .with_events(events);
+    if let Some(path) = snapshot {
+        index.restore(&TreeSnapshot::read(path)?)?;
+    }
+    index.backfill(&source).await?;
+    Ok(index)
+}
+
+/// The Merkle path of `commitment` in `index`.
+pub fn merkle_path(index: &DepositIndex, commitment: &[u8; 32]) -> Result<MerklePath, String> {
+    let (leaves, leaf_index) = index.locate(commitment)?.ok_or_else(|| {
+        format!(
+            "commitment {} not found in the history of {}",
+            to_hex32(commitment),
+            index.program_id()
+        )
+    })?;
+    Ok(MerklePath::new(&leaves, leaf_index))
+}
+
+/// What withdrawing `note` to the `recipient` and `relayer` wallets pays out.
+/// Token notes pay the wallets' associated token accounts, as the server's
+/// withdrawals do.
+pub fn payout(note: &Note, recipient: &Pubkey, relayer: &Pubkey, fee: u64, refund: u64) -> Payout {
+    let account = |wallet: &Pubkey| match &note.mint {
+        Some(mint) => get_associated_token_address(wallet, mint).to_bytes(),
+        None => wallet.to_bytes(),
+    };
+    Payout {
+        recipient: account(recipient),
+        relayer: account(relayer),
+        fee,
+        refund,
+        mint: note.mint_bytes(),
+        amount: note.amount,
+    }
+}
+
+/// What `vkey` does besides printing the vkey hash. Shared by `mixer vkey`
+/// and the `vkey` binary.
+#[derive(Subcommand, Debug)]
+pub enum VkeyCommand {
+    /// Write the vkey hash, ELF hash, SP1 version, Solana/Solidity verifying keys
+    /// and a JSON manifest into a directory.
+    Generate {
+        #[arg(long, default_value = "artifacts")]
+        out_dir: PathBuf,
+    },
+    /// Check that an existing manifest matches the current ELF. Exits non-zero on drift.
+    Check {
+        #[arg(long, default_value = "artifacts/manifest.json")]
+        manifest: PathBuf,
+    },
+}
+
+/// The vkey hash of `MIXER_ELF`.
+pub fn vkey_hash() -> String {
+    let prover = ProverClient::builder().cpu().build();
+    let (_, vk) = prover.setup(MIXER_ELF);
+    vk.bytes32()
+}
+
+/// Prints the vkey hash, or writes or checks verifier artifacts. Drift from a
+/// checked manifest is an error, one `drift:` line per field.
+pub fn vkey(command: Option<VkeyCommand>) -> Result<(), String> {
+    let vkey_hash = vkey_hash();
+    let current = VerifierManifest::new(MIXER_ELF, &vkey_hash, &GROTH16_VK_BYTES)?;
+    match command {
+        None => println!("{}", vkey_hash),
+        Some(VkeyCommand::Generate { out_dir }) => {
+            artifacts::write_artifacts(&out_dir, &current, &GROTH16_VK_BYTES)?;
+            eprintln!(
+                "Verifier artifacts written to {}",
+                out_dir.join(MANIFEST_FILE).display()
+            );
+        }
+        Some(VkeyCommand::Check { manifest }) => {
+            let drift = artifacts::check(&manifest, &current)?;
+            if !drift.is_empty() {
+                let lines: Vec<String> = drift.iter().map(|l| format!("drift: {}", l)).collect();
+                return Err(lines.join("\n"));
+            }
+            eprintln!("{} matches the current ELF", manifest.display());
+        }
+    }
+    Ok(())
+}
+
+/// A prover server, for `prove --backend remote`. Private inputs are sealed
+/// to the server's input key, see [`crate::sealed`].
+pub struct RemoteServer {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl RemoteServer {
+    pub fn new(url: &str) -> Self {
+        RemoteServer {
+            url: url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, req: reqwest::RequestBuilder) -> Result<T, String> {
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| format!("{}: {}", self.url, e))?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("{}: {} {}", self.url, status, body));
+        }
+        resp.json()
+            .await
+            .map_err(|e| format!("{}: invalid response: {}", self.url, e))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        self.send(self.client.get(format!("{}{}", self.url, path)))
+            .await
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<T, String> {
+        self.send(self.client.post(format!("{}{}", self.url, path)).json(body))
+            .await
+    }
+
+    /// The pools the server serves.
+    pub async fn pools(&self) -> Result<Vec<PoolConfig>, String> {
+        self.get("/api/pools").await
+    }
+
+    /// The Merkle path of `commitment` in the server's index of `pool_id`,
+    /// checked against the commitment.
+    pub async fn merkle_path(
+        &self,
+        pool_id: &str,
+        commitment: &[u8; 32],
+    ) -> Result<MerklePath, String> {
+        let served: MerklePathResponse = self
+            .get(&format!(
+                "/api/pools/{}/merkle-path?commitment={}",
+                pool_id,
+                to_hex32(commitment)
+            ))
+            .await?;
+        let path = served.path()?;
+        if !path.verify(*commitment) {
+            return Err(format!("{} served a path that does not fit", self.url));
+        }
+        Ok(path)
+    }
+
+    /// The request for `inputs`, with the nullifier and secret sealed to the
+    /// server's current input key.
+    async fn sealed_request(
+        &self,
+        inputs: &WithdrawalInputs,
+        format: ProofFormat,
+    ) -> Result<ProveRequest, String> {
+        let key: InputKeyResponse = self.get("/api/input-key").await?;
+        let public_key = hex::decode(&key.public_key)
+            .ok()
+            .and_then(|k| <[u8; 32]>::try_from(k).ok())
+            .ok_or_else(|| format!("{} published an invalid input key", self.url))?;
+        let sealed = SealedInputs::seal(
+            &public_key,
+            &PrivateInputs {
+                nullifier: inputs.nullifier.clone(),
+                secret: inputs.secret.clone(),
+            },
+        )?;
+        Ok(ProveRequest {
+            root: to_hex32(&inputs.root),
+            nullifier_hash: to_hex32(&inputs.nullifier_hash),
+            recipient: to_hex32(&inputs.recipient),
+            relayer: to_hex32(&inputs.relayer),
+            fee: inputs.fee,
+            refund: inputs.refund,
+            mint: Some(to_hex32(&inputs.mint)),
+            amount: inputs.amount,
+            nullifier: Default::default(),
+            secret: Default::default(),
+            sealed: Some(sealed),
+            path_elements: to_hex_vec(&inputs.path_elements),
+            path_indices: inputs.path_indices.to_vec(),
+            format,
+            dry_run: false,
+        })
+    }
+
+    pub async fn prove(
+        &self,
+        inputs: &WithdrawalInputs,
+        format: ProofFormat,
+    ) -> Result<ProveResponse, String> {
+        let req = self.sealed_request(inputs, format).await?;
+        self.post("/api/prove-mix", &req).await
+    }
+
+    pub async fn dry_run(&self, inputs: &WithdrawalInputs) -> Result<DryRunResponse, String> {
+        let req = self.sealed_request(inputs, ProofFormat::Sp1).await?;
+        self.post("/api/prove-mix/dry-run", &req).await
+    }
+}
@@ -0,0 +1,345 @@
+//! Directory of relayers, so wallets can pick one instead of hardcoding
+//! `new_relayer_address`.
+//!
+//! Relayers register by posting a [`Registration`] signed with their Solana
+//! key: the endpoint of their server, their fee schedule and the pools they
+//! relay. A newer registration replaces the older one, and one without pools
+//! removes the relayer. Registrations are kept in a local JSON file.
+//!
+//! The directory checks every relayer periodically by asking its endpoint for
+//! a fee quote for one of its pools. The check passes if the quote is signed
+//! by the registered key, which also proves the endpoint belongs to it.
+
+use anchor_client::solana_sdk::{pubkey::Pubkey, signature::Signature};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::quote::{FeeQuote, FeeSchedule};
+use crate::relayer::Relayer;
+
+/// Domain separator of the signed registration message.
+const REGISTRATION_DOMAIN: &[u8] = b"mixer-relayer-registration:v1";
+
+/// How far a registration may be dated ahead of the directory's clock.
+const MAX_CLOCK_SKEW: u64 = 300;
+
+/// A relayer's signed announcement.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    /// Base58 relayer key, the key quotes are signed with.
+    pub relayer: String,
+    /// Base URL of the relayer's proof server.
+    pub endpoint: String,
+    pub fees: FeeSchedule,
+    /// Ids of the pools relayed, as named by this directory.
+    pub pools: Vec<String>,
+    /// Unix time of signing. Orders registrations of one relayer.
+    pub registered_at: u64,
+    /// Base58 ed25519 signature of [`Registration::message`] by `relayer`.
+    pub signature: String,
+}
+
+impl Registration {
+    /// A registration of `relayer`, signed now.
+    pub fn sign(relayer: &Relayer, endpoint: &str, fees: FeeSchedule, pools: Vec<String>) -> Self {
+        let mut registration = Registration {
+            relayer: relayer.pubkey().to_string(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            fees,
+            pools,
+            registered_at: unix_now(),
+            signature: String::new(),
+        };
+        registration.signature = relayer.sign(&registration.message()).to_string();
+        registration
+    }
+
+    /// The signed bytes: every field but the signature.
+    pub fn message(&self) -> Vec<u8> {
+        let mut out = REGISTRATION_DOMAIN.to_vec();
+        let strings = [&self.relayer, &self.endpoint]
+            .into_iter()
+            .chain(&self.pools);
+        out.extend_from_slice(&(self.pools.len() as u32).to_le_bytes());
+        for s in strings {
+            out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            out.extend_from_slice(s.as_bytes());
+        }
+        out.extend_from_slice(&self.fees.rate_bps.to_le_bytes());
+        out.extend_from_slice(&self.fees.prover_cost.to_le_bytes());
+        out.extend_from_slice(&self.registered_at.to_le_bytes());
+        out
+    }
+
+    /// Checks the signature, the endpoint URL and the date.
+    pub fn verify(&self) -> Result<(), String> {
+        let relayer = Pubkey::from_str(&self.relayer)
+            .map_err(|e| format!("invalid relayer in registration: {}", e))?;
+        let signature = Signature::from_str(&self.signature)
+            .map_err(|e| format!("invalid registration signature: {}", e))?;
+        if !signature.verify(relayer.as_ref(), &self.message()) {
+            return Err(format!(
+                "registration is not signed by relayer {}",
+                self.relayer
+            ));
+        }
+        if !self.endpoint.starts_with("http://") && !self.endpoint.starts_with("https://") {
+            return Err(format!("endpoint {} is not an http(s) URL", self.endpoint));
+        }
+        if self.registered_at > unix_now() + MAX_CLOCK_SKEW {
+            return Err("registration is dated in the future".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Results of the directory's checks of one relayer.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Health {
+    /// Whether the last check passed.
+    pub healthy: bool,
+    /// Unix time of the last check.
+    pub last_checked: Option<u64>,
+    /// Unix time of the last check that passed.
+    pub last_seen: Option<u64>,
+    pub checks: u64,
+    pub successes: u64,
+    /// Why the last check failed.
+    pub last_error: Option<String>,
+}
+
+impl Health {
+    /// Share of passed checks, `None` before the first.
+    pub fn uptime(&self) -> Option<f64> {
+        (self.checks > 0).then(|| self.successes as f64 / self.checks as f64)
+    }
+
+    fn record(&mut self, result: Result<(), String>) {
+        let now = unix_now();
+        self.checks += 1;
+        self.last_checked = Some(now);
+        self.healthy = result.is_ok();
+        match result {
+            Ok(()) => {
+                self.successes += 1;
+                self.last_seen = Some(now);
+                self.last_error = None;
+            }
+            Err(e) => self.last_error = Some(e),
+        }
+    }
+}
+
+/// A registered relayer as listed by `GET /api/relayers`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RelayerEntry {
+    pub registration: Registration,
+    pub health: Health,
+    pub uptime: Option<f64>,
+}
+
+/// The registered relayers, persisted to an optional file.
+pub struct RelayerDirectory {
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<String, (Registration, Health)>>,
+    client: reqwest::Client,
+}
+
+impl RelayerDirectory {
+    /// An empty directory kept in memory only.
+    pub fn new() -> Self {
+        RelayerDirectory {
+            path: None,
+            entries: Mutex::default(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("HTTP client builds"),
+        }
+    }
+
+    /// The directory stored at `path`, which need not exist yet.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let entries: Vec<(Registration, Health)> = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| format!("parsing relayer directory {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(format!(
+                    "reading relayer directory {}: {}",
+                    path.display(),
+                    e
+                ))
+            }
+        };
+        let directory = RelayerDirectory {
+            path: Some(path),
+            ..Self::new()
+        };
+        *directory.entries.lock().unwrap() = entries
+            .into_iter()
+            .map(|(r, h)| (r.relayer.clone(), (r, h)))
+            .collect();
+        Ok(directory)
+    }
+
+    /// Adds, replaces or (without pools) removes the relayer's registration.
+    /// Registrations older than the stored one are refused as replays.
+    pub fn register(&self, registration: Registration) -> Result<(), String> {
+        registration.verify()?;
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some((current, _)) = entries.get(&registration.relayer) {
+                if registration.registered_at <= current.registered_at {
+                    return Err(format!(
+                        "relayer {} has a newer registration",
+                        registration.relayer
+                    ));
+                }
+            }
+            // A new endpoint or schedule starts with a clean record. Removals
+            // are kept too, so older registrations cannot be replayed.
+            entries.insert(
+                registration.relayer.clone(),
+                (registration, Health::default()),
+            );
+        }
+        self.save()
+    }
+
+    /// Relayers serving `pool` (all if `None`), healthy ones with the best
+    /// uptime first.
+    pub fn list(&self, pool: Option<&str>) -> Vec<RelayerEntry> {
+        let mut list: Vec<RelayerEntry> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(r, _)| match pool {
+                Some(pool) => r.pools.iter().any(|id| id == pool),
+                None => !r.pools.is_empty(),
+            })
+            .map(|(registration, health)| RelayerEntry {
+                registration: registration.clone(),
+                uptime: health.uptime(),
+                health: health.clone(),
+            })
+            .collect();
+        list.sort_by(|a, b| {
+            b.health
+                .healthy
+                .cmp(&a.health.healthy)
+                .then(b.uptime.unwrap_or(0.0).total_cmp(&a.uptime.unwrap_or(0.0)))
+                .then_with(|| a.registration.relayer.cmp(&b.registration.relayer))
+        });
+        list
+    }
+
+    /// Number of relayers with pools.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(r, _)| !r.pools.is_empty())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks every registered relayer once and stores the results.
+    pub async fn check_all(&self) -> Result<(), String> {
+        let registrations: Vec<Registration> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(r, _)| !r.pools.is_empty())
+            .map(|(r, _)| r.clone())
+            .collect();
+        for registration in registrations {
+            let result = self.check(&registration).await;
+            let mut entries = self.entries.lock().unwrap();
+            // Skip relayers that re-registered during the check.
+            if let Some((current, health)) = entries.get_mut(&registration.relayer) {
+                if *current == registration {
+                    health.record(result);
+                }
+            }
+        }
+        self.save()
+    }
+
+    /// Asks the relayer for a quote for its first pool.
+    async fn check(&self, registration: &Registration) -> Result<(), String> {
+        let pool = registration.pools.first().ok_or("no pools")?;
+        let url = format!("{}/api/pools/{}/fee-quote", registration.endpoint, pool);
+        let resp = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("{}: {}", url, e))?;
+        if !resp.status().is_success() {
+            return Err(format!("{}: {}", url, resp.status()));
+        }
+        let quote: FeeQuote = resp.json().await.map_err(|e| format!("{}: {}", url, e))?;
+        if quote.relayer != registration.relayer {
+            return Err(format!("quote is signed by {}", quote.relayer));
+        }
+        quote.verify()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let entries: Vec<(Registration, Health)> =
+            self.entries.lock().unwrap().values().cloned().collect();
+        let json = serde_json::to_vec_pretty(&entries).map_err(|e| e.to_string())?;
+        // Write a sibling file and rename it, so a crash keeps the old file.
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, json)
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(|e| format!("writing relayer directory {}: {}", path.display(), e))
+    }
+}
+
+impl Default for RelayerDirectory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for RelayerDirectory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RelayerDirectory")
+            .field("path", &self.path)
+            .field("relayers", &self.len())
+            .finish()
+    }
+}
+
+/// Checks the directory's relayers every `interval`, forever.
+pub async fn run_health_checks(directory: Arc<RelayerDirectory>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = directory.check_all().await {
+            tracing::warn!("relayer health checks: {}", e);
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,460 @@
+//! Decoding of the mixer program's Anchor events, driven by its IDL.
+//!
+//! Discriminators and field layouts come from the IDL instead of hand-written
+//! structs, so a program upgrade that adds event fields keeps decoding, and one
+//! that drops a field the server relies on is rejected when the IDL is loaded.
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_lang_idl::convert::convert_idl;
+use anchor_lang_idl::types::{
+    Idl, IdlArrayLen, IdlDefinedFields, IdlType, IdlTypeDef, IdlTypeDefTy,
+};
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, OnceLock};
+
+use crate::source::DepositSource;
+
+/// IDL of the mixer program as deployed when this server was built. Used when
+/// no other IDL is configured.
+const BUNDLED_IDL: &str = include_str!("../idl/mixer.json");
+
+pub const DEPOSIT_EVENT: &str = "DepositEvent";
+pub const WITHDRAWAL_EVENT: &str = "WithdrawalEvent";
+
+/// Seed of the account Anchor stores a program's IDL in.
+const IDL_ACCOUNT_SEED: &str = "anchor:idl";
+/// Account discriminator, authority and compressed length precede the IDL.
+const IDL_ACCOUNT_HEADER_LEN: usize = 8 + 32 + 4;
+
+/// A deposit into the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositEvent {
+    pub commitment: [u8; 32],
+    pub leaf_index: u32,
+    pub depositor: Pubkey,
+}
+
+/// A withdrawal from the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawalEvent {
+    pub nullifier_hash: [u8; 32],
+    pub recipient: Pubkey,
+    pub relayer: Pubkey,
+    pub fee: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MixerEvent {
+    Deposit(DepositEvent),
+    Withdrawal(WithdrawalEvent),
+}
+
+/// Anchor event discriminator: `sha256("event:<name>")[..8]`.
+pub fn event_discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("event:{}", name));
+    hash[..8].try_into().unwrap()
+}
+
+/// Address of the account holding `program_id`'s IDL (`anchor idl init`).
+pub fn idl_address(program_id: &Pubkey) -> Pubkey {
+    let (base, _) = Pubkey::find_program_address(&[], program_id);
+    Pubkey::create_with_seed(&base, IDL_ACCOUNT_SEED, program_id).expect("seed is valid")
+}
+
+/// A decoded value, in the shape the IDL describes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdlValue {
+    Bool(bool),
+    UInt(u128),
+    Int(i128),
+    Float(f64),
+    /// `bytes`, and arrays or vectors of `u8`.
+    Bytes(Vec<u8>),
+    String(String),
+    Pubkey(Pubkey),
+    Option(Option<Box<IdlValue>>),
+    List(Vec<IdlValue>),
+    Struct(Vec<(String, IdlValue)>),
+    Enum(String, Vec<IdlValue>),
+}
+
+/// Decodes mixer events from transaction logs.
+#[derive(Debug, Clone)]
+pub struct EventDecoder {
+    program: String,
+    types: HashMap<String, IdlTypeDef>,
+    deposit: [u8; 8],
+    /// `None` for program versions that do not emit withdrawal events.
+    withdrawal: Option<[u8; 8]>,
+}
+
+impl EventDecoder {
+    /// Builds a decoder from IDL JSON, either the current spec or the legacy
+    /// (pre Anchor 0.30) one.
+    pub fn from_json(json: &[u8]) -> Result<Self, String> {
+        let idl = convert_idl(json).map_err(|e| format!("invalid IDL: {}", e))?;
+        Self::from_idl(idl)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let json = std::fs::read(path).map_err(|e| format!("reading IDL {}: {}", path, e))?;
+        Self::from_json(&json)
+    }
+
+    /// Builds a decoder from the raw data of a program's IDL account.
+    pub fn from_idl_account(data: &[u8]) -> Result<Self, String> {
+        if data.len() < IDL_ACCOUNT_HEADER_LEN {
+            return Err(format!("IDL account too short ({} bytes)", data.len()));
+        }
+        let expected: [u8; 8] = Sha256::digest("account:IdlAccount")[..8]
+            .try_into()
+            .unwrap();
+        if data[..8] != expected {
+            return Err("not an Anchor IDL account".to_string());
+        }
+        let len = u32::from_le_bytes(data[40..44].try_into().unwrap()) as usize;
+        let compressed = data
+            .get(IDL_ACCOUNT_HEADER_LEN..IDL_ACCOUNT_HEADER_LEN + len)
+            .ok_or_else(|| format!("IDL account holds less than {} IDL bytes", len))?;
+        let mut json = Vec::new();
+        ZlibDecoder::new(compressed)
+            .read_to_end(&mut json)
+            .map_err(|e| format!("decompressing IDL: {}", e))?;
+        Self::from_json(&json)
+    }
+
+    /// Fetches and loads the IDL published on chain for `program_id`.
+    pub async fn fetch(source: &dyn DepositSource, program_id: &Pubkey) -> Result<Self, String> {
+        let data = source.account_data(&idl_address(program_id)).await?;
+        Self::from_idl_account(&data)
+    }
+
+    /// Decoder for the IDL shipped with the server.
+    pub fn bundled() -> Arc<Self> {
+        static BUNDLED: OnceLock<Arc<EventDecoder>> = OnceLock::new();
+        BUNDLED
+            .get_or_init(|| {
+                Arc::new(Self::from_json(BUNDLED_IDL.as_bytes()).expect("bundled IDL is valid"))
+            })
+            .clone()
+    }
+
+    pub fn from_idl(idl: Idl) -> Result<Self, String> {
+        let discriminators: HashMap<String, [u8; 8]> = idl
+            .events
+            .iter()
+            .filter_map(|e| Some((e.name.clone(), e.discriminator.as_slice().try_into().ok()?)))
+            .collect();
+        let decoder = EventDecoder {
+            program: idl.metadata.name,
+            types: idl.types.into_iter().map(|t| (t.name.clone(), t)).collect(),
+            deposit: *discriminators
+                .get(DEPOSIT_EVENT)
+                .ok_or_else(|| format!("IDL has no {}", DEPOSIT_EVENT))?,
+            withdrawal: discriminators.get(WITHDRAWAL_EVENT).copied(),
+        };
+
+        decoder.check_fields(
+            DEPOSIT_EVENT,
+            &[
+                ("commitment", &bytes32()),
+                ("leaf_index", &IdlType::U32),
+                ("depositor", &IdlType::Pubkey),
+            ],
+        )?;
+        if decoder.withdrawal.is_some() {
+            decoder.check_fields(
+                WITHDRAWAL_EVENT,
+                &[
+                    ("nullifier_hash", &bytes32()),
+                    ("recipient", &IdlType::Pubkey),
+                    ("relayer", &IdlType::Pubkey),
+                    ("fee", &IdlType::U64),
+                ],
+            )?;
+        } else {
+            tracing::warn!(
+                "IDL of {} has no {}; withdrawals are not indexed",
+                decoder.program,
+                WITHDRAWAL_EVENT
+            );
+        }
+        Ok(decoder)
+    }
+
+    /// Discriminators of `DepositEvent` and, if the program emits it,
+    /// `WithdrawalEvent`.
+    pub fn discriminators(&self) -> ([u8; 8], Option<[u8; 8]>) {
+        (self.deposit, self.withdrawal)
+    }
+
+    /// Every mixer event emitted in a transaction's logs, in order. Events that
+    /// fail to decode are logged and skipped.
+    pub fn decode(&self, logs: &[String]) -> Vec<MixerEvent> {
+        const PREFIX: &str = "Program data: ";
+
+        let mut out = Vec::new();
+        for log in logs.iter().filter_map(|l| l.strip_prefix(PREFIX)) {
+            let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(log) else {
+                continue;
+            };
+            if bytes.len() < 8 {
+                continue;
+            }
+            let (disc, data) = bytes.split_at(8);
+            let event = if disc == self.deposit {
+                self.decode_event(DEPOSIT_EVENT, data)
+                    .map(|f| MixerEvent::Deposit(deposit_from_fields(&f)))
+            } else if Some(disc) == self.withdrawal.as_ref().map(|d| d.as_slice()) {
+                self.decode_event(WITHDRAWAL_EVENT, data)
+                    .map(|f| MixerEvent::Withdrawal(withdrawal_from_fields(&f)))
+            } else {
+                continue;
+            };
+            match event {
+                Ok(event) => out.push(event),
+                Err(e) => tracing::warn!("undecodable {} event: {}", self.program, e),
+            }
+        }
+        out
+    }
+
+    pub fn deposits(&self, logs: &[String]) -> Vec<DepositEvent> {
+        self.decode(logs)
+            .into_iter()
+            .filter_map(|e| match e {
+                MixerEvent::Deposit(d) => Some(d),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn withdrawals(&self, logs: &[String]) -> Vec<WithdrawalEvent> {
+        self.decode(logs)
+            .into_iter()
+            .filter_map(|e| match e {
+                MixerEvent::Withdrawal(w) => Some(w),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Ensures `event` is a struct with at least `required` fields, typed as given.
+    fn check_fields(&self, event: &str, required: &[(&str, &IdlType)]) -> Result<(), String> {
+        let fields = match self.types.get(event).map(|t| &t.ty) {
+            Some(IdlTypeDefTy::Struct {
+                fields: Some(IdlDefinedFields::Named(fields)),
+            }) => fields,
+            Some(_) => return Err(format!("IDL type {} is not a struct", event)),
+            None => return Err(format!("IDL has no type for {}", event)),
+        };
+        for (name, ty) in required {
+            match fields.iter().find(|f| f.name == *name) {
+                Some(f) if f.ty == **ty => {}
+                Some(f) => {
+                    return Err(format!(
+                        "{}.{} is {:?}, expected {:?}",
+                        event, name, f.ty, ty
+                    ))
+                }
+                None => return Err(format!("IDL {} has no field {}", event, name)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the fields of `event` from its data (without discriminator).
+    pub fn decode_event(
+        &self,
+        event: &str,
+        data: &[u8],
+    ) -> Result<Vec<(String, IdlValue)>, String> {
+        let mut reader = Reader(data);
+        let ty = IdlType::Defined {
+            name: event.to_string(),
+            generics: Vec::new(),
+        };
+        match self.read(&ty, &mut reader)? {
+            IdlValue::Struct(fields) => Ok(fields),
+            _ => Err(format!("{} is not a struct", event)),
+        }
+    }
+
+    /// Reads one Borsh-encoded value of type `ty`.
+    fn read(&self, ty: &IdlType, r: &mut Reader) -> Result<IdlValue, String> {
+        Ok(match ty {
+            IdlType::Bool => IdlValue::Bool(r.take(1)?[0] != 0),
+            IdlType::U8 => IdlValue::UInt(r.uint(1)?),
+            IdlType::U16 => IdlValue::UInt(r.uint(2)?),
+            IdlType::U32 => IdlValue::UInt(r.uint(4)?),
+            IdlType::U64 => IdlValue::UInt(r.uint(8)?),
+            IdlType::U128 => IdlValue::UInt(r.uint(16)?),
+            IdlType::I8 => IdlValue::Int(r.int(1)?),
+            IdlType::I16 => IdlValue::Int(r.int(2)?),
+            IdlType::I32 => IdlValue::Int(r.int(4)?),
+            IdlType::I64 => IdlValue::Int(r.int(8)?),
+            IdlType::I128 => IdlValue::Int(r.int(16)?),
+            IdlType::F32 => IdlValue::Float(f32::from_le_bytes(r.array()?) as f64),
+            IdlType::F64 => IdlValue::Float(f64::from_le_bytes(r.array()?)),
+            IdlType::Pubkey => IdlValue::Pubkey(Pubkey::new_from_array(r.array()?)),
+            IdlType::Bytes => {
+                let len = r.len()?;
+                IdlValue::Bytes(r.take(len)?.to_vec())
+            }
+            IdlType::String => {
+                let len = r.len()?;
+                IdlValue::String(
+                    String::from_utf8(r.take(len)?.to_vec()).map_err(|e| e.to_string())?,
+                )
+            }
+            IdlType::Option(inner) => match r.take(1)?[0] {
+                0 => IdlValue::Option(None),
+                _ => IdlValue::Option(Some(Box::new(self.read(inner, r)?))),
+            },
+            IdlType::Vec(inner) => {
+                let len = r.len()?;
+                self.read_list(inner, len, r)?
+            }
+            IdlType::Array(inner, IdlArrayLen::Value(len)) => self.read_list(inner, *len, r)?,
+            IdlType::Defined { name, .. } => {
+                let def = self
+                    .types
+                    .get(name)
+                    .ok_or_else(|| format!("IDL has no type {}", name))?;
+                match &def.ty {
+                    IdlTypeDefTy::Struct { fields } => {
+                        IdlValue::Struct(self.read_fields(fields, r)?)
+                    }
+                    IdlTypeDefTy::Enum { variants } => {
+                        let tag = r.take(1)?[0] as usize;
+                        let variant = variants
+                            .get(tag)
+                            .ok_or_else(|| format!("{} has no variant {}", name, tag))?;
+                        let fields = self.read_fields(&variant.fields, r)?;
+                        IdlValue::Enum(
+                            variant.name.clone(),
+                            fields.into_iter().map(|(_, v)| v).collect(),
+                        )
+                    }
+                    IdlTypeDefTy::Type { alias } => self.read(alias, r)?,
+                }
+            }
+            other => return Err(format!("unsupported IDL type {:?}", other)),
+        })
+    }
+
+    fn read_list(&self, inner: &IdlType, len: usize, r: &mut Reader) -> Result<IdlValue, String> {
+        if *inner == IdlType::U8 {
+            return Ok(IdlValue::Bytes(r.take(len)?.to_vec()));
+        }
+        (0..len)
+            .map(|_| self.read(inner, r))
+            .collect::<Result<_, _>>()
+            .map(IdlValue::List)
+    }
+
+    fn read_fields(
+        &self,
+        fields: &Option<IdlDefinedFields>,
+        r: &mut Reader,
+    ) -> Result<Vec<(String, IdlValue)>, String> {
+        match fields {
+            None => Ok(Vec::new()),
+            Some(IdlDefinedFields::Named(fields)) => fields
+                .iter()
+                .map(|f| Ok((f.name.clone(), self.read(&f.ty, r)?)))
+                .collect(),
+            Some(IdlDefinedFields::Tuple(types)) => types
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| Ok((i.to_string(), self.read(ty, r)?)))
+                .collect(),
+        }
+    }
+}
+
+fn bytes32() -> IdlType {
+    IdlType::Array(Box::new(IdlType::U8), IdlArrayLen::Value(32))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < n {
+            return Err(format!("needs {} more bytes, {} left", n, self.0.len()));
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn uint(&mut self, n: usize) -> Result<u128, String> {
+        let mut buf = [0u8; 16];
+        buf[..n].copy_from_slice(self.take(n)?);
+        Ok(u128::from_le_bytes(buf))
+    }
+
+    fn int(&mut self, n: usize) -> Result<i128, String> {
+        let bits = 8 * n as u32;
+        let v = self.uint(n)? as i128;
+        // Sign-extend from `bits`.
+        Ok((v << (128 - bits)) >> (128 - bits))
+    }
+
+    /// Borsh length prefix (`u32`).
+    fn len(&mut self) -> Result<usize, String> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+}
+
+// `check_fields` guarantees these fields exist with these types.
+fn field<'a>(fields: &'a [(String, IdlValue)], name: &str) -> &'a IdlValue {
+    &fields.iter().find(|(n, _)| n == name).unwrap().1
+}
+
+fn bytes32_field(fields: &[(String, IdlValue)], name: &str) -> [u8; 32] {
+    match field(fields, name) {
+        IdlValue::Bytes(b) => b.as_slice().try_into().unwrap(),
+        _ => unreachable!(),
+    }
+}
+
+fn uint_field(fields: &[(String, IdlValue)], name: &str) -> u128 {
+    match field(fields, name) {
+        IdlValue::UInt(v) => *v,
+        _ => unreachable!(),
+    }
+}
+
+fn pubkey_field(fields: &[(String, IdlValue)], name: &str) -> Pubkey {
+    match field(fields, name) {
+        IdlValue::Pubkey(p) => *p,
+        _ => unreachable!(),
+    }
+}
+
+fn deposit_from_fields(fields: &[(String, IdlValue)]) -> DepositEvent {
+    DepositEvent {
+        commitment: bytes32_field(fields, "commitment"),
+        leaf_index: uint_field(fields, "leaf_index") as u32,
+        depositor: pubkey_field(fields, "depositor"),
+    }
+}
+
+fn withdrawal_from_fields(fields: &[(String, IdlValue)]) -> WithdrawalEvent {
+    WithdrawalEvent {
+        nullifier_hash: bytes32_field(fields, "nullifier_hash"),
+        recipient: pubkey_field(fields, "recipient"),
+        relayer: pubkey_field(fields, "relayer"),
+        fee: uint_field(fields, "fee") as u64,
+    }
+}
@@ -0,0 +1,653 @@
+use anchor_client::{
+    solana_client::{
+        nonblocking::pubsub_client::PubsubClient,
+        rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+    },
+    solana_sdk::{
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        pubkey::Pubkey,
+    },
+};
+use futures_util::StreamExt;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::idl::{EventDecoder, MixerEvent};
+use crate::snapshot::TreeSnapshot;
+use crate::source::{is_at_least, DepositSource, RetryPolicy, SignatureInfo};
+use crate::utils::{compute_merkle_proof, TREE_DEPTH};
+
+/// How often non-finalized deposits are re-checked against the chain.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A deposit as seen on chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositRecord {
+    pub commitment: [u8; 32],
+    pub leaf_index: u32,
+    pub signature: String,
+    pub slot: u64,
+    /// Commitment level of the deposit transaction when last checked.
+    pub status: CommitmentLevel,
+    /// Unix time of the block, or when the deposit was first seen live.
+    pub block_time: i64,
+}
+
+/// A withdrawal as seen on chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawalRecord {
+    pub nullifier_hash: [u8; 32],
+    pub recipient: Pubkey,
+    pub relayer: Pubkey,
+    pub fee: u64,
+    pub signature: String,
+    pub slot: u64,
+    pub status: CommitmentLevel,
+}
+
+/// Snapshot of a pool's activity, see [`DepositIndex::stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    pub deposits: usize,
+    pub withdrawals: usize,
+    /// Servable deposits not withdrawn yet.
+    pub anonymity_set: usize,
+    /// Unix time of the newest deposit.
+    pub last_deposit_time: Option<i64>,
+    /// Root over the servable leaves, `None` while there are none.
+    pub root: Option<[u8; 32]>,
+}
+
+/// Outcome of [`DepositIndex::reconcile`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReconcileReport {
+    /// Deposits whose commitment level went up.
+    pub promoted: usize,
+    /// Deposits removed because their transaction (or an earlier one) was dropped.
+    pub rolled_back: usize,
+    /// Withdrawals removed because their transaction was dropped.
+    pub dropped_withdrawals: usize,
+    /// Events re-applied from the canonical history afterwards.
+    pub reapplied: usize,
+}
+
+#[derive(Debug, Default)]
+struct IndexState {
+    deposits: BTreeMap<u32, DepositRecord>,
+    by_commitment: HashMap<[u8; 32], u32>,
+    withdrawals: HashMap<[u8; 32], WithdrawalRecord>,
+    /// Transactions already applied, from either the subscription or a backfill.
+    seen: HashSet<String>,
+    /// Last transaction of the snapshot the index was restored from. Backfills
+    /// skip it and everything older.
+    restored_until: Option<String>,
+    /// Bumped whenever deposits or withdrawals change, to tell whether
+    /// [`ServedTree`] is stale.
+    version: u64,
+}
+
+/// The servable leaves and their root at one [`IndexState::version`].
+#[derive(Debug, Clone)]
+struct ServedTree {
+    version: u64,
+    leaves: Arc<[[u8; 32]]>,
+    root: Option<[u8; 32]>,
+}
+
+/// Local copy of one pool's deposit and withdrawal history, kept up to date by
+/// [`run_log_subscription`].
+///
+/// Only deposits at least as final as `min_status` (finalized by default) are
+/// served as tree leaves, so proofs never use a root that might not finalize.
+#[derive(Debug)]
+pub struct DepositIndex {
+    program_id: Pubkey,
+    min_status: CommitmentLevel,
+    events: Arc<EventDecoder>,
+    state: RwLock<IndexState>,
+    served: Mutex<Option<ServedTree>>,
+}
+
+impl DepositIndex {
+    pub fn new(program_id: Pubkey) -> Self {
+        DepositIndex {
+            program_id,
+            min_status: CommitmentLevel::Finalized,
+            events: EventDecoder::bundled(),
+            state: RwLock::new(IndexState::default()),
+            served: Mutex::new(None),
+        }
+    }
+
+    /// Serves leaves from deposits at `min_status` or above.
+    pub fn with_min_commitment(mut self, min_status: CommitmentLevel) -> Self {
+        self.min_status = min_status;
+        self
+    }
+
+    /// Decodes deposits with `events` instead of the bundled IDL.
+    pub fn with_events(mut self, events: Arc<EventDecoder>) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn min_commitment(&self) -> CommitmentLevel {
+        self.min_status
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    /// Applies the events emitted by one transaction and returns how many
+    /// were new. For transactions seen before only the status is updated.
+    pub fn apply_logs(
+        &self,
+        signature: &str,
+        slot: u64,
+        status: CommitmentLevel,
+        logs: &[String],
+    ) -> usize {
+        self.apply(signature, slot, status, None, logs)
+    }
+
+    fn apply(
+        &self,
+        signature: &str,
+        slot: u64,
+        status: CommitmentLevel,
+        block_time: Option<i64>,
+        logs: &[String],
+    ) -> usize {
+        let mut state = self.state.write().unwrap();
+        if !state.seen.insert(signature.to_string()) {
+            let mut changed = false;
+            for d in state.deposits.values_mut() {
+                if d.signature == signature && !is_at_least(d.status, status) {
+                    d.status = status;
+                    changed = true;
+                }
+            }
+            for w in state.withdrawals.values_mut() {
+                if w.signature == signature && !is_at_least(w.status, status) {
+                    w.status = status;
+                    changed = true;
+                }
+            }
+            if changed {
+                state.version += 1;
+            }
+            return 0;
+        }
+        let block_time = block_time.unwrap_or_else(unix_now);
+        let mut added = 0;
+        for event in self.events.decode(logs) {
+            match event {
+                MixerEvent::Deposit(event) => {
+                    if state.deposits.contains_key(&event.leaf_index) {
+                        continue;
+                    }
+                    state
+                        .by_commitment
+                        .insert(event.commitment, event.leaf_index);
+                    state.deposits.insert(
+                        event.leaf_index,
+                        DepositRecord {
+                            commitment: event.commitment,
+                            leaf_index: event.leaf_index,
+                            signature: signature.to_string(),
+                            slot,
+                            status,
+                            block_time,
+                        },
+                    );
+                }
+                MixerEvent::Withdrawal(event) => {
+                    if state.withdrawals.contains_key(&event.nullifier_hash) {
+                        continue;
+                    }
+                    state.withdrawals.insert(
+                        event.nullifier_hash,
+                        WithdrawalRecord {
+                            nullifier_hash: event.nullifier_hash,
+                            recipient: event.recipient,
+                            relayer: event.relayer,
+                            fee: event.fee,
+                            signature: signature.to_string(),
+                            slot,
+                            status,
+                        },
+                    );
+                }
+            }
+            added += 1;
+        }
+        if added > 0 {
+            state.version += 1;
+        }
+        added
+    }
+
+    /// Replays every program transaction not applied yet. Called on startup
+    /// and after each reconnect to fill whatever the subscription missed.
+    pub async fn backfill(&self, source: &dyn DepositSource) -> Result<usize, String> {
+        let until = self.state.read().unwrap().restored_until.clone();
+        let sigs = source
+            .signatures(&self.program_id, until.as_deref())
+            .await?;
+        let mut added = 0;
+        // Oldest first, so leaves arrive roughly in order.
+        for sig in sigs.iter().rev() {
+            if self.state.read().unwrap().seen.contains(&sig.signature) {
+                continue;
+            }
+            let logs = source.transaction_logs(&sig.signature).await?;
+            added += self.apply(&sig.signature, sig.slot, sig.status, sig.block_time, &logs);
+        }
+        Ok(added)
+    }
+
+    /// Re-checks every non-finalized event against the program's signature
+    /// list. Events that became more final are promoted. A deposit whose
+    /// transaction vanished although the node has seen later slots was dropped
+    /// by a fork switch: it and every later leaf are rolled back, and the tree
+    /// is rebuilt from the canonical history. Dropped withdrawals are removed.
+    ///
+    /// Only the history after the newest finalized transaction older than
+    /// every pending event is listed, and nothing at all while none is pending.
+    pub async fn reconcile(&self, source: &dyn DepositSource) -> Result<ReconcileReport, String> {
+        let Some(until) = self.reconcile_from() else {
+            return Ok(ReconcileReport::default());
+        };
+        let sigs = source
+            .signatures(&self.program_id, until.as_deref())
+            .await?;
+        let listed: HashMap<&str, &SignatureInfo> =
+            sigs.iter().map(|s| (s.signature.as_str(), s)).collect();
+        let newest_slot = sigs.iter().map(|s| s.slot).max().unwrap_or(0);
+
+        let mut report = ReconcileReport::default();
+        {
+            let mut state = self.state.write().unwrap();
+            let mut cut: Option<u32> = None;
+            for d in state.deposits.values_mut() {
+                if d.status == CommitmentLevel::Finalized {
+                    continue;
+                }
+                match listed.get(d.signature.as_str()) {
+                    Some(info) => {
+                        if !is_at_least(d.status, info.status) {
+                            d.status = info.status;
+                            d.slot = info.slot;
+                            report.promoted += 1;
+                        }
+                    }
+                    None if d.slot < newest_slot => {
+                        cut = Some(cut.map_or(d.leaf_index, |c| c.min(d.leaf_index)));
+                    }
+                    None => {}
+                }
+            }
+
+            if let Some(cut) = cut {
+                let dropped = state.deposits.split_off(&cut);
+                for d in dropped.values() {
+                    state.by_commitment.remove(&d.commitment);
+                    state.seen.remove(&d.signature);
+                }
+                report.rolled_back = dropped.len();
+                tracing::warn!(
+                    "rolled back {} deposit(s) of {} from leaf {}",
+                    dropped.len(),
+                    self.program_id,
+                    cut
+                );
+            }
+
+            let mut dropped = Vec::new();
+            for w in state.withdrawals.values_mut() {
+                if w.status == CommitmentLevel::Finalized {
+                    continue;
+                }
+                match listed.get(w.signature.as_str()) {
+                    Some(info) => {
+                        if !is_at_least(w.status, info.status) {
+                            w.status = info.status;
+                            w.slot = info.slot;
+                            report.promoted += 1;
+                        }
+                    }
+                    None if w.slot < newest_slot => dropped.push(w.nullifier_hash),
+                    None => {}
+                }
+            }
+            for nullifier_hash in &dropped {
+                if let Some(w) = state.withdrawals.remove(nullifier_hash) {
+                    state.seen.remove(&w.signature);
+                }
+            }
+            report.dropped_withdrawals = dropped.len();
+            if report.promoted > 0 || report.rolled_back > 0 || report.dropped_withdrawals > 0 {
+                state.version += 1;
+            }
+        }
+
+        if report.rolled_back > 0 || report.dropped_withdrawals > 0 {
+            report.reapplied = self.backfill(source).await?;
+        }
+        Ok(report)
+    }
+
+    /// Where [`DepositIndex::reconcile`] lists history back to: `None` while
+    /// no event is pending, otherwise the newest finalized transaction older
+    /// than every pending one, if the index knows any.
+    fn reconcile_from(&self) -> Option<Option<String>> {
+        let state = self.state.read().unwrap();
+        let events = state
+            .deposits
+            .values()
+            .map(|d| (&d.signature, d.slot, d.status))
+            .chain(
+                state
+                    .withdrawals
+                    .values()
+                    .map(|w| (&w.signature, w.slot, w.status)),
+            );
+        let oldest_pending = events
+            .clone()
+            .filter(|(_, _, status)| *status != CommitmentLevel::Finalized)
+            .map(|(_, slot, _)| slot)
+            .min()?;
+        let anchor = events
+            .filter(|(signature, slot, status)| {
+                *status == CommitmentLevel::Finalized
+                    && !signature.is_empty()
+                    && *slot < oldest_pending
+            })
+            .max_by_key(|(_, slot, _)| *slot)
+            .map(|(signature, _, _)| signature.clone());
+        Some(anchor.or_else(|| state.restored_until.clone()))
+    }
+
+    /// Counts, latest deposit time and current root of the pool.
+    pub fn stats(&self) -> PoolStats {
+        let state = self.state.read().unwrap();
+        let served = self.served(&state);
+        PoolStats {
+            deposits: state.deposits.len(),
+            withdrawals: state.withdrawals.len(),
+            anonymity_set: served.leaves.len().saturating_sub(state.withdrawals.len()),
+            last_deposit_time: state.deposits.values().map(|d| d.block_time).max(),
+            root: served.root,
+        }
+    }
+
+    pub fn withdrawal(&self, nullifier_hash: &[u8; 32]) -> Option<WithdrawalRecord> {
+        self.state
+            .read()
+            .unwrap()
+            .withdrawals
+            .get(nullifier_hash)
+            .cloned()
+    }
+
+    /// Number of deposits indexed, including any past a gap.
+    pub fn len(&self) -> usize {
+        self.state.read().unwrap().deposits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Servable leaves: `0..n` with no gaps and every deposit at the minimum
+    /// commitment level. Later deposits are held back until a backfill fills
+    /// the gap or they finalize.
+    pub fn leaves(&self) -> Vec<[u8; 32]> {
+        let state = self.state.read().unwrap();
+        self.served(&state).leaves.to_vec()
+    }
+
+    /// The servable leaves and the index of `commitment`, if it is one of them.
+    pub fn find(&self, commitment: &[u8; 32]) -> Option<(Vec<[u8; 32]>, usize)> {
+        let state = self.state.read().unwrap();
+        let idx = *state.by_commitment.get(commitment)? as usize;
+        let leaves = self.served(&state).leaves;
+        (idx < leaves.len()).then(|| (leaves.to_vec(), idx))
+    }
+
+    /// The servable leaves and their root, rebuilt only after `state` changed.
+    fn served(&self, state: &IndexState) -> ServedTree {
+        let mut cached = self.served.lock().unwrap();
+        if let Some(tree) = cached.as_ref().filter(|t| t.version == state.version) {
+            return tree.clone();
+        }
+        let leaves: Arc<[[u8; 32]]> = self
+            .servable(&state.deposits)
+            .map(|d| d.commitment)
+            .collect();
+        let root = (!leaves.is_empty()).then(|| compute_merkle_proof::<TREE_DEPTH>(&leaves, 0).2);
+        let tree = ServedTree {
+            version: state.version,
+            leaves,
+            root,
+        };
+        *cached = Some(tree.clone());
+        tree
+    }
+
+    /// Like [`DepositIndex::find`], but fails for a known deposit that is
+    /// not yet at the minimum commitment.
+    pub fn locate(&self, commitment: &[u8; 32]) -> Result<Option<(Vec<[u8; 32]>, usize)>, String> {
+        if let Some(found) = self.find(commitment) {
+            return Ok(Some(found));
+        }
+        match self.deposit(commitment) {
+            Some(d) => Err(format!(
+                "deposit at leaf {} is {:?}, proofs are served from {:?} deposits; retry shortly",
+                d.leaf_index,
+                d.status,
+                self.min_commitment()
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn servable<'a>(
+        &self,
+        deposits: &'a BTreeMap<u32, DepositRecord>,
+    ) -> impl Iterator<Item = &'a DepositRecord> {
+        let min = self.min_status;
+        contiguous(deposits).take_while(move |d| is_at_least(d.status, min))
+    }
+
+    /// The finalized leaves `0..n`, the signature of the transaction that
+    /// added the last one and the finalized withdrawals, as a [`TreeSnapshot`].
+    pub fn snapshot(&self) -> TreeSnapshot {
+        let state = self.state.read().unwrap();
+        let finalized: Vec<&DepositRecord> = contiguous(&state.deposits)
+            .take_while(|d| d.status == CommitmentLevel::Finalized)
+            .collect();
+        let last_signature = finalized.last().and_then(|d| {
+            if d.signature.is_empty() {
+                state.restored_until.clone()
+            } else {
+                Some(d.signature.clone())
+            }
+        });
+        // Whatever landed before the last finalized leaf is final as well.
+        let last_slot = finalized.last().map(|d| d.slot);
+        let spent = state
+            .withdrawals
+            .values()
+            .filter(|w| w.status == CommitmentLevel::Finalized || Some(w.slot) <= last_slot)
+            .map(|w| w.nullifier_hash)
+            .collect();
+        TreeSnapshot::new(
+            self.program_id,
+            finalized.iter().map(|d| d.commitment).collect(),
+            last_signature,
+        )
+        .with_nullifier_hashes(spent)
+    }
+
+    /// Seeds an empty index with the leaves and withdrawals of `snapshot`, as
+    /// finalized. Only later transactions are fetched by backfills.
+    pub fn restore(&self, snapshot: &TreeSnapshot) -> Result<(), String> {
+        if snapshot.program_id != self.program_id {
+            return Err(format!(
+                "snapshot is of program {}, not {}",
+                snapshot.program_id, self.program_id
+            ));
+        }
+        snapshot.check()?;
+        let mut state = self.state.write().unwrap();
+        if !state.deposits.is_empty() || !state.withdrawals.is_empty() {
+            return Err("only an empty index can be restored".to_string());
+        }
+        for (i, commitment) in snapshot.leaves.iter().enumerate() {
+            let leaf_index = i as u32;
+            state.by_commitment.insert(*commitment, leaf_index);
+            state.deposits.insert(
+                leaf_index,
+                DepositRecord {
+                    commitment: *commitment,
+                    leaf_index,
+                    // Not in the snapshot.
+                    signature: String::new(),
+                    slot: 0,
+                    status: CommitmentLevel::Finalized,
+                    block_time: 0,
+                },
+            );
+        }
+        for nullifier_hash in &snapshot.nullifier_hashes {
+            state.withdrawals.insert(
+                *nullifier_hash,
+                WithdrawalRecord {
+                    nullifier_hash: *nullifier_hash,
+                    // Not in the snapshot.
+                    recipient: Pubkey::default(),
+                    relayer: Pubkey::default(),
+                    fee: 0,
+                    signature: String::new(),
+                    slot: 0,
+                    status: CommitmentLevel::Finalized,
+                },
+            );
+        }
+        state.restored_until = snapshot.last_signature.clone();
+        state.version += 1;
+        Ok(())
+    }
+
+    pub fn deposit(&self, commitment: &[u8; 32]) -> Option<DepositRecord> {
+        let state = self.state.read().unwrap();
+        let idx = state.by_commitment.get(commitment)?;
+        state.deposits.get(idx).cloned()
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+fn contiguous(deposits: &BTreeMap<u32, DepositRecord>) -> impl Iterator<Item = &DepositRecord> {
+    deposits
+        .values()
+        .enumerate()
+        .take_while(|(i, d)| d.leaf_index as usize == *i)
+        .map(|(_, d)| d)
+}
+
+/// Keeps `index` current by subscribing to the program's logs over PubSub.
+///
+/// Each (re)connect subscribes first and then backfills from `source`, so a
+/// deposit landing in between is picked up by one or the other. Runs forever.
+pub async fn run_log_subscription(
+    index: Arc<DepositIndex>,
+    ws_url: String,
+    source: Arc<dyn DepositSource>,
+) {
+    let retry = RetryPolicy {
+        max_attempts: u32::MAX,
+        base_delay: Duration::from_millis(500),
+        max_delay: Duration::from_secs(30),
+    };
+    let mut attempt = 0;
+    loop {
+        match subscribe_once(&index, &ws_url, source.as_ref()).await {
+            Ok(()) => {
+                tracing::warn!("logsSubscribe stream for {} ended", index.program_id);
+                attempt = 0;
+            }
+            Err(e) => tracing::warn!("logsSubscribe for {} failed: {}", index.program_id, e),
+        }
+        tokio::time::sleep(retry.delay(attempt)).await;
+        attempt = attempt.saturating_add(1);
+    }
+}
+
+async fn subscribe_once(
+    index: &DepositIndex,
+    ws_url: &str,
+    source: &dyn DepositSource,
+) -> Result<(), String> {
+    let client = PubsubClient::new(ws_url).await.map_err(|e| e.to_string())?;
+    let (mut stream, unsubscribe) = client
+        .logs_subscribe(
+            RpcTransactionLogsFilter::Mentions(vec![index.program_id.to_string()]),
+            RpcTransactionLogsConfig {
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let added = index.backfill(source).await?;
+    tracing::info!(
+        "indexed {} deposits for {} ({} new events from backfill)",
+        index.len(),
+        index.program_id,
+        added
+    );
+
+    let mut reconcile = tokio::time::interval_at(
+        tokio::time::Instant::now() + RECONCILE_INTERVAL,
+        RECONCILE_INTERVAL,
+    );
+    loop {
+        tokio::select! {
+            resp = stream.next() => {
+                let Some(resp) = resp else { break };
+                if resp.value.err.is_some() {
+                    continue;
+                }
+                let added = index.apply_logs(
+                    &resp.value.signature,
+                    resp.context.slot,
+                    CommitmentLevel::Confirmed,
+                    &resp.value.logs,
+                );
+                if added > 0 {
+                    tracing::info!(
+                        "{} new event(s) for {} in {}",
+                        added,
+                        index.program_id,
+                        resp.value.signature
+                    );
+                }
+            }
+            _ = reconcile.tick() => {
+                if let Err(e) = index.reconcile(source).await {
+                    tracing::warn!("reconciling {} failed: {}", index.program_id, e);
+                }
+            }
+        }
+    }
+    unsubscribe().await;
+    Ok(())
+}
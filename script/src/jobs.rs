@@ -0,0 +1,170 @@
+//! Proof jobs and `Idempotency-Key` deduplication.
+//!
+//! Every proof runs as a detached task, so a client that drops its connection
+//! does not cancel a prover network job it may retry. Requests carrying the
+//! same idempotency key join the job the first one started and get the same
+//! job id and result. At most `capacity` keys are remembered: when the map
+//! is full the oldest finished job makes room, and new keys are refused while
+//! every remembered job is still proving.
+
+use axum::http::StatusCode;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+
+use crate::cache::CacheKey;
+use crate::server::ProveResponse;
+
+/// Outcome of a job: the response and whether it came from the proof cache.
+pub type JobResult = Result<(ProveResponse, bool), (StatusCode, String)>;
+
+pub type JobHandle = Shared<BoxFuture<'static, JobResult>>;
+
+/// Longest accepted idempotency key.
+pub const MAX_KEY_LEN: usize = 255;
+
+struct Job {
+    id: String,
+    /// Digest of the inputs the key was first used with.
+    fingerprint: CacheKey,
+    created: Instant,
+    handle: JobHandle,
+    /// The detached task, to tell whether it has finished even when no
+    /// caller is polling `handle`.
+    task: AbortHandle,
+}
+
+impl Job {
+    fn failed(&self) -> bool {
+        matches!(self.handle.peek(), Some(Err(_)))
+    }
+}
+
+/// Jobs started under an idempotency key, remembered for `ttl` after they
+/// were submitted, at most `capacity` of them. Failed jobs are forgotten, so
+/// a retry proves again.
+pub struct ProofJobs {
+    ttl: Duration,
+    capacity: usize,
+    jobs: Mutex<HashMap<String, Job>>,
+}
+
+impl ProofJobs {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        ProofJobs {
+            ttl,
+            capacity,
+            jobs: Mutex::default(),
+        }
+    }
+
+    /// Rejects keys that are empty, too long or not printable ASCII.
+    pub fn check_key(key: &str) -> Result<(), String> {
+        if key.is_empty() || key.len() > MAX_KEY_LEN || !key.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(format!(
+                "Idempotency-Key must be 1 to {} printable ASCII characters",
+                MAX_KEY_LEN
+            ));
+        }
+        Ok(())
+    }
+
+    /// Starts `job` as a detached task, or joins the live job submitted under
+    /// `key`. Reusing a key for other inputs is an error, and so is a new key
+    /// while `capacity` jobs are still running (`503`).
+    pub fn submit(
+        &self,
+        key: Option<&str>,
+        fingerprint: CacheKey,
+        job: impl FnOnce() -> BoxFuture<'static, JobResult>,
+    ) -> Result<(String, JobHandle), (StatusCode, String)> {
+        let Some(key) = key else {
+            return Ok((new_job_id(), spawn(job()).0));
+        };
+        Self::check_key(key).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, j| j.created.elapsed() < self.ttl && !j.failed());
+        if let Some(existing) = jobs.get(key) {
+            if existing.fingerprint != fingerprint {
+                return Err((
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "Idempotency-Key was already used for a different request".to_string(),
+                ));
+            }
+            return Ok((existing.id.clone(), existing.handle.clone()));
+        }
+        if jobs.len() >= self.capacity {
+            let oldest_finished = jobs
+                .iter()
+                .filter(|(_, j)| j.task.is_finished())
+                .min_by_key(|(_, j)| j.created)
+                .map(|(k, _)| k.clone());
+            match oldest_finished {
+                Some(oldest) => {
+                    jobs.remove(&oldest);
+                }
+                None => {
+                    return Err((
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        "too many proof jobs are running, retry later".to_string(),
+                    ))
+                }
+            }
+        }
+        let id = new_job_id();
+        let (handle, task) = spawn(job());
+        jobs.insert(
+            key.to_string(),
+            Job {
+                id: id.clone(),
+                fingerprint,
+                created: Instant::now(),
+                handle: handle.clone(),
+                task,
+            },
+        );
+        Ok((id, handle))
+    }
+
+    /// Number of remembered keys.
+    pub fn len(&self) -> usize {
+        self.jobs.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn spawn(job: BoxFuture<'static, JobResult>) -> (JobHandle, AbortHandle) {
+    let task = tokio::spawn(job);
+    let abort = task.abort_handle();
+    let handle = async move {
+        task.await.unwrap_or_else(|e| {
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("proof job failed: {}", e),
+            ))
+        })
+    }
+    .boxed()
+    .shared();
+    (handle, abort)
+}
+
+fn new_job_id() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+impl std::fmt::Debug for ProofJobs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProofJobs")
+            .field("ttl", &self.ttl)
+            .field("capacity", &self.capacity)
+            .field("keys", &self.len())
+            .finish()
+    }
+}
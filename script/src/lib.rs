@@ -0,0 +1,19 @@
+pub mod artifacts;
+pub mod cache;
+pub mod cli;
+pub mod directory;
+pub mod idl;
+pub mod indexer;
+pub mod jobs;
+pub mod note;
+pub mod pools;
+pub mod prover;
+pub mod quote;
+pub mod relayer;
+pub mod sealed;
+pub mod server;
+pub mod snapshot;
+pub mod solana;
+pub mod source;
+pub mod utils;
+pub mod verify;
@@ -0,0 +1,162 @@
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use mixer_lib::mix::NATIVE_MINT;
+use mixer_lib::secret::Secret;
+use mixer_lib::utils::{hash1, hash2, is_field_element};
+use num_bigint::BigUint;
+use regex::Regex;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use zeroize::Zeroizing;
+
+use crate::pools::SOL_DECIMALS;
+use crate::utils::biguint_to_32_le_bytes;
+
+/// A deposit note: `solana-mixer-<asset>-<units>-<nullifierHex>:<secretHex>`,
+/// where the asset is `sol` or the base58 token mint, the amount is in base
+/// units and both secrets are little-endian.
+///
+/// Older notes, `solana-mixer-<amount>-<nullifierHex>:<secretHex>` with the
+/// amount in whole SOL (e.g. `0.1`), are still accepted.
+///
+/// `Debug` redacts the secrets; [`Note::encode`] gives the note text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    /// SPL token mint, `None` for native SOL.
+    pub mint: Option<Pubkey>,
+    /// Amount in base units (lamports, or the mint's smallest unit).
+    pub amount: u64,
+    /// 32 little-endian bytes.
+    pub nullifier: Secret<[u8; 32]>,
+    /// 32 little-endian bytes.
+    pub secret: Secret<[u8; 32]>,
+}
+
+impl Note {
+    /// A note for `amount` of `mint` with a fresh nullifier and secret.
+    pub fn random(mint: Option<Pubkey>, amount: u64) -> Self {
+        let (nullifier, secret, _, _, _) = mixer_lib::utils::create_random_commitment();
+        Note {
+            mint,
+            amount,
+            nullifier: Secret::new(biguint_to_32_le_bytes(&nullifier)),
+            secret: Secret::new(biguint_to_32_le_bytes(&secret)),
+        }
+    }
+
+    pub fn parse(note: &str) -> Result<Self, String> {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| {
+            Regex::new(
+                r"^solana-mixer-(?:(?P<asset>sol|[1-9A-HJ-NP-Za-km-z]{32,44})-(?P<units>\d+)|(?P<sol>\d+(?:\.\d+)?))-(?P<nullifier>[0-9A-Fa-f]+):(?P<secret>[0-9A-Fa-f]+)$",
+            )
+            .unwrap()
+        });
+        let caps = re
+            .captures(note.trim())
+            .ok_or_else(|| "malformed note".to_string())?;
+        let hex_field = |name: &str| {
+            let bytes = Zeroizing::new(
+                hex::decode(&caps[name]).map_err(|_| format!("invalid {} in note", name))?,
+            );
+            if bytes.len() > 32 {
+                return Err(format!("{} in note is longer than 32 bytes", name));
+            }
+            let mut field = [0u8; 32];
+            field[..bytes.len()].copy_from_slice(&bytes);
+            if !is_field_element(&field) {
+                return Err(format!("{} in note is not below the field modulus", name));
+            }
+            Ok(Secret::new(field))
+        };
+        let (mint, amount) = match caps.name("sol") {
+            Some(sol) => (None, parse_units(sol.as_str(), SOL_DECIMALS)?),
+            None => {
+                let mint = match &caps["asset"] {
+                    "sol" => None,
+                    mint => Some(
+                        Pubkey::from_str(mint)
+                            .map_err(|e| format!("invalid mint in note: {}", e))?,
+                    ),
+                };
+                let amount = caps["units"]
+                    .parse()
+                    .map_err(|e| format!("invalid amount in note: {}", e))?;
+                (mint, amount)
+            }
+        };
+        Ok(Note {
+            mint,
+            amount,
+            nullifier: hex_field("nullifier")?,
+            secret: hex_field("secret")?,
+        })
+    }
+
+    /// The mint as committed in the public values.
+    pub fn mint_bytes(&self) -> [u8; 32] {
+        self.mint.map_or(NATIVE_MINT, |m| m.to_bytes())
+    }
+
+    /// The note text. Secrets are written without trailing zero bytes.
+    pub fn encode(&self) -> Secret<String> {
+        let asset = self.mint.map_or("sol".to_string(), |m| m.to_string());
+        Secret::new(format!(
+            "solana-mixer-{}-{}-{}:{}",
+            asset,
+            self.amount,
+            le_hex(&self.nullifier).expose(),
+            le_hex(&self.secret).expose()
+        ))
+    }
+
+    /// Commitment `H(nullifier, secret)` and nullifier hash `H(nullifier)`.
+    pub fn hashes(&self) -> ([u8; 32], [u8; 32]) {
+        (
+            hash2(self.nullifier.expose(), self.secret.expose()),
+            hash1(self.nullifier.expose()),
+        )
+    }
+}
+
+/// A little-endian number in decimal, as the WebSocket requests take it.
+pub fn decimal(bytes: &Secret<[u8; 32]>) -> Secret<String> {
+    Secret::new(BigUint::from_bytes_le(bytes.expose()).to_string())
+}
+
+/// Hex of a little-endian number without its high zero bytes, as notes
+/// write it.
+pub fn le_hex(bytes: &Secret<[u8; 32]>) -> Secret<String> {
+    let bytes = bytes.expose();
+    let len = bytes.iter().rposition(|&b| b != 0).map_or(1, |i| i + 1);
+    Secret::new(hex::encode(&bytes[..len]))
+}
+
+/// Parses a decimal amount such as `0.1` into base units without going through
+/// floating point.
+pub fn parse_units(amount: &str, decimals: u8) -> Result<u64, String> {
+    let (whole, frac) = amount.split_once('.').unwrap_or((amount, ""));
+    let frac = frac.trim_end_matches('0');
+    if frac.len() > decimals as usize {
+        return Err(format!(
+            "amount {} has more than {} decimals",
+            amount, decimals
+        ));
+    }
+    let digits = format!("{}{:0<width$}", whole, frac, width = decimals as usize);
+    digits
+        .parse()
+        .map_err(|e| format!("invalid amount {}: {}", amount, e))
+}
+
+/// Formats base units as a decimal amount, e.g. `100000000` with 9 decimals
+/// as `0.1`.
+pub fn format_units(units: u64, decimals: u8) -> String {
+    let scale = 10u64.pow(decimals as u32);
+    let frac = format!("{:0width$}", units % scale, width = decimals as usize);
+    let frac = frac.trim_end_matches('0');
+    if frac.is_empty() {
+        (units / scale).to_string()
+    } else {
+        format!("{}.{}", units / scale, frac)
+    }
+}
@@ -0,0 +1,277 @@
+//! Registry of the mixer pools a server instance serves.
+//!
+//! Pools are read from the JSON file named by `MIXER_POOLS`:
+//!
+//! ```json
+//! [
+//!   { "id": "sol-0.1", "program_id": "...", "cluster": "devnet", "denomination": 100000000 },
+//!   { "id": "usdc-100", "program_id": "...", "cluster": "mainnet",
+//!     "denomination": 100000000, "mint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "decimals": 6 }
+//! ]
+//! ```
+//!
+//! Without a file, `MIXER_PROGRAM_ID` alone defines one pool, keyed by its
+//! program id and without a denomination check.
+
+use anchor_client::{solana_sdk::pubkey::Pubkey, Cluster};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::indexer::DepositIndex;
+use crate::note::{format_units, Note};
+use crate::utils::TREE_DEPTH;
+use crate::verify::normalize_vkey_hash;
+
+/// Decimals of native SOL.
+pub const SOL_DECIMALS: u8 = 9;
+
+/// Hash function of a pool's commitments and Merkle tree.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Hasher {
+    /// Poseidon over BN254, circom parameters. The only hasher `mixer-program` implements.
+    #[default]
+    Poseidon,
+}
+
+/// One pool as configured.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Name used in `/api/pools/{pool_id}/...`, e.g. `sol-1`.
+    pub id: String,
+    pub program_id: String,
+    /// `mainnet`, `devnet`, `testnet`, `localnet` or an RPC URL. The server's
+    /// `SOLANA_RPC_URLS` are used when unset.
+    #[serde(default)]
+    pub cluster: Option<String>,
+    /// Fixed deposit amount in base units (lamports, or the mint's smallest unit).
+    #[serde(default)]
+    pub denomination: Option<u64>,
+    /// SPL token mint; native SOL when unset.
+    #[serde(default)]
+    pub mint: Option<String>,
+    #[serde(default = "default_decimals")]
+    pub decimals: u8,
+    #[serde(default = "default_tree_depth")]
+    pub tree_depth: usize,
+    #[serde(default)]
+    pub hasher: Hasher,
+    /// Program vkey hash the pool's on-chain verifier expects.
+    #[serde(default)]
+    pub vkey: Option<String>,
+    /// Tree snapshot file, JSON or binary, to start the indexer from. A local
+    /// path, so it is not listed by `/api/pools`.
+    #[serde(default, skip_serializing)]
+    pub snapshot: Option<String>,
+}
+
+fn default_decimals() -> u8 {
+    SOL_DECIMALS
+}
+
+fn default_tree_depth() -> usize {
+    TREE_DEPTH
+}
+
+impl PoolConfig {
+    /// A pool for `program_id` alone, as configured by `MIXER_PROGRAM_ID`.
+    pub fn for_program(program_id: &str) -> Self {
+        PoolConfig {
+            id: program_id.to_string(),
+            program_id: program_id.to_string(),
+            cluster: None,
+            denomination: None,
+            mint: None,
+            decimals: SOL_DECIMALS,
+            tree_depth: TREE_DEPTH,
+            hasher: Hasher::Poseidon,
+            vkey: None,
+            snapshot: None,
+        }
+    }
+
+    pub fn program_pubkey(&self) -> Pubkey {
+        // Checked by `PoolRegistry::new`.
+        Pubkey::from_str(&self.program_id).unwrap()
+    }
+
+    fn cluster(&self) -> Option<Result<Cluster, String>> {
+        let cluster = self.cluster.as_deref()?;
+        Some(
+            Cluster::from_str(cluster)
+                .map_err(|e| format!("pool {}: invalid cluster {}: {}", self.id, cluster, e)),
+        )
+    }
+
+    /// RPC endpoint of the pool's cluster, if one is set.
+    pub fn rpc_url(&self) -> Option<String> {
+        Some(self.cluster()?.ok()?.url().to_string())
+    }
+
+    /// PubSub endpoint of the pool's cluster, if one is set.
+    pub fn ws_url(&self) -> Option<String> {
+        Some(self.cluster()?.ok()?.ws_url().to_string())
+    }
+
+    /// SPL token mint of the pool, `None` for native SOL.
+    pub fn mint_pubkey(&self) -> Option<Pubkey> {
+        // Checked by `PoolRegistry::new`.
+        self.mint.as_deref().map(|m| Pubkey::from_str(m).unwrap())
+    }
+
+    /// Rejects notes for another mint, or whose amount is not the pool's
+    /// denomination.
+    pub fn check_note(&self, note: &Note) -> Result<(), String> {
+        if note.mint != self.mint_pubkey() {
+            let asset = |mint: Option<Pubkey>| mint.map_or("SOL".to_string(), |m| m.to_string());
+            return Err(format!(
+                "note is for {} but pool {} holds {}",
+                asset(note.mint),
+                self.id,
+                asset(self.mint_pubkey())
+            ));
+        }
+        let Some(denomination) = self.denomination else {
+            return Ok(());
+        };
+        if note.amount != denomination {
+            return Err(format!(
+                "note is for {} but pool {} takes deposits of {}",
+                format_units(note.amount, self.decimals),
+                self.id,
+                format_units(denomination, self.decimals)
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() || self.id.contains('/') {
+            return Err(format!("invalid pool id {:?}", self.id));
+        }
+        Pubkey::from_str(&self.program_id)
+            .map_err(|e| format!("pool {}: invalid program id: {}", self.id, e))?;
+        if let Some(mint) = &self.mint {
+            Pubkey::from_str(mint).map_err(|e| format!("pool {}: invalid mint: {}", self.id, e))?;
+        }
+        if let Some(cluster) = self.cluster() {
+            cluster?;
+        }
+        if let Some(vkey) = &self.vkey {
+            normalize_vkey_hash(vkey).map_err(|e| format!("pool {}: {}", self.id, e))?;
+        }
+        if self.tree_depth != TREE_DEPTH {
+            return Err(format!(
+                "pool {}: tree depth {} is not supported, mixer-program proves depth {}",
+                self.id, self.tree_depth, TREE_DEPTH
+            ));
+        }
+        if self.decimals > 19 {
+            return Err(format!("pool {}: too many decimals", self.id));
+        }
+        Ok(())
+    }
+}
+
+/// A configured pool and its live index, if one runs.
+#[derive(Debug, Clone)]
+pub struct Pool {
+    pub config: PoolConfig,
+    pub index: Option<Arc<DepositIndex>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PoolRegistry {
+    pools: Vec<Pool>,
+}
+
+impl PoolRegistry {
+    /// Validates `configs`: well-formed keys, supported tree parameters and
+    /// unique ids and program ids.
+    pub fn new(configs: Vec<PoolConfig>) -> Result<Self, String> {
+        let mut ids = HashSet::new();
+        let mut programs = HashSet::new();
+        for pool in &configs {
+            pool.validate()?;
+            if !ids.insert(pool.id.as_str()) {
+                return Err(format!("duplicate pool id {}", pool.id));
+            }
+            if !programs.insert(pool.program_id.as_str()) {
+                return Err(format!(
+                    "program {} is configured for more than one pool",
+                    pool.program_id
+                ));
+            }
+        }
+        Ok(PoolRegistry {
+            pools: configs
+                .into_iter()
+                .map(|config| Pool {
+                    config,
+                    index: None,
+                })
+                .collect(),
+        })
+    }
+
+    /// Parses a pool file. Unlike the `MIXER_PROGRAM_ID` shorthand, every pool
+    /// in a file must name its denomination.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let configs: Vec<PoolConfig> =
+            serde_json::from_str(json).map_err(|e| format!("invalid pool config: {}", e))?;
+        if let Some(pool) = configs.iter().find(|p| p.denomination.is_none()) {
+            return Err(format!("pool {} has no denomination", pool.id));
+        }
+        Self::new(configs)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("reading pool config {}: {}", path, e))?;
+        Self::from_json(&json)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Pool> {
+        self.pools.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Pool> {
+        self.pools.iter_mut()
+    }
+
+    /// Looks a pool up by id, or by program id.
+    pub fn get(&self, key: &str) -> Option<&Pool> {
+        self.pools
+            .iter()
+            .find(|p| p.config.id == key)
+            .or_else(|| self.by_program(key))
+    }
+
+    pub fn by_program(&self, program_id: &str) -> Option<&Pool> {
+        self.pools
+            .iter()
+            .find(|p| p.config.program_id == program_id)
+    }
+
+    /// Ensures every pool that names a vkey expects the one this server proves with.
+    pub fn check_vkey(&self, vkey_hash: &str) -> Result<(), String> {
+        let ours = normalize_vkey_hash(vkey_hash)?;
+        for pool in &self.pools {
+            if let Some(theirs) = &pool.config.vkey {
+                if normalize_vkey_hash(theirs)? != ours {
+                    return Err(format!(
+                        "pool {} expects vkey {} but this server proves with {}",
+                        pool.config.id, theirs, vkey_hash
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+pub use mixer_lib::mix::withdrawal_stdin;
+use serde::{Deserialize, Serialize};
+use sp1_sdk::{
+    CpuProver, ExecutionReport, NetworkProver, Prover, ProverClient, SP1ProofWithPublicValues,
+    SP1ProvingKey, SP1PublicValues, SP1Stdin, SP1VerifyingKey,
+};
+use std::ops::Deref;
+use std::str::FromStr;
+use zeroize::Zeroize;
+
+pub const SP1_NETWORK_RPC_URL: &str = "https://rpc.production.succinct.xyz";
+
+/// Where proofs are generated. Mirrors the `SP1_PROVER` modes in `.env.example`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProverBackend {
+    /// Executes the program and returns an empty (mock) proof.
+    Mock,
+    /// Proves locally on the CPU.
+    Cpu,
+    /// Proves on the Succinct prover network (`NETWORK_PRIVATE_KEY_SP1`).
+    Network,
+}
+
+impl FromStr for ProverBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mock" => Ok(ProverBackend::Mock),
+            "cpu" => Ok(ProverBackend::Cpu),
+            "network" => Ok(ProverBackend::Network),
+            other => Err(format!("unknown prover backend “{}”", other)),
+        }
+    }
+}
+
+/// An SP1 prover client for one of the supported backends.
+pub enum MixerProver {
+    Cpu(Box<CpuProver>),
+    Network(Box<NetworkProver>),
+}
+
+impl MixerProver {
+    pub fn new(backend: ProverBackend) -> Result<MixerProver, String> {
+        Ok(match backend {
+            ProverBackend::Mock => {
+                MixerProver::Cpu(Box::new(ProverClient::builder().mock().build()))
+            }
+            ProverBackend::Cpu => MixerProver::Cpu(Box::new(ProverClient::builder().cpu().build())),
+            ProverBackend::Network => {
+                let key = std::env::var("NETWORK_PRIVATE_KEY_SP1")
+                    .map_err(|_| "NETWORK_PRIVATE_KEY_SP1 is not set".to_string())?;
+                MixerProver::Network(Box::new(
+                    ProverClient::builder()
+                        .network()
+                        .private_key(&key)
+                        .rpc_url(SP1_NETWORK_RPC_URL)
+                        .build(),
+                ))
+            }
+        })
+    }
+
+    pub fn setup(&self, elf: &[u8]) -> (SP1ProvingKey, SP1VerifyingKey) {
+        match self {
+            MixerProver::Cpu(p) => p.setup(elf),
+            MixerProver::Network(p) => p.setup(elf),
+        }
+    }
+
+    /// Runs the program without proving it. Fails if any assertion in the program fails.
+    pub fn execute(
+        &self,
+        elf: &[u8],
+        stdin: &SP1Stdin,
+    ) -> Result<(SP1PublicValues, ExecutionReport), String> {
+        match self {
+            MixerProver::Cpu(p) => p.execute(elf, stdin).run(),
+            MixerProver::Network(p) => p.execute(elf, stdin).run(),
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    /// Generates a Groth16 proof for `stdin`.
+    pub fn prove_groth16(
+        &self,
+        pk: &SP1ProvingKey,
+        stdin: &SP1Stdin,
+    ) -> Result<SP1ProofWithPublicValues, String> {
+        match self {
+            MixerProver::Cpu(p) => p.prove(pk, stdin).groth16().run(),
+            MixerProver::Network(p) => p.prove(pk, stdin).groth16().run(),
+        }
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// Program input that is zeroized when dropped, since it holds the note's
+/// nullifier and secret.
+pub struct ZeroizingStdin(pub SP1Stdin);
+
+impl Deref for ZeroizingStdin {
+    type Target = SP1Stdin;
+
+    fn deref(&self) -> &SP1Stdin {
+        &self.0
+    }
+}
+
+impl Drop for ZeroizingStdin {
+    fn drop(&mut self) {
+        for buffer in &mut self.0.buffer {
+            buffer.zeroize();
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! Relayer fee quotes.
+//!
+//! Before proving a relayed withdrawal the client asks for a quote, which
+//! fixes the fee and thereby the amount the recipient gets. The fee is a
+//! basis-point share of the pool's denomination plus, for SOL pools, the
+//! relayer's costs in lamports: a fixed prover cost and the transaction fee at
+//! the current priority fee. Token pools are charged the share alone, since
+//! the server has no price for the mint.
+//!
+//! Quotes are signed with the relayer's Solana key, so any prover can check
+//! a quote the client got from a relayer and prove with exactly its relayer,
+//! fee and refund. A frontend that swaps in another relayer or a higher fee
+//! breaks the signature. This server's own quotes are also kept until they
+//! expire, so clients may name them by `quote_id`. The book holds a bounded
+//! number of them, dropping the oldest first, and each client may only ask
+//! for so many quotes a minute: every quote costs an RPC call and a signature.
+
+use anchor_client::solana_sdk::{pubkey::Pubkey, signature::Signature};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::relayer::{Relayer, STATE_SEED, WITHDRAW_COMPUTE_UNITS};
+use crate::source::DepositSource;
+
+/// Base fee of a transaction with one signature.
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+/// Domain separator of the signed quote message.
+const QUOTE_DOMAIN: &[u8] = b"mixer-fee-quote:v1";
+
+/// The window of [`QuoteBook::with_rate_limit`].
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// How the relayer prices a withdrawal.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeSchedule {
+    /// Share of the withdrawn amount, in basis points.
+    pub rate_bps: u32,
+    /// Lamports charged per withdrawal for the proof.
+    pub prover_cost: u64,
+}
+
+/// The components of a quoted fee, in base units of the pool.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeBreakdown {
+    pub rate: u64,
+    pub prover: u64,
+    pub network: u64,
+}
+
+impl FeeSchedule {
+    /// Fee for withdrawing `amount`, with the network and prover costs only
+    /// charged for SOL pools.
+    pub fn fee(&self, amount: u64, compute_unit_price: u64, sol: bool) -> FeeBreakdown {
+        let rate = (amount as u128 * self.rate_bps as u128 / 10_000) as u64;
+        if !sol {
+            return FeeBreakdown {
+                rate,
+                ..Default::default()
+            };
+        }
+        let priority =
+            (compute_unit_price as u128 * WITHDRAW_COMPUTE_UNITS as u128).div_ceil(1_000_000);
+        FeeBreakdown {
+            rate,
+            prover: self.prover_cost,
+            network: LAMPORTS_PER_SIGNATURE + priority as u64,
+        }
+    }
+}
+
+/// Priority fee to pay, in micro-lamports per compute unit: the 75th
+/// percentile of recent fees, so the withdrawal lands without overpaying.
+pub fn priority_fee_estimate(recent: &[u64]) -> u64 {
+    if recent.is_empty() {
+        return 0;
+    }
+    let mut fees = recent.to_vec();
+    fees.sort_unstable();
+    fees[(fees.len() - 1) * 3 / 4]
+}
+
+/// Priority fee to pay for a withdrawal from `program_id`, estimated from the
+/// fees recently paid by transactions writing its state account.
+pub async fn current_priority_fee(
+    source: &dyn DepositSource,
+    program_id: &Pubkey,
+) -> Result<u64, String> {
+    let (state_account, _) = Pubkey::find_program_address(&[STATE_SEED], program_id);
+    let recent = source.recent_prioritization_fees(&[state_account]).await?;
+    Ok(priority_fee_estimate(&recent))
+}
+
+/// A signed offer to relay a withdrawal from `pool_id` for `fee`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeeQuote {
+    pub id: String,
+    pub pool_id: String,
+    pub program_id: String,
+    /// Base58 relayer key, the wallet the fee is paid to.
+    pub relayer: String,
+    /// Withdrawn amount, in base units.
+    pub amount: u64,
+    pub fee: u64,
+    pub refund: u64,
+    /// What the recipient gets: `amount - fee`.
+    pub net_amount: u64,
+    pub breakdown: FeeBreakdown,
+    /// Priority fee the relayer pays, in micro-lamports per compute unit.
+    pub compute_unit_price: u64,
+    /// Unix time after which the quote is void.
+    pub expires_at: u64,
+    /// Base58 ed25519 signature of [`FeeQuote::message`] by `relayer`.
+    pub signature: String,
+}
+
+impl FeeQuote {
+    /// The signed bytes: every field the withdrawal depends on.
+    pub fn message(&self) -> Vec<u8> {
+        let mut out = QUOTE_DOMAIN.to_vec();
+        for s in [&self.id, &self.pool_id, &self.program_id, &self.relayer] {
+            out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            out.extend_from_slice(s.as_bytes());
+        }
+        for n in [
+            self.amount,
+            self.fee,
+            self.refund,
+            self.compute_unit_price,
+            self.expires_at,
+        ] {
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn is_expired(&self) -> bool {
+        unix_now() >= self.expires_at
+    }
+
+    /// Checks that `relayer` signed the quote and that it is still valid.
+    pub fn verify(&self) -> Result<(), String> {
+        let relayer = Pubkey::from_str(&self.relayer)
+            .map_err(|e| format!("invalid relayer in fee quote: {}", e))?;
+        let signature = Signature::from_str(&self.signature)
+            .map_err(|e| format!("invalid fee quote signature: {}", e))?;
+        if !signature.verify(relayer.as_ref(), &self.message()) {
+            return Err(format!(
+                "fee quote {} is not signed by relayer {}",
+                self.id, self.relayer
+            ));
+        }
+        if self.fee >= self.amount || self.net_amount != self.amount - self.fee {
+            return Err(format!("fee quote {} does not add up", self.id));
+        }
+        if self.is_expired() {
+            return Err(format!("fee quote {} has expired", self.id));
+        }
+        Ok(())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// What a quote is for.
+#[derive(Debug, Clone)]
+pub struct QuoteRequest<'a> {
+    pub pool_id: &'a str,
+    pub program_id: &'a str,
+    pub amount: u64,
+    /// Token pools are only charged the rate.
+    pub sol: bool,
+    pub compute_unit_price: u64,
+}
+
+/// Quotes issued by this server's relayer, until they expire or the book is
+/// full.
+pub struct QuoteBook {
+    schedule: FeeSchedule,
+    ttl: Duration,
+    capacity: usize,
+    /// Quotes per client and minute, `0` for no limit.
+    per_minute: u32,
+    quotes: Mutex<HashMap<String, FeeQuote>>,
+    /// Start of each client's current window and its quotes in it.
+    clients: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl QuoteBook {
+    pub fn new(schedule: FeeSchedule, ttl: Duration) -> Self {
+        QuoteBook {
+            schedule,
+            ttl,
+            capacity: 1024,
+            per_minute: 0,
+            quotes: Mutex::default(),
+            clients: Mutex::default(),
+        }
+    }
+
+    /// Keeps at most `capacity` quotes, dropping the oldest to make room.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Lets each client ask for `per_minute` quotes a minute. `0` disables the
+    /// limit.
+    pub fn with_rate_limit(mut self, per_minute: u32) -> Self {
+        self.per_minute = per_minute;
+        self
+    }
+
+    pub fn schedule(&self) -> FeeSchedule {
+        self.schedule
+    }
+
+    /// Counts a quote request by `client` against its limit, refusing it when
+    /// the client already had its share of the current minute.
+    pub fn admit(&self, client: IpAddr) -> Result<(), String> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|_, (start, _)| now.duration_since(*start) < RATE_WINDOW);
+        let (_, count) = clients.entry(client).or_insert((now, 0));
+        if *count >= self.per_minute {
+            return Err(format!(
+                "more than {} fee quotes a minute, retry later",
+                self.per_minute
+            ));
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Prices, signs and remembers a quote.
+    pub fn issue(&self, relayer: &Relayer, req: QuoteRequest) -> Result<FeeQuote, String> {
+        let breakdown = self
+            .schedule
+            .fee(req.amount, req.compute_unit_price, req.sol);
+        let fee = breakdown.rate + breakdown.prover + breakdown.network;
+        if fee >= req.amount {
+            return Err(format!(
+                "fee {} would take the whole withdrawal of {}",
+                fee, req.amount
+            ));
+        }
+        let mut quote = FeeQuote {
+            id: hex::encode(rand::random::<[u8; 16]>()),
+            pool_id: req.pool_id.to_string(),
+            program_id: req.program_id.to_string(),
+            relayer: relayer.pubkey().to_string(),
+            amount: req.amount,
+            fee,
+            refund: 0,
+            net_amount: req.amount - fee,
+            breakdown,
+            compute_unit_price: req.compute_unit_price,
+            expires_at: unix_now() + self.ttl.as_secs(),
+            signature: String::new(),
+        };
+        quote.signature = relayer.sign(&quote.message()).to_string();
+
+        let mut quotes = self.quotes.lock().unwrap();
+        quotes.retain(|_, q| !q.is_expired());
+        while quotes.len() >= self.capacity {
+            let oldest = quotes
+                .values()
+                .min_by_key(|q| q.expires_at)
+                .map(|q| q.id.clone())
+                .unwrap();
+            quotes.remove(&oldest);
+        }
+        quotes.insert(quote.id.clone(), quote.clone());
+        Ok(quote)
+    }
+
+    /// The live quote `id`.
+    pub fn get(&self, id: &str) -> Result<FeeQuote, String> {
+        let quotes = self.quotes.lock().unwrap();
+        let quote = quotes
+            .get(id)
+            .ok_or_else(|| format!("unknown fee quote {}", id))?;
+        if quote.is_expired() {
+            return Err(format!("fee quote {} has expired", id));
+        }
+        Ok(quote.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.quotes.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl std::fmt::Debug for QuoteBook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuoteBook")
+            .field("schedule", &self.schedule)
+            .field("ttl", &self.ttl)
+            .field("capacity", &self.capacity)
+            .field("per_minute", &self.per_minute)
+            .field("quotes", &self.len())
+            .finish()
+    }
+}
@@ -0,0 +1,294 @@
+//! Submission of withdrawals on behalf of the user.
+//!
+//! A withdrawal sent from the recipient's own wallet links the recipient to
+//! whoever funded the fees. The relayer instead signs and pays for the
+//! mixer's `withdraw` instruction with its own key and is paid back through
+//! the proof's `fee`.
+//!
+//! `withdraw` takes the proof in the `groth16-solana` layout and the raw
+//! public values:
+//!
+//! | account           | seeds / address                         | flags            |
+//! |-------------------|-----------------------------------------|------------------|
+//! | relayer           | the relayer key                         | signer, writable |
+//! | state             | `["mixer_state"]`                       | writable         |
+//! | nullifier         | `["nullifier", nullifier_hash]`         | writable         |
+//! | recipient         | public `recipient`                      | writable         |
+//! | fee account       | public `relayer`                        | writable         |
+//! | system program    |                                         |                  |
+//!
+//! Token pools append the mint, the vault (the state's associated token
+//! account) and the token program.
+
+use anchor_client::solana_sdk::{
+    commitment_config::CommitmentLevel,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    signer::keypair::read_keypair_file,
+    system_program,
+    transaction::Transaction,
+};
+use mixer_lib::mix::{PublicValues, NATIVE_MINT};
+use sha2::{Digest, Sha256};
+use spl_associated_token_account_client::address::get_associated_token_address;
+use std::time::Duration;
+
+use crate::solana::{self, SolanaProof};
+use crate::source::{commitment_level, is_at_least, DepositSource};
+
+pub const STATE_SEED: &[u8] = b"mixer_state";
+pub const NULLIFIER_SEED: &[u8] = b"nullifier";
+
+/// SPL Token program.
+pub const TOKEN_PROGRAM_ID: Pubkey =
+    anchor_client::solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// Compute budget of a withdrawal; the Groth16 check alone takes ~200k units.
+pub const WITHDRAW_COMPUTE_UNITS: u32 = 400_000;
+
+/// Anchor instruction discriminator: `sha256("global:<name>")[..8]`.
+pub fn instruction_discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("global:{}", name));
+    hash[..8].try_into().unwrap()
+}
+
+fn public_values(proof: &SolanaProof) -> Result<PublicValues, String> {
+    PublicValues::decode(&proof.public_values).ok_or_else(|| {
+        format!(
+            "unexpected public values length {}",
+            proof.public_values.len()
+        )
+    })
+}
+
+/// Refuses `proof` unless it commits to exactly `expected` and verifies
+/// against the program vkey `vkey_hash`. The client controls the proof, so
+/// without this the relayer would pay for withdrawals with another fee, or
+/// for proofs the program rejects.
+pub fn check_proof(
+    proof: &SolanaProof,
+    expected: &PublicValues,
+    vkey_hash: &str,
+) -> Result<(), String> {
+    let got = public_values(proof)?;
+    let mismatch = [
+        ("root", got.root != expected.root),
+        (
+            "nullifier hash",
+            got.nullifier_hash != expected.nullifier_hash,
+        ),
+        ("recipient", got.recipient != expected.recipient),
+        ("relayer", got.relayer != expected.relayer),
+        ("fee", got.fee != expected.fee),
+        ("refund", got.refund != expected.refund),
+        ("mint", got.mint != expected.mint),
+        ("amount", got.amount != expected.amount),
+    ]
+    .into_iter()
+    .find(|(_, differs)| *differs);
+    if let Some((field, _)) = mismatch {
+        return Err(format!(
+            "proof commits to another {} than the withdrawal",
+            field
+        ));
+    }
+    solana::verify_solana_proof(proof, vkey_hash)
+        .map_err(|e| format!("proof does not verify: {}", e))
+}
+
+/// The mixer's `withdraw` instruction for `proof`, paid for by `payer`.
+pub fn withdraw_instruction(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    proof: &SolanaProof,
+) -> Result<Instruction, String> {
+    let public_values = public_values(proof)?;
+    if proof.proof_a.len() != 64 || proof.proof_b.len() != 128 || proof.proof_c.len() != 64 {
+        return Err("proof is not in the groth16-solana layout".to_string());
+    }
+
+    let mut data = instruction_discriminator("withdraw").to_vec();
+    data.extend_from_slice(&proof.proof_a);
+    data.extend_from_slice(&proof.proof_b);
+    data.extend_from_slice(&proof.proof_c);
+    data.extend_from_slice(&(proof.public_values.len() as u32).to_le_bytes());
+    data.extend_from_slice(&proof.public_values);
+
+    let (state, _) = Pubkey::find_program_address(&[STATE_SEED], program_id);
+    let (nullifier, _) =
+        Pubkey::find_program_address(&[NULLIFIER_SEED, &public_values.nullifier_hash], program_id);
+    let mut accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(state, false),
+        AccountMeta::new(nullifier, false),
+        AccountMeta::new(Pubkey::new_from_array(public_values.recipient), false),
+        AccountMeta::new(Pubkey::new_from_array(public_values.relayer), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if public_values.mint != NATIVE_MINT {
+        let mint = Pubkey::new_from_array(public_values.mint);
+        accounts.extend([
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new(get_associated_token_address(&state, &mint), false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ]);
+    }
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Signs and submits withdrawals with the relayer key.
+pub struct Relayer {
+    keypair: Keypair,
+    commitment: CommitmentLevel,
+    confirm_timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl Relayer {
+    pub fn new(keypair: Keypair) -> Self {
+        Relayer {
+            keypair,
+            commitment: CommitmentLevel::Confirmed,
+            confirm_timeout: Duration::from_secs(90),
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+
+    /// Loads a keypair file as written by `solana-keygen`.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        read_keypair_file(path)
+            .map(Self::new)
+            .map_err(|e| format!("reading relayer keypair {}: {}", path, e))
+    }
+
+    /// Level at which a withdrawal counts as landed. Defaults to `confirmed`.
+    pub fn with_commitment(mut self, commitment: CommitmentLevel) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    /// How long to wait for that level. A transaction whose blockhash expired
+    /// in the meantime never lands.
+    pub fn with_confirm_timeout(mut self, timeout: Duration) -> Self {
+        self.confirm_timeout = timeout;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    /// Signs `message` with the relayer key.
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        self.keypair.sign_message(message)
+    }
+
+    /// Account the fee of a withdrawal of `mint` is paid to: the relayer key
+    /// itself for SOL, its associated token account otherwise.
+    pub fn fee_account(&self, mint: &[u8; 32]) -> Pubkey {
+        if *mint == NATIVE_MINT {
+            self.pubkey()
+        } else {
+            get_associated_token_address(&self.pubkey(), &Pubkey::new_from_array(*mint))
+        }
+    }
+
+    /// Builds and signs the withdrawal transaction, paying
+    /// `compute_unit_price` micro-lamports per compute unit. Proofs naming
+    /// another relayer are refused: the fee would go to someone else.
+    pub fn withdraw_transaction(
+        &self,
+        program_id: &Pubkey,
+        proof: &SolanaProof,
+        compute_unit_price: u64,
+        recent_blockhash: Hash,
+    ) -> Result<Transaction, String> {
+        let public_values = public_values(proof)?;
+        let expected = self.fee_account(&public_values.mint);
+        if public_values.relayer != expected.to_bytes() {
+            return Err(format!(
+                "proof pays its fee to {}, not to this relayer ({})",
+                Pubkey::new_from_array(public_values.relayer),
+                expected
+            ));
+        }
+        let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            WITHDRAW_COMPUTE_UNITS,
+        )];
+        if compute_unit_price > 0 {
+            ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
+                compute_unit_price,
+            ));
+        }
+        ixs.push(withdraw_instruction(program_id, &self.pubkey(), proof)?);
+        Ok(Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&self.pubkey()),
+            &[&self.keypair],
+            recent_blockhash,
+        ))
+    }
+
+    /// Signs and sends the withdrawal of `proof`, returning its signature.
+    pub async fn submit(
+        &self,
+        source: &dyn DepositSource,
+        program_id: &Pubkey,
+        proof: &SolanaProof,
+        compute_unit_price: u64,
+    ) -> Result<Signature, String> {
+        let blockhash = source.latest_blockhash().await?;
+        let tx = self.withdraw_transaction(program_id, proof, compute_unit_price, blockhash)?;
+        source.send_transaction(&tx).await
+    }
+
+    /// Waits until `signature` reaches the relayer's commitment level and
+    /// returns its slot. Fails if the transaction failed on-chain or did not
+    /// land in time.
+    pub async fn confirm(
+        &self,
+        source: &dyn DepositSource,
+        signature: &Signature,
+    ) -> Result<u64, String> {
+        let deadline = tokio::time::Instant::now() + self.confirm_timeout;
+        loop {
+            if let Some(status) = source.signature_status(signature).await? {
+                if let Some(err) = status.err {
+                    return Err(format!("withdrawal {} failed: {}", signature, err));
+                }
+                let level = commitment_level(status.confirmation_status.as_ref(), self.commitment);
+                if is_at_least(level, self.commitment) {
+                    return Ok(status.slot);
+                }
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(format!(
+                    "withdrawal {} not {:?} after {:?}",
+                    signature, self.commitment, self.confirm_timeout
+                ));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+impl std::fmt::Debug for Relayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Relayer")
+            .field("pubkey", &self.pubkey())
+            .field("commitment", &self.commitment)
+            .finish()
+    }
+}
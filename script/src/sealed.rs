@@ -0,0 +1,139 @@
+//! Private inputs encrypted to the prover.
+//!
+//! A TLS-terminating proxy in front of the prover would otherwise see the
+//! nullifier and secret of every note it forwards. The server publishes an
+//! X25519 key and accepts the two values sealed to it with HPKE (RFC 9180,
+//! base mode): DHKEM(X25519, HKDF-SHA256), HKDF-SHA256 and ChaCha20-Poly1305,
+//! with [`HPKE_INFO`] as `info` and no associated data. The plaintext is the
+//! nullifier followed by the secret, each as 32 little-endian bytes.
+//!
+//! Only the proving path opens sealed inputs, and the plaintext is zeroized as
+//! soon as it has been copied into the program input.
+
+use hpke::{
+    aead::ChaCha20Poly1305, kdf::HkdfSha256, kem::X25519HkdfSha256, Deserializable, Kem, OpModeR,
+    OpModeS, Serializable,
+};
+use mixer_lib::secret::Secret;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+type InputKem = X25519HkdfSha256;
+
+/// HPKE `info` binding ciphertexts to this use.
+pub const HPKE_INFO: &[u8] = b"mixer-prove-server:private-inputs:v1";
+
+/// The HPKE suite, as published by `GET /api/input-key`.
+pub const HPKE_SUITE: &str = "DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, ChaCha20Poly1305";
+
+/// Nullifier and secret of a note, as 32 little-endian bytes each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivateInputs {
+    pub nullifier: Secret<[u8; 32]>,
+    pub secret: Secret<[u8; 32]>,
+}
+
+/// [`PrivateInputs`] sealed to the server's input key.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SealedInputs {
+    /// Hex HPKE encapsulated key, 32 bytes.
+    pub enc: String,
+    /// Hex ciphertext of the 64-byte plaintext, with its tag.
+    pub ciphertext: String,
+}
+
+impl SealedInputs {
+    /// Seals `inputs` to `public_key`, as a client does.
+    pub fn seal(public_key: &[u8; 32], inputs: &PrivateInputs) -> Result<Self, String> {
+        let public_key = <InputKem as Kem>::PublicKey::from_bytes(public_key)
+            .map_err(|e| format!("invalid input key: {}", e))?;
+        let mut plaintext = Zeroizing::new([0u8; 64]);
+        plaintext[..32].copy_from_slice(inputs.nullifier.expose());
+        plaintext[32..].copy_from_slice(inputs.secret.expose());
+        let (enc, ciphertext) =
+            hpke::single_shot_seal::<ChaCha20Poly1305, HkdfSha256, InputKem, _>(
+                &OpModeS::Base,
+                &public_key,
+                HPKE_INFO,
+                &plaintext[..],
+                &[],
+                &mut rand::thread_rng(),
+            )
+            .map_err(|e| format!("sealing private inputs: {}", e))?;
+        Ok(SealedInputs {
+            enc: hex::encode(enc.to_bytes()),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+}
+
+/// The server's X25519 key for [`SealedInputs`].
+pub struct InputKey {
+    secret: <InputKem as Kem>::PrivateKey,
+    public: <InputKem as Kem>::PublicKey,
+}
+
+impl InputKey {
+    /// A fresh key. Inputs sealed to it cannot be opened after a restart.
+    pub fn generate() -> Self {
+        let (secret, public) = InputKem::gen_keypair(&mut rand::thread_rng());
+        InputKey { secret, public }
+    }
+
+    /// Loads a hex-encoded 32-byte X25519 secret key.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = Zeroizing::new(
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("reading input key {}: {}", path, e))?,
+        );
+        let bytes = Zeroizing::new(
+            hex::decode(text.trim()).map_err(|e| format!("invalid input key {}: {}", path, e))?,
+        );
+        let secret = <InputKem as Kem>::PrivateKey::from_bytes(&bytes)
+            .map_err(|e| format!("invalid input key {}: {}", path, e))?;
+        let public = InputKem::sk_to_pk(&secret);
+        Ok(InputKey { secret, public })
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public.to_bytes().into()
+    }
+
+    /// Decrypts `sealed`. The plaintext buffer is zeroized before returning.
+    pub fn open(&self, sealed: &SealedInputs) -> Result<PrivateInputs, String> {
+        let enc = hex::decode(&sealed.enc).map_err(|e| format!("invalid sealed `enc`: {}", e))?;
+        let enc = <InputKem as Kem>::EncappedKey::from_bytes(&enc)
+            .map_err(|e| format!("invalid sealed `enc`: {}", e))?;
+        let ciphertext = hex::decode(&sealed.ciphertext)
+            .map_err(|e| format!("invalid sealed `ciphertext`: {}", e))?;
+        let plaintext = Zeroizing::new(
+            hpke::single_shot_open::<ChaCha20Poly1305, HkdfSha256, InputKem>(
+                &OpModeR::Base,
+                &self.secret,
+                &enc,
+                HPKE_INFO,
+                &ciphertext,
+                &[],
+            )
+            .map_err(|_| "sealed inputs do not open with this server's input key".to_string())?,
+        );
+        if plaintext.len() != 64 {
+            return Err(format!(
+                "sealed inputs are {} bytes, expected 64",
+                plaintext.len()
+            ));
+        }
+        Ok(PrivateInputs {
+            nullifier: Secret::new(plaintext[..32].try_into().unwrap()),
+            secret: Secret::new(plaintext[32..].try_into().unwrap()),
+        })
+    }
+}
+
+impl std::fmt::Debug for InputKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InputKey")
+            .field("public_key", &hex::encode(self.public_key()))
+            .finish()
+    }
+}
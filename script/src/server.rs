@@ -0,0 +1,1957 @@
+use anchor_client::solana_sdk::{commitment_config::CommitmentLevel, pubkey::Pubkey};
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::{ConnectInfo, FromRef, Json, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use futures_util::FutureExt;
+use hex::{encode as hex_encode, FromHex};
+use mixer_lib::mix::{PublicValues, WithdrawalInputs, NATIVE_MINT};
+use mixer_lib::secret::Secret;
+use mixer_lib::tree::{MerklePath, SubtreeSnapshot, SUBTREE_HEIGHT};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_poseidon::{hashv, Endianness, Parameters};
+use sp1_sdk::{include_elf, HashableKey, SP1PublicValues, SP1Stdin};
+use spl_associated_token_account_client::address::get_associated_token_address;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceBuilder;
+use tower_http::{catch_panic::CatchPanicLayer, cors::CorsLayer};
+use zeroize::Zeroizing;
+
+use crate::cache::ProofCache;
+use crate::directory::{Registration, RelayerDirectory, RelayerEntry};
+use crate::idl::EventDecoder;
+use crate::indexer::DepositIndex;
+use crate::jobs::ProofJobs;
+use crate::note::{decimal, le_hex, parse_units, Note};
+use crate::pools::{Pool, PoolConfig, PoolRegistry, SOL_DECIMALS};
+use crate::prover::{withdrawal_stdin, MixerProver, ProverBackend, ZeroizingStdin};
+use crate::quote::{current_priority_fee, FeeQuote, FeeSchedule, QuoteBook, QuoteRequest};
+use crate::relayer::{check_proof, Relayer};
+use crate::sealed::{InputKey, PrivateInputs, SealedInputs, HPKE_INFO, HPKE_SUITE};
+use crate::snapshot::SnapshotFormat;
+use crate::solana::{self, ProofFormat, SolanaProof};
+use crate::source::RpcDepositSource;
+use crate::utils::{self, *};
+use crate::verify;
+
+pub const MIXER_ELF: &[u8] = include_elf!("mixer-program");
+const MERKLE_LEVELS: usize = 20;
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ProveRequest {
+    // ─── Public inputs ─────
+    pub root: String,           // hex, 32 bytes
+    pub nullifier_hash: String, // hex, 32 bytes
+    pub recipient: String,      // hex, 32 bytes (Solana Pubkey)
+    pub relayer: String,        // hex, 32 bytes
+    pub fee: u64,
+    pub refund: u64,
+    #[serde(default)]
+    pub mint: Option<String>, // hex, 32 bytes; native SOL when unset
+    #[serde(default)]
+    pub amount: u64, // pool denomination, base units of `mint`
+    // ─── Private inputs ──────
+    #[serde(default)]
+    pub nullifier: Secret<String>, // hex, 32 bytes
+    #[serde(default)]
+    pub secret: Secret<String>, // hex, 32 bytes
+    /// Nullifier and secret sealed to the server's input key, instead of the
+    /// two fields above.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<SealedInputs>,
+    // ─── Merkle path ────────
+    pub path_elements: Vec<String>, // each hex, 32 bytes
+    pub path_indices: Vec<u8>,      // each 0 or 1
+    // ─── Output ─────────────
+    #[serde(default)]
+    pub format: ProofFormat,
+    /// Only execute the program and report cycles and public values.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProveResponse {
+    pub proof: String, // hex-encoded Groth16 proof
+    pub public_inputs: SP1PublicValues,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solana: Option<SolanaProof>, // set when `format` is `solana`
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ProveResponseCustom {
+    proof: String,
+    public_inputs: PublicInputsWrapper,
+    #[serde(default)]
+    solana: Option<SolanaProof>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct PublicInputsWrapper {
+    buffer: BufferData,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct BufferData {
+    data: Vec<u8>,
+}
+
+/// Parses and validates the hex inputs of a prove request, opening its
+/// sealed private inputs with `key`.
+fn withdrawal_inputs(
+    req: &ProveRequest,
+    key: &InputKey,
+) -> Result<WithdrawalInputs, (StatusCode, String)> {
+    macro_rules! hex32 {
+
+    ([ $($byte:expr),* ]) => {{
+
+        let arr: [u8;32] = [ $($byte),* ];
+        arr
+    }};
+    ($s:expr) => {{
+        let s: &str = &$s;
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        let vec = Vec::from_hex(s)
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid hex “{}”: {}", s, e)))?;
+        if vec.len() != 32 {
+            return Err((StatusCode::BAD_REQUEST, format!("hex length != 32: “{}”", s)));
+        }
+        let mut arr = [0u8;32];
+        arr.copy_from_slice(&vec);
+        arr
+    }};
+}
+
+    let root_arr = hex32!(req.root);
+    let null_hash_arr = hex32!(req.nullifier_hash);
+    let recipient_arr = hex32!(req.recipient);
+    let relayer_arr = hex32!(req.relayer);
+    // Parse errors must not echo the private inputs.
+    let secret32 = |what: &str, s: &str| -> Result<Secret<[u8; 32]>, (StatusCode, String)> {
+        let bytes = Zeroizing::new(
+            Vec::from_hex(s.strip_prefix("0x").unwrap_or(s))
+                .map_err(|_| (StatusCode::BAD_REQUEST, format!("invalid {} hex", what)))?,
+        );
+        <[u8; 32]>::try_from(&bytes[..])
+            .map(Secret::new)
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("{} is not 32 bytes", what)))
+    };
+    let private = match &req.sealed {
+        Some(_) if !req.nullifier.expose().is_empty() || !req.secret.expose().is_empty() => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "send either `sealed` or `nullifier` and `secret`".to_string(),
+            ))
+        }
+        Some(sealed) => {
+            let private = key
+                .open(sealed)
+                .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+            let nullifier_hash =
+                mixer_lib::utils::try_hash1(private.nullifier.expose()).map_err(|_| {
+                    (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "sealed nullifier is not below the field modulus".to_string(),
+                    )
+                })?;
+            if nullifier_hash != null_hash_arr {
+                return Err((
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "sealed inputs do not match `nullifier_hash`".to_string(),
+                ));
+            }
+            private
+        }
+        None => PrivateInputs {
+            nullifier: secret32("nullifier", req.nullifier.expose())?,
+            secret: secret32("secret", req.secret.expose())?,
+        },
+    };
+    // The program hashes them; values above the modulus would only fail there.
+    for (what, value) in [
+        ("nullifier", &private.nullifier),
+        ("secret", &private.secret),
+    ] {
+        if !mixer_lib::utils::is_field_element(value.expose()) {
+            return Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("{} is not below the field modulus", what),
+            ));
+        }
+    }
+    let mint_arr = match &req.mint {
+        Some(mint) => hex32!(mint),
+        None => NATIVE_MINT,
+    };
+
+    if req.path_elements.len() != MERKLE_LEVELS || req.path_indices.len() != MERKLE_LEVELS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Expected {} path elements & indices, got {} elems and {} idxs",
+                MERKLE_LEVELS,
+                req.path_elements.len(),
+                req.path_indices.len()
+            ),
+        ));
+    }
+    let mut path_elems = [[0u8; 32]; MERKLE_LEVELS];
+    for (i, hexstr) in req.path_elements.iter().enumerate() {
+        path_elems[i] = hex32!(hexstr);
+    }
+    let path_idxs = req.path_indices.clone();
+
+    Ok(WithdrawalInputs {
+        root: root_arr,
+        nullifier_hash: null_hash_arr,
+        recipient: recipient_arr,
+        relayer: relayer_arr,
+        fee: req.fee,
+        refund: req.refund,
+        mint: mint_arr,
+        amount: req.amount,
+        nullifier: private.nullifier,
+        secret: private.secret,
+        path_elements: path_elems,
+        path_indices: path_idxs.try_into().expect("wrong number of indices"),
+    })
+}
+
+/// Response header telling whether a proof came from the [`ProofCache`].
+pub const PROOF_CACHE_HEADER: &str = "x-proof-cache";
+
+/// Request header naming a proof job; retries with the same key join it.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Response header with the id of the job that produced the proof.
+pub const PROOF_JOB_HEADER: &str = "x-proof-job";
+
+/// The request's `Idempotency-Key`, if any.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<&str>, (StatusCode, String)> {
+    headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .map(|v| {
+            v.to_str().map_err(|_| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    "Idempotency-Key must be printable ASCII".to_string(),
+                )
+            })
+        })
+        .transpose()
+}
+
+async fn prove_mix(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<ProveRequest>,
+) -> Result<Response, (StatusCode, String)> {
+    if req.dry_run {
+        return Ok((StatusCode::OK, Json(dry_run(&state, &req)?)).into_response());
+    }
+    let inputs = withdrawal_inputs(&req, &state.input_key)?;
+    let stdin = ZeroizingStdin(withdrawal_stdin(&inputs));
+    let public_values = inputs.public_values();
+    drop(inputs);
+    let format = req.format;
+
+    let key = ProofCache::key(&stdin, format);
+    let (job_id, job) = state.jobs.submit(idempotency_key(&headers)?, key, || {
+        let proofs = state.proofs.clone();
+        let config = state.config.clone();
+        async move {
+            // Proving blocks for minutes; keep it off the runtime's workers.
+            proofs
+                .get_or_prove(key, public_values, move || async move {
+                    tokio::task::spawn_blocking(move || prove(&config, &stdin, format))
+                        .await
+                        .unwrap_or_else(|e| {
+                            Err((
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                format!("proof job failed: {}", e),
+                            ))
+                        })
+                })
+                .await
+        }
+        .boxed()
+    })?;
+    let (response, hit) = job.await.inspect_err(|(status, e)| {
+        tracing::warn!(job = %job_id, status = status.as_u16(), "proof failed: {}", e)
+    })?;
+    tracing::info!(job = %job_id, cache_hit = hit, ?format, "proof ready");
+
+    Ok((
+        StatusCode::OK,
+        [
+            (PROOF_CACHE_HEADER, if hit { "hit" } else { "miss" }),
+            (PROOF_JOB_HEADER, job_id.as_str()),
+        ],
+        Json(response),
+    )
+        .into_response())
+}
+
+/// Proves `stdin` with the configured backend and checks the proof.
+pub fn prove(
+    config: &ServerConfig,
+    stdin: &SP1Stdin,
+    format: ProofFormat,
+) -> Result<ProveResponse, (StatusCode, String)> {
+    let client =
+        MixerProver::new(config.prover).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let (pk, vk) = client.setup(MIXER_ELF);
+    let proof = match client.prove_groth16(&pk, stdin) {
+        Ok(p) => p,
+        Err(e) => {
+            let msg = format!("❌ proof generation failed: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, msg));
+        }
+    };
+    if config.verify_proofs() {
+        if let Err(e) = verify::verify_groth16(
+            &proof.bytes(),
+            proof.public_values.as_slice(),
+            &vk.bytes32(),
+        ) {
+            let msg = format!("❌ proof verification failed: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, msg));
+        }
+    }
+
+    let solana = match format {
+        ProofFormat::Sp1 => None,
+        ProofFormat::Solana => Some(
+            solana::to_solana_proof(
+                &proof.bytes(),
+                proof.public_values.as_slice(),
+                &vk.bytes32(),
+            )
+            .map_err(|e| {
+                let msg = format!("❌ Solana proof encoding failed: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, msg)
+            })?,
+        ),
+    };
+
+    Ok(ProveResponse {
+        proof: hex_encode(proof.bytes()),
+        public_inputs: proof.public_values,
+        solana,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DryRunResponse {
+    pub cycles: u64,
+    pub public_values: String, // hex, committed public values
+    pub root: String,
+    pub nullifier_hash: String,
+    pub recipient: String,
+    pub relayer: String,
+    pub fee: u64,
+    pub refund: u64,
+    pub mint: String,
+    pub amount: u64,
+}
+
+async fn prove_mix_dry_run(
+    State(state): State<AppState>,
+    Json(req): Json<ProveRequest>,
+) -> Result<(StatusCode, Json<DryRunResponse>), (StatusCode, String)> {
+    Ok((StatusCode::OK, Json(dry_run(&state, &req)?)))
+}
+
+/// Executes `MIXER_ELF` on the request without proving it. Execution is local
+/// regardless of the configured backend, so no prover credits are spent.
+fn dry_run(state: &AppState, req: &ProveRequest) -> Result<DryRunResponse, (StatusCode, String)> {
+    execute_withdrawal(&withdrawal_inputs(req, &state.input_key)?)
+}
+
+/// Executes `MIXER_ELF` on `inputs` with the mock prover and checks the
+/// committed public values against them.
+pub fn execute_withdrawal(
+    inputs: &WithdrawalInputs,
+) -> Result<DryRunResponse, (StatusCode, String)> {
+    let stdin = ZeroizingStdin(withdrawal_stdin(inputs));
+
+    let client = MixerProver::new(ProverBackend::Mock)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let (public_values, report) = client.execute(MIXER_ELF, &stdin).map_err(|e| {
+        let msg = format!("❌ execution failed: {}", e);
+        (StatusCode::UNPROCESSABLE_ENTITY, msg)
+    })?;
+
+    let decoded = PublicValues::decode(public_values.as_slice()).ok_or((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!(
+            "unexpected public values length {}",
+            public_values.as_slice().len()
+        ),
+    ))?;
+    if decoded != inputs.public_values() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "committed public values do not match the request".to_string(),
+        ));
+    }
+
+    Ok(DryRunResponse {
+        cycles: report.total_instruction_count(),
+        public_values: hex_encode(public_values.as_slice()),
+        root: to_hex32(&decoded.root),
+        nullifier_hash: to_hex32(&decoded.nullifier_hash),
+        recipient: to_hex32(&decoded.recipient),
+        relayer: to_hex32(&decoded.relayer),
+        fee: decoded.fee,
+        refund: decoded.refund,
+        mint: to_hex32(&decoded.mint),
+        amount: decoded.amount,
+    })
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VerifyRequest {
+    pub proof: String,         // hex, SP1 Groth16 proof bytes
+    pub public_values: String, // hex, committed public values
+    pub vkey_hash: String,     // hex, 32 bytes (`vk.bytes32()`)
+}
+
+#[derive(Serialize, Debug)]
+pub struct VerifyResponse {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+async fn verify_proof(Json(req): Json<VerifyRequest>) -> impl IntoResponse {
+    let decode = |s: &str| Vec::from_hex(s.strip_prefix("0x").unwrap_or(s));
+    let proof = decode(&req.proof)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("invalid proof hex: {}", e)))?;
+    let public_values = decode(&req.public_values).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("invalid public values hex: {}", e),
+        )
+    })?;
+    let vkey_hash =
+        verify::normalize_vkey_hash(&req.vkey_hash).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let res = verify::verify_groth16(&proof, &public_values, &vkey_hash);
+    Ok::<_, (StatusCode, String)>((
+        StatusCode::OK,
+        Json(VerifyResponse {
+            valid: res.is_ok(),
+            error: res.err(),
+        }),
+    ))
+}
+
+#[derive(Serialize)]
+pub struct DepositDetails {
+    pub nullifier: Secret<String>,
+    pub secret: Secret<String>,
+    pub note: Secret<String>,
+    pub commitment: [u8; 32],
+}
+
+impl DepositDetails {
+    pub fn new(note: &Note) -> Self {
+        DepositDetails {
+            nullifier: le_hex(&note.nullifier),
+            secret: le_hex(&note.secret),
+            note: note.encode(),
+            commitment: note.hashes().0,
+        }
+    }
+}
+
+/// The deposit to create a note for: `units` of `mint`, or `amount` SOL.
+#[derive(Deserialize)]
+pub struct GenerateDepositDetailsRequest {
+    /// Whole SOL, e.g. `0.1`.
+    #[serde(default)]
+    pub amount: Option<f64>,
+    /// Base units of `mint`, or lamports without one.
+    #[serde(default)]
+    pub units: Option<u64>,
+    /// Base58 SPL token mint.
+    #[serde(default)]
+    pub mint: Option<String>,
+}
+
+impl GenerateDepositDetailsRequest {
+    /// The mint, `None` for SOL, and the amount in base units.
+    pub fn asset(&self) -> Result<(Option<Pubkey>, u64), String> {
+        let mint = self
+            .mint
+            .as_deref()
+            .map(Pubkey::from_str)
+            .transpose()
+            .map_err(|e| format!("invalid mint: {}", e))?;
+        match (self.units, self.amount) {
+            (Some(units), None) => Ok((mint, units)),
+            (None, Some(amount)) if mint.is_none() => {
+                Ok((None, parse_units(&amount.to_string(), SOL_DECIMALS)?))
+            }
+            (None, Some(_)) => Err("token deposits take `units`, not `amount`".to_string()),
+            _ => Err("set exactly one of `amount` and `units`".to_string()),
+        }
+    }
+}
+
+async fn generate_deposit_details(
+    Json(req): Json<GenerateDepositDetailsRequest>,
+) -> Result<(StatusCode, Json<DepositDetails>), (StatusCode, String)> {
+    let (mint, amount) = req.asset().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let note = Note::random(mint, amount);
+    tracing::info!(mint = ?mint, amount, "generated deposit note");
+
+    Ok((StatusCode::OK, Json(DepositDetails::new(&note))))
+}
+
+#[derive(Deserialize)]
+pub struct DecodeNoteDetailsRequest {
+    pub note: Secret<String>,
+    pub program_pubkey: String,
+}
+
+#[derive(Serialize)]
+pub struct DecodeNoteDetailsResponse {
+    pub nullifier_str: Secret<String>,
+    pub secret_str: Secret<String>,
+    /// Whole tokens, using the pool's decimals for token notes.
+    pub amount: f64,
+    /// Base units.
+    pub units: u64,
+    /// Base58 SPL token mint, `null` for SOL.
+    pub mint: Option<String>,
+    pub state_pubkey: String,
+}
+
+async fn decode_note_details(
+    State(state): State<AppState>,
+    Json(req): Json<DecodeNoteDetailsRequest>,
+) -> impl IntoResponse {
+    let note = match Note::parse(req.note.expose()) {
+        Ok(note) => note,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(DecodeNoteDetailsResponse {
+                    nullifier_str: Default::default(),
+                    secret_str: Default::default(),
+                    amount: 0.0,
+                    units: 0,
+                    mint: None,
+                    state_pubkey: "".to_string(),
+                }),
+            );
+        }
+    };
+    let decimals = match state.pools.by_program(&req.program_pubkey) {
+        Some(pool) => pool.config.decimals,
+        None => SOL_DECIMALS,
+    };
+    (
+        StatusCode::OK,
+        Json(note_details(&note, req.program_pubkey, decimals)),
+    )
+}
+
+fn note_details(note: &Note, program_pubkey: String, decimals: u8) -> DecodeNoteDetailsResponse {
+    DecodeNoteDetailsResponse {
+        nullifier_str: decimal(&note.nullifier),
+        secret_str: decimal(&note.secret),
+        amount: note.amount as f64 / 10f64.powi(decimals as i32),
+        units: note.amount,
+        mint: note.mint.map(|m| m.to_string()),
+        state_pubkey: utils::get_pubkeys_utils(program_pubkey),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PoolNoteRequest {
+    pub note: Secret<String>,
+}
+
+/// Decodes a note for a pool, rejecting notes of another denomination.
+async fn decode_pool_note(
+    State(state): State<AppState>,
+    Path(pool_id): Path<String>,
+    Json(req): Json<PoolNoteRequest>,
+) -> Result<Json<DecodeNoteDetailsResponse>, (StatusCode, String)> {
+    let pool = state.pool(&pool_id)?;
+    let note = Note::parse(req.note.expose()).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    pool.config
+        .check_note(&note)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    Ok(Json(note_details(
+        &note,
+        pool.config.program_id.clone(),
+        pool.config.decimals,
+    )))
+}
+
+/// The configured pools.
+async fn list_pools(State(state): State<AppState>) -> Json<Vec<PoolConfig>> {
+    Json(state.pools.iter().map(|p| p.config.clone()).collect())
+}
+
+async fn get_pubkeys(program_pubkey: String) -> String {
+    utils::get_pubkeys_utils(program_pubkey)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PoolStatsResponse {
+    pub pool_id: String,
+    pub program_id: String,
+    pub deposit_count: usize,
+    pub withdrawal_count: usize,
+    /// Deposits not withdrawn yet: the notes a new withdrawal hides among.
+    pub anonymity_set: usize,
+    pub seconds_since_last_deposit: Option<u64>,
+    /// Root over the deposits proofs are currently built from.
+    pub root: Option<String>,
+}
+
+fn pool_index(pool: &Pool) -> Result<&DepositIndex, (StatusCode, String)> {
+    pool.index.as_deref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("pool {} is not indexed by this server", pool.config.id),
+        )
+    })
+}
+
+async fn pool_stats(
+    State(state): State<AppState>,
+    Path(pool_id): Path<String>,
+) -> Result<Json<PoolStatsResponse>, (StatusCode, String)> {
+    let pool = state.pool(&pool_id)?;
+    let stats = pool_index(pool)?.stats();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    Ok(Json(PoolStatsResponse {
+        pool_id: pool.config.id.clone(),
+        program_id: pool.config.program_id.clone(),
+        deposit_count: stats.deposits,
+        withdrawal_count: stats.withdrawals,
+        anonymity_set: stats.anonymity_set,
+        seconds_since_last_deposit: stats
+            .last_deposit_time
+            .map(|t| now.saturating_sub(t).max(0) as u64),
+        root: stats.root.map(|r| to_hex32(&r)),
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MerklePathQuery {
+    /// Hex commitment of the deposit.
+    pub commitment: String,
+}
+
+/// The Merkle path of a deposit, for clients that prove on their own machine.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MerklePathResponse {
+    pub pool_id: String,
+    pub commitment: String,
+    pub leaf_index: u64,
+    pub root: String,
+    pub path_elements: Vec<String>,
+    pub path_indices: Vec<u8>,
+}
+
+impl MerklePathResponse {
+    pub fn new(pool_id: &str, commitment: &[u8; 32], path: &MerklePath) -> Self {
+        MerklePathResponse {
+            pool_id: pool_id.to_string(),
+            commitment: to_hex32(commitment),
+            leaf_index: path.leaf_index,
+            root: to_hex32(&path.root),
+            path_elements: to_hex_vec(&path.path_elements),
+            path_indices: path.path_indices.to_vec(),
+        }
+    }
+
+    /// The path as [`WithdrawalInputs::for_note`] takes it.
+    pub fn path(&self) -> Result<MerklePath, String> {
+        let path_elements = self
+            .path_elements
+            .iter()
+            .map(|e| parse_hex32("path element", e))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(MerklePath {
+            leaf_index: self.leaf_index,
+            root: parse_hex32("root", &self.root)?,
+            path_elements: path_elements
+                .try_into()
+                .map_err(|_| format!("path needs {} elements", TREE_DEPTH))?,
+            path_indices: self
+                .path_indices
+                .clone()
+                .try_into()
+                .map_err(|_| format!("path needs {} indices", TREE_DEPTH))?,
+        })
+    }
+}
+
+fn parse_hex32(what: &str, s: &str) -> Result<[u8; 32], String> {
+    let bytes = Vec::from_hex(s.strip_prefix("0x").unwrap_or(s))
+        .map_err(|e| format!("invalid {} hex: {}", what, e))?;
+    <[u8; 32]>::try_from(bytes).map_err(|_| format!("{} is not 32 bytes", what))
+}
+
+/// Path, root and leaf index of a deposit, by its public commitment. Clients
+/// build the program input with [`WithdrawalInputs::for_note`] and prove
+/// locally, so the note never reaches this server.
+async fn merkle_path(
+    State(state): State<AppState>,
+    Path(pool_id): Path<String>,
+    Query(query): Query<MerklePathQuery>,
+) -> Result<Json<MerklePathResponse>, (StatusCode, String)> {
+    let pool = state.pool(&pool_id)?;
+    let commitment =
+        parse_hex32("commitment", &query.commitment).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let (leaves, leaf_index) =
+        find_deposit(&state, Some(pool), "", &pool.config.program_id, commitment)
+            .await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("commitment not found in pool {}", pool.config.id),
+                )
+            })?;
+    Ok(Json(MerklePathResponse::new(
+        &pool.config.id,
+        &commitment,
+        &MerklePath::new(&leaves, leaf_index),
+    )))
+}
+
+/// A [`SubtreeSnapshot`] in hex, see `GET /api/pools/{pool_id}/tree/subtree/{index}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SubtreeResponse {
+    pub pool_id: String,
+    pub height: usize,
+    pub index: u64,
+    pub leaves: Vec<String>,
+    pub siblings: Vec<String>,
+    pub root: String,
+    /// Number of leaves in the whole tree.
+    pub next_index: u64,
+}
+
+impl SubtreeResponse {
+    pub fn snapshot(&self) -> Result<SubtreeSnapshot, String> {
+        let hex = |what: &str, v: &[String]| {
+            v.iter()
+                .map(|e| parse_hex32(what, e))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(SubtreeSnapshot {
+            height: self.height,
+            index: self.index,
+            leaves: hex("leaf", &self.leaves)?,
+            siblings: hex("sibling", &self.siblings)?,
+            root: parse_hex32("root", &self.root)?,
+        })
+    }
+}
+
+/// The leaves of one subtree of [`SUBTREE_HEIGHT`] and the siblings above it.
+/// Clients compute their path with [`SubtreeSnapshot::path`], so the server
+/// only learns which group of leaves holds the deposit.
+async fn tree_subtree(
+    State(state): State<AppState>,
+    Path((pool_id, index)): Path<(String, u64)>,
+) -> Result<Json<SubtreeResponse>, (StatusCode, String)> {
+    let pool = state.pool(&pool_id)?;
+    let leaves = pool_index(pool)?.leaves();
+    let snapshot = SubtreeSnapshot::new(&leaves, SUBTREE_HEIGHT, index).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!(
+                "pool {} has {} leaves, none in subtree {}",
+                pool.config.id,
+                leaves.len(),
+                index
+            ),
+        )
+    })?;
+    Ok(Json(SubtreeResponse {
+        pool_id: pool.config.id.clone(),
+        height: snapshot.height,
+        index: snapshot.index,
+        leaves: to_hex_vec(&snapshot.leaves),
+        siblings: to_hex_vec(&snapshot.siblings),
+        root: to_hex32(&snapshot.root),
+        next_index: leaves.len() as u64,
+    }))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SnapshotQuery {
+    #[serde(default)]
+    pub format: SnapshotFormat,
+}
+
+/// The pool's finalized tree as a [`crate::snapshot::TreeSnapshot`], in JSON
+/// or binary.
+async fn tree_snapshot(
+    State(state): State<AppState>,
+    Path(pool_id): Path<String>,
+    Query(query): Query<SnapshotQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let pool = state.pool(&pool_id)?;
+    let snapshot = pool_index(pool)?.snapshot();
+    Ok(match query.format {
+        SnapshotFormat::Json => Json(snapshot.to_json()).into_response(),
+        SnapshotFormat::Binary => (
+            [(header::CONTENT_TYPE, "application/octet-stream")],
+            snapshot.to_bytes(),
+        )
+            .into_response(),
+    })
+}
+
+/// Prices a relayed withdrawal from the pool at the current priority fee.
+async fn fee_quote(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(pool_id): Path<String>,
+) -> Result<Json<FeeQuote>, (StatusCode, String)> {
+    let pool = state.pool(&pool_id)?;
+    let relayer = state.relayer.as_ref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            "this server does not relay withdrawals".to_string(),
+        )
+    })?;
+    state
+        .quotes
+        .admit(client.ip())
+        .map_err(|e| (StatusCode::TOO_MANY_REQUESTS, e))?;
+    let amount = pool.config.denomination.ok_or_else(|| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("pool {} has no denomination to quote", pool.config.id),
+        )
+    })?;
+
+    let source = state
+        .config
+        .pool_source(&pool.config)
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+    let compute_unit_price = current_priority_fee(&source, &pool.config.program_pubkey())
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
+
+    let quote = state
+        .quotes
+        .issue(
+            relayer,
+            QuoteRequest {
+                pool_id: &pool.config.id,
+                program_id: &pool.config.program_id,
+                amount,
+                sol: pool.config.mint.is_none(),
+                compute_unit_price,
+            },
+        )
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    Ok(Json(quote))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InputKeyResponse {
+    /// Hex X25519 public key.
+    pub public_key: String,
+    pub suite: String,
+    /// HPKE `info` string.
+    pub info: String,
+}
+
+/// The key to seal private inputs to, see [`crate::sealed`].
+async fn input_key(State(state): State<AppState>) -> Json<InputKeyResponse> {
+    Json(InputKeyResponse {
+        public_key: hex_encode(state.input_key.public_key()),
+        suite: HPKE_SUITE.to_string(),
+        info: String::from_utf8_lossy(HPKE_INFO).into_owned(),
+    })
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RelayersQuery {
+    pub pool: Option<String>,
+}
+
+fn directory(state: &AppState) -> Result<&RelayerDirectory, (StatusCode, String)> {
+    state.directory.as_deref().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            "this server is not a relayer directory".to_string(),
+        )
+    })
+}
+
+/// Registered relayers, optionally only those serving `pool`.
+async fn list_relayers(
+    State(state): State<AppState>,
+    Query(query): Query<RelayersQuery>,
+) -> Result<Json<Vec<RelayerEntry>>, (StatusCode, String)> {
+    let directory = directory(&state)?;
+    if let Some(pool) = &query.pool {
+        state.pool(pool)?;
+    }
+    Ok(Json(directory.list(query.pool.as_deref())))
+}
+
+/// Adds, updates or removes a relayer from its signed registration.
+async fn register_relayer(
+    State(state): State<AppState>,
+    Json(registration): Json<Registration>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let directory = directory(&state)?;
+    for pool in &registration.pools {
+        state
+            .pool(pool)
+            .map_err(|(_, e)| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    }
+    directory
+        .register(registration)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn ws_compute_proof_withdrawal(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, (StatusCode, String)> {
+    let key = idempotency_key(&headers)?.map(str::to_string);
+    Ok(ws
+        .on_upgrade(move |socket| handle_ws(socket, state, None, key))
+        .into_response())
+}
+
+/// Like `/ws/compute_withdrawal`, for a configured pool. The request must
+/// carry the note, whose denomination is checked against the pool.
+async fn ws_compute_pool_withdrawal(
+    State(state): State<AppState>,
+    Path(pool_id): Path<String>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, (StatusCode, String)> {
+    let pool = state.pool(&pool_id)?.clone();
+    let key = idempotency_key(&headers)?.map(str::to_string);
+    Ok(ws
+        .on_upgrade(move |socket| handle_ws(socket, state, Some(pool), key))
+        .into_response())
+}
+
+/// Tree leaves and the leaf index of `commitment`. Served from the live index
+/// when one runs for the program, otherwise by scanning the chain.
+async fn find_deposit(
+    state: &AppState,
+    pool: Option<&Pool>,
+    rpc_url: &str,
+    program_id: &str,
+    commitment: [u8; 32],
+) -> Result<Option<(Vec<[u8; 32]>, usize)>, String> {
+    let pool = pool.or_else(|| state.pools.by_program(program_id));
+    if let Some(index) = pool.and_then(|p| p.index.as_ref()) {
+        if let Some(found) = index.locate(&commitment)? {
+            return Ok(Some(found));
+        }
+    }
+    let source = match pool {
+        Some(pool) if rpc_url.trim().is_empty() => state.config.pool_source(&pool.config)?,
+        _ => state.config.deposit_source(rpc_url)?,
+    };
+    let (all_commits, _entries, _indices, leaf_index, found) = utils::fetch_deposits(
+        &source,
+        &state.events,
+        commitment,
+        program_id,
+        state.config.proof_commitment,
+    )
+    .await
+    .map_err(|e| format!("fetch_deposits failed: {}", e))?;
+    Ok(found.then_some((all_commits, leaf_index)))
+}
+#[derive(Deserialize, Debug)]
+pub struct WithdrawalComputeRequest {
+    /// Decimal; taken from `note` when that is set.
+    #[serde(default)]
+    pub nullifier: Secret<String>,
+    #[serde(default)]
+    pub secret: Secret<String>,
+    /// The deposit note. Pool withdrawals need it or `sealed`.
+    #[serde(default)]
+    pub note: Option<Secret<String>>,
+    /// Nullifier and secret sealed to the input key of the prover at
+    /// `server_url`, instead of `note` or the fields above. The server then
+    /// never sees them and needs the public `commitment` and `nullifier_hash`.
+    #[serde(default)]
+    pub sealed: Option<SealedInputs>,
+    /// Hex commitment of the sealed note.
+    #[serde(default)]
+    pub commitment: Option<String>,
+    /// Hex nullifier hash of the sealed note.
+    #[serde(default)]
+    pub nullifier_hash: Option<String>,
+    #[serde(default)]
+    pub rpc_url: String,
+    /// Set from the pool for pool withdrawals.
+    #[serde(default)]
+    pub program_pubkey: String,
+    /// Wallets; for token notes the proof names their associated token accounts.
+    pub new_withdrawal_recipient_address: String,
+    /// The server's relayer when `relay` is set.
+    #[serde(default)]
+    pub new_relayer_address: String,
+    /// Base58 SPL token mint, taken from `note` when that is set.
+    #[serde(default)]
+    pub mint: Option<String>,
+    /// Deposit amount in base units, taken from `note` when that is set.
+    #[serde(default)]
+    pub amount: u64,
+    pub server_url: String,
+    #[serde(default)]
+    pub format: ProofFormat,
+    /// Sent on as the `Idempotency-Key` of the proof request. Overrides the
+    /// header of the WebSocket upgrade, which browsers cannot set.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// Have the server's relayer submit the withdrawal, instead of returning
+    /// the proof for the client to send.
+    #[serde(default)]
+    pub relay: bool,
+    /// A fee quote signed by the relayer. Its relayer, fee and refund become
+    /// the public inputs. Required with `relay`.
+    #[serde(default)]
+    pub quote: Option<FeeQuote>,
+    /// Names a quote issued by this server instead of sending it.
+    #[serde(default)]
+    pub quote_id: Option<String>,
+}
+
+/// Takes the secrets from `req.note` if given and, for a pool withdrawal,
+/// checks the note (or, for sealed inputs, takes the asset) against the pool
+/// and targets the pool's program. Returns
+/// the verified fee quote, whose relayer the proof then names. Relayed
+/// withdrawals need a quote from the server's relayer and a Solana proof.
+fn prepare_withdrawal(
+    req: &mut WithdrawalComputeRequest,
+    pool: Option<&Pool>,
+    state: &AppState,
+) -> Result<Option<FeeQuote>, String> {
+    if req.sealed.is_some() && req.note.is_some() {
+        return Err("send either `sealed` or `note`".to_string());
+    }
+    if let Some(pool) = pool {
+        if req.sealed.is_some() {
+            // The pool fixes the asset that the note of a sealed withdrawal holds.
+            req.mint = pool.config.mint.clone();
+            req.amount = pool.config.denomination.ok_or_else(|| {
+                format!(
+                    "sealed withdrawals from pool {} need a denomination",
+                    pool.config.id
+                )
+            })?;
+        } else if req.note.is_none() {
+            return Err(format!(
+                "withdrawals from pool {} need the note",
+                pool.config.id
+            ));
+        }
+        req.program_pubkey = pool.config.program_id.clone();
+    }
+    if let Some(note) = &req.note {
+        let note = Note::parse(note.expose())?;
+        if let Some(pool) = pool {
+            pool.config.check_note(&note)?;
+        }
+        req.nullifier = decimal(&note.nullifier);
+        req.secret = decimal(&note.secret);
+        req.mint = note.mint.map(|m| m.to_string());
+        req.amount = note.amount;
+    }
+    let quote = match (&req.quote, &req.quote_id) {
+        (Some(quote), _) => Some(quote.clone()),
+        (None, Some(id)) => Some(state.quotes.get(id)?),
+        (None, None) => None,
+    };
+
+    if req.relay {
+        let relayer = state
+            .relayer
+            .as_ref()
+            .ok_or("this server does not relay withdrawals")?;
+        let ours = relayer.pubkey().to_string();
+        if !req.new_relayer_address.is_empty() && req.new_relayer_address != ours {
+            return Err(format!(
+                "relayed withdrawals pay this server's relayer {}, not {}",
+                ours, req.new_relayer_address
+            ));
+        }
+        match &quote {
+            None => {
+                return Err(
+                    "relayed withdrawals need a fee quote (`quote` or `quote_id`)".to_string(),
+                )
+            }
+            Some(quote) if quote.relayer != ours => {
+                return Err(format!(
+                    "fee quote is from relayer {}, not this server's {}",
+                    quote.relayer, ours
+                ))
+            }
+            Some(_) => {}
+        }
+        req.format = ProofFormat::Solana;
+    }
+
+    if let Some(quote) = &quote {
+        quote.verify()?;
+        if quote.program_id != req.program_pubkey || quote.amount != req.amount {
+            return Err(format!(
+                "fee quote {} is for pool {}, not this withdrawal",
+                quote.id, quote.pool_id
+            ));
+        }
+        if !req.new_relayer_address.is_empty() && req.new_relayer_address != quote.relayer {
+            return Err(format!(
+                "fee quote pays relayer {}, not {}",
+                quote.relayer, req.new_relayer_address
+            ));
+        }
+        req.new_relayer_address = quote.relayer.clone();
+    }
+    Ok(quote)
+}
+
+/// What the withdrawal flow needs of the note: the commitment to look up, the
+/// nullifier hash, and the hex private inputs unless they are sealed.
+struct NoteHashes {
+    commitment: [u8; 32],
+    nullifier_hash: [u8; 32],
+    nullifier: Secret<String>,
+    secret: Secret<String>,
+}
+
+/// Hashes the note's decimal nullifier and secret or, for sealed inputs,
+/// takes the client's `commitment` and `nullifier_hash`. The prover checks
+/// those against the sealed values.
+fn note_hashes(req: &WithdrawalComputeRequest) -> Result<NoteHashes, String> {
+    if req.sealed.is_some() {
+        let hash = |what: &str, value: &Option<String>| {
+            let value = value
+                .as_deref()
+                .ok_or_else(|| format!("sealed withdrawals need the `{}`", what))?;
+            let bytes = Vec::from_hex(value.strip_prefix("0x").unwrap_or(value))
+                .map_err(|e| format!("invalid {} hex: {}", what, e))?;
+            <[u8; 32]>::try_from(bytes).map_err(|_| format!("{} is not 32 bytes", what))
+        };
+        return Ok(NoteHashes {
+            commitment: hash("commitment", &req.commitment)?,
+            nullifier_hash: hash("nullifier_hash", &req.nullifier_hash)?,
+            nullifier: Default::default(),
+            secret: Default::default(),
+        });
+    }
+    let number = |what: &str, value: &Secret<String>| {
+        BigUint::from_str(value.expose())
+            .map_err(|_| format!("invalid {}: not a decimal number", what))
+    };
+    let nullifier = number("nullifier", &req.nullifier)?;
+    let secret = number("secret", &req.secret)?;
+    let nullifier_bytes = Zeroizing::new(nullifier.to_bytes_le());
+    let secret_bytes = Zeroizing::new(secret.to_bytes_le());
+    let commitment = hashv(
+        Parameters::Bn254X5,
+        Endianness::LittleEndian,
+        &[&nullifier_bytes[..], &secret_bytes[..]],
+    )
+    .map_err(|e| format!("hashing the note: {}", e))?
+    .to_bytes();
+    let nullifier_hash = hashv(
+        Parameters::Bn254X5,
+        Endianness::LittleEndian,
+        &[&nullifier_bytes[..]],
+    )
+    .map_err(|e| format!("hashing the note: {}", e))?
+    .to_bytes();
+    Ok(NoteHashes {
+        commitment,
+        nullifier_hash,
+        nullifier: Secret::new(to_hex32(&biguint_to_32_le_bytes(&nullifier))),
+        secret: Secret::new(to_hex32(&biguint_to_32_le_bytes(&secret))),
+    })
+}
+
+/// Recipient, relayer and mint as the proof commits them.
+struct WithdrawalAccounts {
+    recipient: [u8; 32],
+    relayer: [u8; 32],
+    mint: [u8; 32],
+}
+
+/// Token withdrawals pay out to the associated token accounts of the
+/// recipient and relayer wallets, so those are the public inputs.
+fn withdrawal_accounts(req: &WithdrawalComputeRequest) -> Result<WithdrawalAccounts, String> {
+    let wallet = |what: &str, key: &str| {
+        Pubkey::from_str(key).map_err(|e| format!("invalid {} address {}: {}", what, key, e))
+    };
+    let recipient = wallet("recipient", &req.new_withdrawal_recipient_address)?;
+    let relayer = wallet("relayer", &req.new_relayer_address)?;
+    let Some(mint) = &req.mint else {
+        return Ok(WithdrawalAccounts {
+            recipient: recipient.to_bytes(),
+            relayer: relayer.to_bytes(),
+            mint: NATIVE_MINT,
+        });
+    };
+    let mint = Pubkey::from_str(mint).map_err(|e| format!("invalid mint {}: {}", mint, e))?;
+    Ok(WithdrawalAccounts {
+        recipient: get_associated_token_address(&recipient, &mint).to_bytes(),
+        relayer: get_associated_token_address(&relayer, &mint).to_bytes(),
+        mint: mint.to_bytes(),
+    })
+}
+
+async fn handle_ws(
+    mut socket: WebSocket,
+    state: AppState,
+    pool: Option<Pool>,
+    idempotency_key: Option<String>,
+) {
+    let msg = match socket.recv().await {
+        Some(Ok(Message::Text(txt))) => txt,
+        _ => return,
+    };
+    let mut req: WithdrawalComputeRequest = match serde_json::from_str(&msg) {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = socket
+                .send(Message::Text(
+                    json!({
+                        "error": format!("Invalid request JSON: {}", e)
+                    })
+                    .to_string(),
+                ))
+                .await;
+            return;
+        }
+    };
+    if req.server_url.is_empty() {
+        req.server_url = "http://localhost:3001".to_string();
+    }
+    if req.idempotency_key.is_none() {
+        req.idempotency_key = idempotency_key;
+    }
+    let prepared = prepare_withdrawal(&mut req, pool.as_ref(), &state)
+        .and_then(|quote| withdrawal_accounts(&req).map(|accounts| (quote, accounts)));
+    let (
+        quote,
+        WithdrawalAccounts {
+            recipient,
+            relayer,
+            mint,
+        },
+    ) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            let _ = socket
+                .send(Message::Text(json!({ "error": e }).to_string()))
+                .await;
+            return;
+        }
+    };
+    let NoteHashes {
+        commitment,
+        nullifier_hash,
+        nullifier,
+        secret,
+    } = match note_hashes(&req) {
+        Ok(hashes) => hashes,
+        Err(e) => {
+            let _ = socket
+                .send(Message::Text(json!({ "error": e }).to_string()))
+                .await;
+            return;
+        }
+    };
+
+    let (all_commits, leaf_index) = match find_deposit(
+        &state,
+        pool.as_ref(),
+        &req.rpc_url,
+        &req.program_pubkey,
+        commitment,
+    )
+    .await
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            let _ = socket
+                .send(Message::Text(
+                    json!({
+                        "error": "Commitment not found in on-chain history"
+                    })
+                    .to_string(),
+                ))
+                .await;
+            return;
+        }
+        Err(e) => {
+            let _ = socket
+                .send(Message::Text(json!({ "error": e }).to_string()))
+                .await;
+            return;
+        }
+    };
+
+    let (siblings, path_indices, root) =
+        utils::compute_merkle_proof::<20>(&all_commits, leaf_index);
+
+    let siblings_array: [[u8; 32]; 20] = siblings;
+    let path_indices_array: [u8; 20] = path_indices;
+    utils::merkle_check::<20>(root, commitment, &siblings_array, &path_indices_array);
+    tracing::info!(
+        pool = pool.as_ref().map(|p| p.config.id.as_str()),
+        program = %req.program_pubkey,
+        leaf_index,
+        sealed = req.sealed.is_some(),
+        relay = req.relay,
+        "proving withdrawal"
+    );
+    let root: [u8; 32] = root;
+    let nullifier_hash: [u8; 32] = nullifier_hash;
+
+    let (fee, refund) = quote.as_ref().map_or((0, 0), |q| (q.fee, q.refund));
+    let path_elems: Vec<[u8; 32]> = siblings.to_vec();
+    let path_inds: Vec<u8> = path_indices.to_vec();
+
+    let prove_req = ProveRequest {
+        root: to_hex32(&root),
+        nullifier_hash: to_hex32(&nullifier_hash),
+        recipient: to_hex32(&recipient),
+        relayer: to_hex32(&relayer),
+        fee,
+        refund,
+        mint: Some(to_hex32(&mint)),
+        amount: req.amount,
+        nullifier,
+        secret,
+        sealed: req.sealed.clone(),
+        path_elements: to_hex_vec(&path_elems),
+        path_indices: path_inds,
+        format: req.format,
+        dry_run: false,
+    };
+
+    let client = reqwest::Client::new();
+    let mut prove_http = client
+        .post(format!("{}/api/prove-mix", req.server_url))
+        .json(&prove_req);
+    if let Some(key) = &req.idempotency_key {
+        prove_http = prove_http.header(IDEMPOTENCY_KEY_HEADER, key);
+    }
+    let resp = match prove_http.send().await {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = socket
+                .send(Message::Text(
+                    json!({
+                        "error": format!("Prover HTTP request failed: {}", e)
+                    })
+                    .to_string(),
+                ))
+                .await;
+            return;
+        }
+    };
+    let job_id = resp
+        .headers()
+        .get(PROOF_JOB_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let resp_text = match resp.text().await {
+        Ok(t) => t,
+        Err(e) => {
+            let _ = socket
+                .send(Message::Text(
+                    json!({
+                        "error": format!("Reading prover response failed: {}", e)
+                    })
+                    .to_string(),
+                ))
+                .await;
+            return;
+        }
+    };
+
+    let prove_resp: ProveResponseCustom = match serde_json::from_str(&resp_text) {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = socket
+                .send(Message::Text(
+                    json!({
+                        "error": format!("Invalid prover response JSON: {}", e)
+                    })
+                    .to_string(),
+                ))
+                .await;
+            return;
+        }
+    };
+
+    let proof_bytes = match hex::decode(&prove_resp.proof) {
+        Ok(b) => b,
+        Err(e) => {
+            let _ = socket
+                .send(Message::Text(
+                    json!({
+                        "error": format!("Invalid proof hex: {}", e)
+                    })
+                    .to_string(),
+                ))
+                .await;
+            return;
+        }
+    };
+    let public_inputs = prove_resp.public_inputs.buffer.data;
+
+    let mut result = json!({
+        "proof_bytes": &proof_bytes,
+        "public_inputs": public_inputs,
+    });
+    if let Some(solana) = &prove_resp.solana {
+        result["solana"] = json!(solana);
+    }
+    if let Some(job_id) = job_id {
+        result["job_id"] = json!(job_id);
+    }
+    let _ = socket.send(Message::Text(result.to_string())).await;
+
+    if let Some(quote) = quote.filter(|_| req.relay) {
+        // The proof must pay the signed quote's fee and refund to the quote's
+        // relayer, whose fee account `prepare_withdrawal` made `relayer`.
+        let expected = PublicValues {
+            root,
+            nullifier_hash,
+            recipient,
+            relayer,
+            fee: quote.fee,
+            refund: quote.refund,
+            mint,
+            amount: req.amount,
+        };
+        let relayed = match prove_resp.solana {
+            Some(proof) => {
+                relay(
+                    &state,
+                    pool.as_ref(),
+                    &req,
+                    &quote,
+                    &expected,
+                    &proof,
+                    &mut socket,
+                )
+                .await
+            }
+            None => Err("prover returned no Solana proof to relay".to_string()),
+        };
+        if let Err(e) = relayed {
+            let _ = socket
+                .send(Message::Text(json!({ "error": e }).to_string()))
+                .await;
+        }
+    }
+
+    let _ = socket.close().await;
+}
+
+/// Submits `proof` with the server's relayer at the priority fee of `quote`
+/// and reports the signature over `socket`, once when sent and once when
+/// confirmed. The proof comes from the client's prover, so it is first
+/// checked to commit to `expected` and to verify against the pool's vkey.
+async fn relay(
+    state: &AppState,
+    pool: Option<&Pool>,
+    req: &WithdrawalComputeRequest,
+    quote: &FeeQuote,
+    expected: &PublicValues,
+    proof: &SolanaProof,
+    socket: &mut WebSocket,
+) -> Result<(), String> {
+    let relayer = state
+        .relayer
+        .as_ref()
+        .ok_or("this server does not relay withdrawals")?;
+    let vkey_hash = pool
+        .and_then(|p| p.config.vkey.as_deref())
+        .or(state.vkey_hash.as_deref())
+        .ok_or("no program vkey to check relayed proofs against")?;
+    check_proof(proof, expected, vkey_hash)?;
+    let program_id = Pubkey::from_str(&req.program_pubkey)
+        .map_err(|e| format!("invalid program id {}: {}", req.program_pubkey, e))?;
+    let source = match pool {
+        Some(pool) if req.rpc_url.trim().is_empty() => state.config.pool_source(&pool.config)?,
+        _ => state.config.deposit_source(&req.rpc_url)?,
+    };
+
+    let signature = relayer
+        .submit(&source, &program_id, proof, quote.compute_unit_price)
+        .await?;
+    let _ = socket
+        .send(Message::Text(
+            json!({ "status": "submitted", "signature": signature.to_string() }).to_string(),
+        ))
+        .await;
+    let slot = relayer.confirm(&source, &signature).await?;
+    let _ = socket
+        .send(Message::Text(
+            json!({
+                "status": "confirmed",
+                "signature": signature.to_string(),
+                "slot": slot,
+            })
+            .to_string(),
+        ))
+        .await;
+    Ok(())
+}
+
+/// Runtime settings shared by the handlers.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub prover: ProverBackend,
+    pub skip_proof_verification: bool,
+    /// Used when a request does not name its own `rpc_url`.
+    pub rpc_urls: Vec<String>,
+    /// PubSub endpoint for live indexing. Derived from the first RPC URL when unset.
+    pub ws_url: Option<String>,
+    /// Program whose deposits are indexed in the background, if any.
+    pub program_id: Option<String>,
+    /// `0` disables rate limiting.
+    pub rpc_requests_per_second: u32,
+    /// Minimum commitment level of the deposits a proof may use.
+    pub proof_commitment: CommitmentLevel,
+    /// Where the mixer IDL comes from: a JSON file, or `onchain` for the IDL
+    /// account of each pool's program. The bundled IDL is used when unset.
+    pub idl: Option<String>,
+    /// Pool registry file, see [`crate::pools`].
+    pub pools_file: Option<String>,
+    /// How long finished proofs are served again for identical inputs.
+    pub proof_cache_ttl: Duration,
+    /// Maximum number of cached proofs. `0` disables the cache.
+    pub proof_cache_size: usize,
+    /// How long an `Idempotency-Key` keeps pointing at its proof job.
+    pub idempotency_ttl: Duration,
+    /// Maximum number of remembered `Idempotency-Key`s.
+    pub idempotency_keys: usize,
+    /// Keypair file of the relayer that submits withdrawals. Relaying is
+    /// disabled when unset.
+    pub relayer_keypair: Option<String>,
+    /// How the relayer prices withdrawals.
+    pub relayer_fees: FeeSchedule,
+    /// How long a fee quote can be accepted.
+    pub quote_ttl: Duration,
+    /// Maximum number of live fee quotes kept for `quote_id`.
+    pub quote_book_size: usize,
+    /// Fee quotes each client may ask for per minute. `0` disables the limit.
+    pub quotes_per_minute: u32,
+    /// File of the relayer directory. The server is no directory when unset.
+    pub relayer_directory: Option<String>,
+    /// How often the directory checks its relayers.
+    pub relayer_check_interval: Duration,
+    /// File with the hex X25519 secret key that private inputs are sealed
+    /// to. A fresh key is generated at startup when unset.
+    pub input_key_file: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            prover: ProverBackend::Network,
+            skip_proof_verification: false,
+            rpc_urls: Vec::new(),
+            ws_url: None,
+            program_id: None,
+            rpc_requests_per_second: 0,
+            proof_commitment: CommitmentLevel::Finalized,
+            idl: None,
+            pools_file: None,
+            proof_cache_ttl: Duration::from_secs(600),
+            proof_cache_size: 128,
+            idempotency_ttl: Duration::from_secs(3600),
+            idempotency_keys: 1024,
+            relayer_keypair: None,
+            relayer_fees: FeeSchedule::default(),
+            quote_ttl: Duration::from_secs(120),
+            quote_book_size: 1024,
+            quotes_per_minute: 30,
+            relayer_directory: None,
+            relayer_check_interval: Duration::from_secs(60),
+            input_key_file: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Reads `SP1_PROVER` (defaults to `network`), `SKIP_PROOF_VERIFICATION`,
+    /// `SOLANA_RPC_URLS` (comma-separated), `SOLANA_RPC_RPS`, `SOLANA_WS_URL`,
+    /// `MIXER_PROGRAM_ID`, `SOLANA_PROOF_COMMITMENT` (defaults to `finalized`),
+    /// `MIXER_IDL`, `MIXER_POOLS`, `PROOF_CACHE_TTL_SECS` (defaults to 600),
+    /// `PROOF_CACHE_SIZE` (defaults to 128), `IDEMPOTENCY_TTL_SECS` (defaults
+    /// to 3600), `IDEMPOTENCY_MAX_KEYS` (defaults to 1024), `RELAYER_KEYPAIR`,
+    /// `RELAYER_FEE_BPS`, `RELAYER_PROVER_COST` (lamports, both default to
+    /// 0), `QUOTE_TTL_SECS` (defaults to 120), `QUOTE_BOOK_SIZE` (defaults to
+    /// 1024), `QUOTES_PER_MINUTE` (defaults to 30, `0` for no limit),
+    /// `RELAYER_DIRECTORY`, `RELAYER_CHECK_INTERVAL_SECS` (defaults to 60) and
+    /// `INPUT_KEY_FILE`.
+    pub fn from_env() -> Result<Self, String> {
+        let prover = match std::env::var("SP1_PROVER") {
+            Ok(s) if !s.trim().is_empty() => s.trim().parse()?,
+            _ => ProverBackend::Network,
+        };
+        let rpc_urls = std::env::var("SOLANA_RPC_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty())
+            .collect();
+        let rpc_requests_per_second = match std::env::var("SOLANA_RPC_RPS") {
+            Ok(s) if !s.trim().is_empty() => s
+                .trim()
+                .parse()
+                .map_err(|e| format!("invalid SOLANA_RPC_RPS: {}", e))?,
+            _ => 0,
+        };
+        let non_empty = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let proof_commitment = match non_empty("SOLANA_PROOF_COMMITMENT") {
+            Some(s) => CommitmentLevel::from_str(&s.to_ascii_lowercase())
+                .map_err(|_| format!("invalid SOLANA_PROOF_COMMITMENT “{}”", s))?,
+            None => CommitmentLevel::Finalized,
+        };
+        let defaults = ServerConfig::default();
+        let number = |name: &str, default: u64| match non_empty(name) {
+            Some(s) => s.parse().map_err(|e| format!("invalid {}: {}", name, e)),
+            None => Ok(default),
+        };
+        let proof_cache_ttl = Duration::from_secs(number(
+            "PROOF_CACHE_TTL_SECS",
+            defaults.proof_cache_ttl.as_secs(),
+        )?);
+        let proof_cache_size =
+            number("PROOF_CACHE_SIZE", defaults.proof_cache_size as u64)? as usize;
+        let idempotency_ttl = Duration::from_secs(number(
+            "IDEMPOTENCY_TTL_SECS",
+            defaults.idempotency_ttl.as_secs(),
+        )?);
+        let idempotency_keys =
+            number("IDEMPOTENCY_MAX_KEYS", defaults.idempotency_keys as u64)? as usize;
+        if idempotency_keys == 0 {
+            return Err("invalid IDEMPOTENCY_MAX_KEYS: must be positive".to_string());
+        }
+        let relayer_fees = FeeSchedule {
+            rate_bps: number("RELAYER_FEE_BPS", 0)?
+                .try_into()
+                .map_err(|_| "invalid RELAYER_FEE_BPS: too large".to_string())?,
+            prover_cost: number("RELAYER_PROVER_COST", 0)?,
+        };
+        let quote_ttl =
+            Duration::from_secs(number("QUOTE_TTL_SECS", defaults.quote_ttl.as_secs())?);
+        let quote_book_size = number("QUOTE_BOOK_SIZE", defaults.quote_book_size as u64)? as usize;
+        if quote_book_size == 0 {
+            return Err("invalid QUOTE_BOOK_SIZE: must be positive".to_string());
+        }
+        let quotes_per_minute = number("QUOTES_PER_MINUTE", defaults.quotes_per_minute as u64)?
+            .try_into()
+            .map_err(|_| "invalid QUOTES_PER_MINUTE: too large".to_string())?;
+        let relayer_check_interval = Duration::from_secs(number(
+            "RELAYER_CHECK_INTERVAL_SECS",
+            defaults.relayer_check_interval.as_secs(),
+        )?);
+        if relayer_check_interval.is_zero() {
+            return Err("invalid RELAYER_CHECK_INTERVAL_SECS: must be positive".to_string());
+        }
+        Ok(ServerConfig {
+            prover,
+            skip_proof_verification: verify::skip_proof_verification(),
+            rpc_urls,
+            ws_url: non_empty("SOLANA_WS_URL"),
+            program_id: non_empty("MIXER_PROGRAM_ID"),
+            rpc_requests_per_second,
+            proof_commitment,
+            idl: non_empty("MIXER_IDL"),
+            pools_file: non_empty("MIXER_POOLS"),
+            proof_cache_ttl,
+            proof_cache_size,
+            idempotency_ttl,
+            idempotency_keys,
+            relayer_keypair: non_empty("RELAYER_KEYPAIR"),
+            relayer_fees,
+            quote_ttl,
+            quote_book_size,
+            quotes_per_minute,
+            relayer_directory: non_empty("RELAYER_DIRECTORY"),
+            relayer_check_interval,
+            input_key_file: non_empty("INPUT_KEY_FILE"),
+        })
+    }
+
+    /// The relayer configured by `relayer_keypair`, if any.
+    pub fn relayer(&self) -> Result<Option<Relayer>, String> {
+        self.relayer_keypair
+            .as_deref()
+            .map(Relayer::from_file)
+            .transpose()
+    }
+
+    /// The key in `input_key_file`, or a fresh one.
+    pub fn input_key(&self) -> Result<InputKey, String> {
+        match &self.input_key_file {
+            Some(path) => InputKey::from_file(path),
+            None => Ok(InputKey::generate()),
+        }
+    }
+
+    /// The relayer directory stored in `relayer_directory`, if any.
+    pub fn relayer_directory(&self) -> Result<Option<RelayerDirectory>, String> {
+        self.relayer_directory
+            .as_deref()
+            .map(RelayerDirectory::open)
+            .transpose()
+    }
+
+    /// Pools from `pools_file`, or the single `program_id` pool.
+    pub fn pools(&self) -> Result<PoolRegistry, String> {
+        match (&self.pools_file, &self.program_id) {
+            (Some(path), _) => PoolRegistry::from_file(path),
+            (None, Some(program_id)) => {
+                PoolRegistry::new(vec![PoolConfig::for_program(program_id)])
+            }
+            (None, None) => Ok(PoolRegistry::default()),
+        }
+    }
+
+    /// Deposit source for `pool`: its cluster, or the configured endpoints.
+    pub fn pool_source(&self, pool: &PoolConfig) -> Result<RpcDepositSource, String> {
+        match pool.rpc_url() {
+            Some(url) => {
+                Ok(RpcDepositSource::new(vec![url])?.with_rate_limit(self.rpc_requests_per_second))
+            }
+            None => self.deposit_source(""),
+        }
+    }
+
+    /// PubSub URL for `pool`: its cluster's, or [`Self::pubsub_url`].
+    pub fn pool_pubsub_url(&self, pool: &PoolConfig) -> Option<String> {
+        pool.ws_url().or_else(|| self.pubsub_url())
+    }
+
+    /// Loads the event decoder selected by `idl` for `pool`, or for requests
+    /// outside any pool (which never use the on-chain IDL).
+    pub async fn load_events(
+        &self,
+        pool: Option<&PoolConfig>,
+    ) -> Result<Arc<EventDecoder>, String> {
+        match (self.idl.as_deref(), pool) {
+            (None, _) | (Some("onchain"), None) => Ok(EventDecoder::bundled()),
+            (Some("onchain"), Some(pool)) => {
+                let source = self.pool_source(pool)?;
+                EventDecoder::fetch(&source, &pool.program_pubkey())
+                    .await
+                    .map(Arc::new)
+                    .map_err(|e| format!("loading IDL of pool {}: {}", pool.id, e))
+            }
+            (Some(path), _) => EventDecoder::from_file(path).map(Arc::new),
+        }
+    }
+
+    /// The PubSub URL, e.g. `wss://api.devnet.solana.com` for
+    /// `https://api.devnet.solana.com`.
+    pub fn pubsub_url(&self) -> Option<String> {
+        self.ws_url.clone().or_else(|| {
+            let url = self.rpc_urls.first()?;
+            Some(if let Some(rest) = url.strip_prefix("https://") {
+                format!("wss://{}", rest)
+            } else {
+                format!("ws://{}", url.strip_prefix("http://").unwrap_or(url))
+            })
+        })
+    }
+
+    /// Deposit source for a request: its own comma-separated `rpc_url`, or the
+    /// configured endpoints.
+    pub fn deposit_source(&self, rpc_url: &str) -> Result<RpcDepositSource, String> {
+        let source = if rpc_url.trim().is_empty() {
+            RpcDepositSource::new(self.rpc_urls.clone())
+        } else {
+            RpcDepositSource::from_url_list(rpc_url)
+        }?;
+        Ok(source.with_rate_limit(self.rpc_requests_per_second))
+    }
+
+    /// Mock proofs carry no Groth16 points, so they are never verified.
+    fn verify_proofs(&self) -> bool {
+        !self.skip_proof_verification && self.prover != ProverBackend::Mock
+    }
+}
+
+/// Shared handler state: the configuration, the event decoder, the pools
+/// with their live indexes, the proof cache, the running proof jobs and the
+/// relayer with its fee quotes, if the server relays withdrawals, and the
+/// relayer directory, if it is one, the key private inputs are sealed to and
+/// the program vkey hash.
+#[derive(Clone)]
+pub struct AppState {
+    pub config: ServerConfig,
+    pub events: Arc<EventDecoder>,
+    pub pools: Arc<PoolRegistry>,
+    pub proofs: Arc<ProofCache>,
+    pub jobs: Arc<ProofJobs>,
+    pub relayer: Option<Arc<Relayer>>,
+    pub quotes: Arc<QuoteBook>,
+    pub directory: Option<Arc<RelayerDirectory>>,
+    pub input_key: Arc<InputKey>,
+    /// Vkey hash of the program this server proves, which relayed proofs of
+    /// pools without their own `vkey` must verify against.
+    pub vkey_hash: Option<String>,
+}
+
+impl AppState {
+    /// State with a proof cache and idempotency window sized by `config`, and
+    /// a fresh input key.
+    pub fn new(config: ServerConfig, events: Arc<EventDecoder>, pools: Arc<PoolRegistry>) -> Self {
+        let proofs = Arc::new(ProofCache::new(
+            config.proof_cache_ttl,
+            config.proof_cache_size,
+        ));
+        let jobs = Arc::new(ProofJobs::new(
+            config.idempotency_ttl,
+            config.idempotency_keys,
+        ));
+        let quotes = Arc::new(
+            QuoteBook::new(config.relayer_fees, config.quote_ttl)
+                .with_capacity(config.quote_book_size)
+                .with_rate_limit(config.quotes_per_minute),
+        );
+        AppState {
+            config,
+            events,
+            pools,
+            proofs,
+            jobs,
+            relayer: None,
+            quotes,
+            directory: None,
+            input_key: Arc::new(InputKey::generate()),
+            vkey_hash: None,
+        }
+    }
+
+    pub fn with_relayer(mut self, relayer: Relayer) -> Self {
+        self.relayer = Some(Arc::new(relayer));
+        self
+    }
+
+    pub fn with_input_key(mut self, key: InputKey) -> Self {
+        self.input_key = Arc::new(key);
+        self
+    }
+
+    pub fn with_vkey_hash(mut self, vkey_hash: &str) -> Self {
+        self.vkey_hash = Some(vkey_hash.to_string());
+        self
+    }
+
+    pub fn with_directory(mut self, directory: Arc<RelayerDirectory>) -> Self {
+        self.directory = Some(directory);
+        self
+    }
+
+    fn pool(&self, pool_id: &str) -> Result<&Pool, (StatusCode, String)> {
+        self.pools
+            .get(pool_id)
+            .ok_or_else(|| (StatusCode::NOT_FOUND, format!("unknown pool {}", pool_id)))
+    }
+}
+
+impl FromRef<AppState> for ServerConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+/// Builds the proof API with all routes, CORS and panic handling.
+pub fn router(config: ServerConfig) -> Router {
+    app(AppState::new(
+        config,
+        EventDecoder::bundled(),
+        Arc::default(),
+    ))
+}
+
+/// Like [`router`], serving `state.pools` and withdrawals from their indexes.
+pub fn app(state: AppState) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin(tower_http::cors::Any)
+        .allow_methods(tower_http::cors::Any)
+        .allow_headers(tower_http::cors::Any);
+
+    let panic_layer = CatchPanicLayer::new();
+
+    Router::new()
+        .route("/api/prove-mix", post(prove_mix))
+        .route("/api/prove-mix/dry-run", post(prove_mix_dry_run))
+        .route("/api/verify", post(verify_proof))
+        .route(
+            "/api/generate-deposit-details",
+            post(generate_deposit_details),
+        )
+        .route("/api/decode-note-details", post(decode_note_details))
+        .route("/api/get-pubkeys", get(get_pubkeys))
+        .route("/api/input-key", get(input_key))
+        .route("/api/pools", get(list_pools))
+        .route("/api/pools/:pool_id/stats", get(pool_stats))
+        .route("/api/pools/:pool_id/merkle-path", get(merkle_path))
+        .route("/api/pools/:pool_id/tree/subtree/:index", get(tree_subtree))
+        .route("/api/pools/:pool_id/tree/snapshot", get(tree_snapshot))
+        .route("/api/pools/:pool_id/decode-note", post(decode_pool_note))
+        .route("/api/pools/:pool_id/fee-quote", get(fee_quote))
+        .route("/api/relayers", get(list_relayers).post(register_relayer))
+        .route(
+            "/api/pools/:pool_id/compute_withdrawal",
+            get(ws_compute_pool_withdrawal),
+        )
+        .route("/ws/compute_withdrawal", get(ws_compute_proof_withdrawal))
+        .layer(
+            ServiceBuilder::new()
+                .layer(panic_layer) // catches any downstream panic → 500
+                .layer(cors),
+        )
+        .with_state(state)
+}
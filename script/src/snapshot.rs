@@ -0,0 +1,306 @@
+//! Snapshots of a pool's Merkle tree.
+//!
+//! A snapshot holds every leaf, the `next_index`, `filled_subtrees` and root
+//! the program's incremental tree keeps, the nullifier hashes of the
+//! withdrawals made so far, and the signature of the newest transaction it
+//! covers. A new server bootstraps its indexer from one instead of replaying
+//! the whole history, and anyone can check a snapshot offline.
+//!
+//! The binary encoding, integers little-endian:
+//!
+//! | bytes              | field                                    |
+//! |--------------------|------------------------------------------|
+//! | 8                  | magic `MIXTREE1`                         |
+//! | 32                 | program id                               |
+//! | 8                  | `next_index`                             |
+//! | 32                 | root                                     |
+//! | 32 × 20            | `filled_subtrees`, leaf level first      |
+//! | 1 + n              | last signature, base58, length-prefixed  |
+//! | 32 × `next_index`  | leaves                                   |
+//! | 8                  | number of withdrawals `m`                |
+//! | 32 × `m`           | their nullifier hashes, ascending        |
+//! | 32                 | SHA-256 of everything above              |
+//!
+//! The JSON encoding has the same fields in hex and the same checksum.
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use mixer_lib::tree::{tree_state, TREE_DEPTH};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::utils::{to_hex32, to_hex_vec};
+
+const MAGIC: &[u8; 8] = b"MIXTREE1";
+
+/// Encoding of a snapshot file or response.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotFormat {
+    #[default]
+    Json,
+    Binary,
+}
+
+/// A pool's tree as of one transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeSnapshot {
+    pub program_id: Pubkey,
+    pub leaves: Vec<[u8; 32]>,
+    pub root: [u8; 32],
+    pub filled_subtrees: [[u8; 32]; TREE_DEPTH],
+    /// Newest transaction covered, `None` for an empty tree.
+    pub last_signature: Option<String>,
+    /// Nullifier hashes of the withdrawals covered, ascending.
+    pub nullifier_hashes: Vec<[u8; 32]>,
+}
+
+/// [`TreeSnapshot`] as JSON.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotJson {
+    pub program_id: String,
+    pub next_index: u64,
+    pub root: String,
+    pub filled_subtrees: Vec<String>,
+    pub last_signature: Option<String>,
+    pub leaves: Vec<String>,
+    pub nullifier_hashes: Vec<String>,
+    /// Hex SHA-256 of the binary encoding, as in its trailer.
+    pub checksum: String,
+}
+
+impl TreeSnapshot {
+    /// The snapshot of the tree over `leaves`.
+    pub fn new(program_id: Pubkey, leaves: Vec<[u8; 32]>, last_signature: Option<String>) -> Self {
+        let (root, filled_subtrees) = tree_state(&leaves);
+        TreeSnapshot {
+            program_id,
+            leaves,
+            root,
+            filled_subtrees,
+            last_signature,
+            nullifier_hashes: Vec::new(),
+        }
+    }
+
+    /// Records the withdrawals with `nullifier_hashes` as spent.
+    pub fn with_nullifier_hashes(mut self, mut nullifier_hashes: Vec<[u8; 32]>) -> Self {
+        nullifier_hashes.sort_unstable();
+        nullifier_hashes.dedup();
+        self.nullifier_hashes = nullifier_hashes;
+        self
+    }
+
+    pub fn next_index(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    /// Recomputes the root and `filled_subtrees` from the leaves.
+    pub fn check(&self) -> Result<(), String> {
+        let (root, filled_subtrees) = tree_state(&self.leaves);
+        if root != self.root {
+            return Err(format!(
+                "snapshot root {} does not match its leaves ({})",
+                to_hex32(&self.root),
+                to_hex32(&root)
+            ));
+        }
+        if filled_subtrees != self.filled_subtrees {
+            return Err("snapshot filled_subtrees do not match its leaves".to_string());
+        }
+        if !self.nullifier_hashes.windows(2).all(|w| w[0] < w[1]) {
+            return Err("snapshot nullifier hashes are not ascending".to_string());
+        }
+        Ok(())
+    }
+
+    fn body(&self) -> Vec<u8> {
+        let signature = self.last_signature.as_deref().unwrap_or("");
+        let words = self.leaves.len() + self.nullifier_hashes.len() + TREE_DEPTH + 3;
+        let mut out = Vec::with_capacity(8 + 32 * words + 97);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(self.program_id.as_ref());
+        out.extend_from_slice(&self.next_index().to_le_bytes());
+        out.extend_from_slice(&self.root);
+        for node in &self.filled_subtrees {
+            out.extend_from_slice(node);
+        }
+        out.push(signature.len() as u8);
+        out.extend_from_slice(signature.as_bytes());
+        for leaf in &self.leaves {
+            out.extend_from_slice(leaf);
+        }
+        out.extend_from_slice(&(self.nullifier_hashes.len() as u64).to_le_bytes());
+        for nullifier_hash in &self.nullifier_hashes {
+            out.extend_from_slice(nullifier_hash);
+        }
+        out
+    }
+
+    /// SHA-256 of the binary encoding without its trailer.
+    pub fn checksum(&self) -> [u8; 32] {
+        Sha256::digest(self.body()).into()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.body();
+        let checksum: [u8; 32] = Sha256::digest(&out).into();
+        out.extend_from_slice(&checksum);
+        out
+    }
+
+    /// Decodes a binary snapshot and checks its checksum and root.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let (body, checksum) = bytes
+            .split_last_chunk::<32>()
+            .ok_or("snapshot is truncated")?;
+        if Sha256::digest(body).as_slice() != checksum {
+            return Err("snapshot checksum mismatch".to_string());
+        }
+        let mut reader = Reader(
+            body.strip_prefix(MAGIC)
+                .ok_or("not a tree snapshot (bad magic)")?,
+        );
+        let program_id = Pubkey::new_from_array(reader.word()?);
+        let next_index = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let root = reader.word()?;
+        let mut filled_subtrees = [[0u8; 32]; TREE_DEPTH];
+        for node in filled_subtrees.iter_mut() {
+            *node = reader.word()?;
+        }
+        let len = reader.take(1)?[0] as usize;
+        let signature = std::str::from_utf8(reader.take(len)?)
+            .map_err(|_| "snapshot signature is not UTF-8".to_string())?;
+        let leaves = reader.words(next_index)?;
+        let withdrawals = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let nullifier_hashes = reader.words(withdrawals)?;
+        if !reader.0.is_empty() {
+            return Err("snapshot has trailing bytes".to_string());
+        }
+
+        let snapshot = TreeSnapshot {
+            program_id,
+            leaves,
+            root,
+            filled_subtrees,
+            last_signature: (!signature.is_empty()).then(|| signature.to_string()),
+            nullifier_hashes,
+        };
+        snapshot.check()?;
+        Ok(snapshot)
+    }
+
+    pub fn to_json(&self) -> SnapshotJson {
+        SnapshotJson {
+            program_id: self.program_id.to_string(),
+            next_index: self.next_index(),
+            root: to_hex32(&self.root),
+            filled_subtrees: to_hex_vec(&self.filled_subtrees),
+            last_signature: self.last_signature.clone(),
+            leaves: to_hex_vec(&self.leaves),
+            nullifier_hashes: to_hex_vec(&self.nullifier_hashes),
+            checksum: hex::encode(self.checksum()),
+        }
+    }
+
+    /// Decodes a JSON snapshot and checks its checksum and root.
+    pub fn from_json(json: &SnapshotJson) -> Result<Self, String> {
+        let hex32 = |what: &str, s: &str| -> Result<[u8; 32], String> {
+            let bytes = hex::decode(s.strip_prefix("0x").unwrap_or(s))
+                .map_err(|e| format!("invalid {} hex: {}", what, e))?;
+            <[u8; 32]>::try_from(bytes).map_err(|_| format!("{} is not 32 bytes", what))
+        };
+        let leaves = json
+            .leaves
+            .iter()
+            .map(|l| hex32("leaf", l))
+            .collect::<Result<Vec<_>, _>>()?;
+        if leaves.len() as u64 != json.next_index {
+            return Err(format!(
+                "snapshot has {} leaves but next_index {}",
+                leaves.len(),
+                json.next_index
+            ));
+        }
+        let filled_subtrees = json
+            .filled_subtrees
+            .iter()
+            .map(|n| hex32("filled subtree", n))
+            .collect::<Result<Vec<_>, _>>()?
+            .try_into()
+            .map_err(|_| format!("snapshot needs {} filled_subtrees", TREE_DEPTH))?;
+        let snapshot = TreeSnapshot {
+            program_id: Pubkey::from_str(&json.program_id)
+                .map_err(|e| format!("invalid snapshot program id: {}", e))?,
+            leaves,
+            root: hex32("root", &json.root)?,
+            filled_subtrees,
+            last_signature: json.last_signature.clone(),
+            nullifier_hashes: json
+                .nullifier_hashes
+                .iter()
+                .map(|n| hex32("nullifier hash", n))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        if hex::encode(snapshot.checksum()) != json.checksum.to_ascii_lowercase() {
+            return Err("snapshot checksum mismatch".to_string());
+        }
+        snapshot.check()?;
+        Ok(snapshot)
+    }
+
+    /// Reads a snapshot file in either encoding.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("reading snapshot {}: {}", path.display(), e))?;
+        let snapshot = if bytes.starts_with(MAGIC) {
+            Self::from_bytes(&bytes)
+        } else {
+            serde_json::from_slice(&bytes)
+                .map_err(|e| e.to_string())
+                .and_then(|json| Self::from_json(&json))
+        };
+        snapshot.map_err(|e| format!("snapshot {}: {}", path.display(), e))
+    }
+
+    pub fn write(&self, path: &Path, format: SnapshotFormat) -> Result<(), String> {
+        let bytes = match format {
+            SnapshotFormat::Binary => self.to_bytes(),
+            SnapshotFormat::Json => {
+                serde_json::to_vec_pretty(&self.to_json()).map_err(|e| e.to_string())?
+            }
+        };
+        std::fs::write(path, bytes)
+            .map_err(|e| format!("writing snapshot {}: {}", path.display(), e))
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < n {
+            return Err("snapshot is truncated".to_string());
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn word(&mut self) -> Result<[u8; 32], String> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    fn words(&mut self, n: u64) -> Result<Vec<[u8; 32]>, String> {
+        let size = usize::try_from(n)
+            .ok()
+            .and_then(|n| n.checked_mul(32))
+            .ok_or("snapshot is too large")?;
+        Ok(self
+            .take(size)?
+            .chunks_exact(32)
+            .map(|word| word.try_into().unwrap())
+            .collect())
+    }
+}
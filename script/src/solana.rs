@@ -0,0 +1,265 @@
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_groth16::{prepare_verifying_key, Groth16, Proof, VerifyingKey};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use serde::{Deserialize, Serialize};
+use sp1_verifier::{
+    decode_sp1_vkey_hash, hash_public_inputs, load_ark_groth16_verifying_key_from_bytes,
+    load_ark_proof_from_bytes, GROTH16_VK_BYTES,
+};
+
+use crate::verify::normalize_vkey_hash;
+
+/// Length of the Groth16 vkey hash prefix SP1 puts in front of the raw gnark proof.
+const VK_HASH_PREFIX_LENGTH: usize = 4;
+/// Length of the raw gnark proof: A (G1), B (G2), C (G1), uncompressed.
+const GROTH16_PROOF_LENGTH: usize = 256;
+
+/// Output format of a proof returned by the server.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofFormat {
+    /// `proof.bytes()` as produced by SP1 (gnark encoding with vkey hash prefix).
+    #[default]
+    Sp1,
+    /// Layout consumed by `groth16-solana` / the alt_bn128 syscalls.
+    Solana,
+}
+
+/// A Groth16 proof laid out for `groth16_solana::groth16::Groth16Verifier::new`.
+///
+/// All points are big-endian and uncompressed; `proof_a` is already negated.
+/// `public_inputs` are the two field elements SP1 exposes to the Groth16 circuit:
+/// the program vkey hash and the (masked) SHA-256 digest of `public_values`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SolanaProof {
+    pub proof_a: Vec<u8>,             // 64 bytes
+    pub proof_b: Vec<u8>,             // 128 bytes
+    pub proof_c: Vec<u8>,             // 64 bytes
+    pub public_inputs: Vec<[u8; 32]>, // [vkey_hash, committed_values_digest]
+    pub public_values: Vec<u8>,       // raw committed values, hashed on-chain
+}
+
+/// Verifying key fields in the layout of `groth16_solana::groth16::Groth16Verifyingkey`.
+#[derive(Debug, Clone)]
+pub struct SolanaVerifyingKey {
+    pub nr_pubinputs: usize,
+    pub vk_alpha_g1: [u8; 64],
+    pub vk_beta_g2: [u8; 128],
+    pub vk_gamma_g2: [u8; 128],
+    pub vk_delta_g2: [u8; 128],
+    pub vk_ic: Vec<[u8; 64]>,
+}
+
+/// Converts SP1 Groth16 proof bytes (`proof.bytes()`) into the Solana layout.
+pub fn to_solana_proof(
+    proof: &[u8],
+    public_values: &[u8],
+    vkey_hash: &str,
+) -> Result<SolanaProof, String> {
+    if proof.len() != VK_HASH_PREFIX_LENGTH + GROTH16_PROOF_LENGTH {
+        return Err(format!(
+            "expected {} proof bytes, got {}",
+            VK_HASH_PREFIX_LENGTH + GROTH16_PROOF_LENGTH,
+            proof.len()
+        ));
+    }
+    let ark_proof = load_ark_proof_from_bytes(&proof[VK_HASH_PREFIX_LENGTH..])
+        .map_err(|e| format!("invalid Groth16 proof: {}", e))?;
+
+    Ok(SolanaProof {
+        proof_a: g1_to_be_bytes(&-ark_proof.a).to_vec(),
+        proof_b: g2_to_be_bytes(&ark_proof.b).to_vec(),
+        proof_c: g1_to_be_bytes(&ark_proof.c).to_vec(),
+        public_inputs: groth16_public_inputs(public_values, vkey_hash)?.to_vec(),
+        public_values: public_values.to_vec(),
+    })
+}
+
+/// The Groth16 public inputs for an SP1 proof: the program vkey hash and the
+/// digest of the committed public values, each as a 32-byte big-endian field element.
+pub fn groth16_public_inputs(
+    public_values: &[u8],
+    vkey_hash: &str,
+) -> Result<[[u8; 32]; 2], String> {
+    let vkey_hash = normalize_vkey_hash(vkey_hash)?;
+    let vkey_hash = decode_sp1_vkey_hash(&vkey_hash).map_err(|e| e.to_string())?;
+    Ok([vkey_hash, hash_public_inputs(public_values)])
+}
+
+/// Verifies `proof` as the on-chain verifier would: its public inputs must be
+/// `vkey_hash` and the digest of its public values, and its points must
+/// verify against SP1's Groth16 key.
+pub fn verify_solana_proof(proof: &SolanaProof, vkey_hash: &str) -> Result<(), String> {
+    let inputs = groth16_public_inputs(&proof.public_values, vkey_hash)?;
+    if proof.public_inputs != inputs {
+        return Err("public inputs are not the vkey hash and public values digest".to_string());
+    }
+    let ark_proof = Proof::<Bn254> {
+        a: -g1_from_be_bytes(&proof.proof_a)?,
+        b: g2_from_be_bytes(&proof.proof_b)?,
+        c: g1_from_be_bytes(&proof.proof_c)?,
+    };
+    let inputs = inputs
+        .iter()
+        .map(|input| fr_from_be_bytes(input))
+        .collect::<Result<Vec<_>, _>>()?;
+    let vk = load_ark_groth16_verifying_key_from_bytes(&GROTH16_VK_BYTES)
+        .map_err(|e| format!("invalid Groth16 verifying key: {}", e))?;
+    match Groth16::<Bn254>::verify_proof(&prepare_verifying_key(&vk), &ark_proof, &inputs) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("Groth16 proof does not verify".to_string()),
+        Err(e) => Err(format!("Groth16 verification failed: {}", e)),
+    }
+}
+
+/// Converts SP1's gnark Groth16 verifying key (`sp1_verifier::GROTH16_VK_BYTES`)
+/// into the Solana layout.
+pub fn to_solana_vk(groth16_vk: &[u8]) -> Result<SolanaVerifyingKey, String> {
+    let vk = load_ark_groth16_verifying_key_from_bytes(groth16_vk)
+        .map_err(|e| format!("invalid Groth16 verifying key: {}", e))?;
+    Ok(SolanaVerifyingKey::from(&vk))
+}
+
+impl From<&VerifyingKey<Bn254>> for SolanaVerifyingKey {
+    fn from(vk: &VerifyingKey<Bn254>) -> Self {
+        SolanaVerifyingKey {
+            nr_pubinputs: vk.gamma_abc_g1.len() - 1,
+            vk_alpha_g1: g1_to_be_bytes(&vk.alpha_g1),
+            vk_beta_g2: g2_to_be_bytes(&vk.beta_g2),
+            vk_gamma_g2: g2_to_be_bytes(&vk.gamma_g2),
+            vk_delta_g2: g2_to_be_bytes(&vk.delta_g2),
+            vk_ic: vk.gamma_abc_g1.iter().map(g1_to_be_bytes).collect(),
+        }
+    }
+}
+
+/// Renders the verifying key and program vkey hash as a Rust source file that an
+/// Anchor program can `include!` next to `groth16-solana`.
+pub fn solana_vk_rust_source(vk: &SolanaVerifyingKey, vkey_hash: &str) -> Result<String, String> {
+    let vkey_hash =
+        decode_sp1_vkey_hash(&normalize_vkey_hash(vkey_hash)?).map_err(|e| e.to_string())?;
+
+    let mut out = String::new();
+    out.push_str("// Generated by `cargo run --bin vkey -- generate`. Do not edit.\n");
+    out.push_str("use groth16_solana::groth16::Groth16Verifyingkey;\n\n");
+    out.push_str("/// SP1 vkey hash of `mixer-program`, the first Groth16 public input.\n");
+    out.push_str(&format!(
+        "pub const MIXER_VKEY_HASH: [u8; 32] = {};\n\n",
+        byte_array(&vkey_hash)
+    ));
+    out.push_str("/// SP1 Groth16 verifying key.\n");
+    out.push_str("pub const VERIFYINGKEY: Groth16Verifyingkey = Groth16Verifyingkey {\n");
+    out.push_str(&format!("    nr_pubinputs: {},\n", vk.nr_pubinputs));
+    out.push_str(&format!(
+        "    vk_alpha_g1: {},\n",
+        byte_array(&vk.vk_alpha_g1)
+    ));
+    out.push_str(&format!(
+        "    vk_beta_g2: {},\n",
+        byte_array(&vk.vk_beta_g2)
+    ));
+    out.push_str(&format!(
+        "    vk_gamme_g2: {},\n",
+        byte_array(&vk.vk_gamma_g2)
+    ));
+    out.push_str(&format!(
+        "    vk_delta_g2: {},\n",
+        byte_array(&vk.vk_delta_g2)
+    ));
+    out.push_str("    vk_ic: &[\n");
+    for ic in &vk.vk_ic {
+        out.push_str(&format!("        {},\n", byte_array(ic)));
+    }
+    out.push_str("    ],\n};\n");
+    Ok(out)
+}
+
+fn byte_array(bytes: &[u8]) -> String {
+    let items: Vec<String> = bytes.iter().map(|b| b.to_string()).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn fq_to_be_bytes(f: &Fq) -> [u8; 32] {
+    let mut buf = [0u8; 32];
+    f.serialize_uncompressed(&mut buf[..])
+        .expect("Fq is 32 bytes");
+    buf.reverse();
+    buf
+}
+
+fn fq2_to_be_bytes(f: &Fq2) -> [u8; 64] {
+    // EIP-197 / alt_bn128 order: imaginary part first.
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(&fq_to_be_bytes(&f.c1));
+    buf[32..].copy_from_slice(&fq_to_be_bytes(&f.c0));
+    buf
+}
+
+fn g1_to_be_bytes(p: &G1Affine) -> [u8; 64] {
+    let mut buf = [0u8; 64];
+    buf[..32].copy_from_slice(&fq_to_be_bytes(&p.x));
+    buf[32..].copy_from_slice(&fq_to_be_bytes(&p.y));
+    buf
+}
+
+fn g2_to_be_bytes(p: &G2Affine) -> [u8; 128] {
+    let mut buf = [0u8; 128];
+    buf[..64].copy_from_slice(&fq2_to_be_bytes(&p.x));
+    buf[64..].copy_from_slice(&fq2_to_be_bytes(&p.y));
+    buf
+}
+
+fn fq_from_be_bytes(bytes: &[u8]) -> Result<Fq, String> {
+    let mut buf = bytes.to_vec();
+    buf.reverse();
+    Fq::deserialize_uncompressed(&buf[..])
+        .map_err(|_| "proof coordinate is not in the field".to_string())
+}
+
+fn fr_from_be_bytes(bytes: &[u8; 32]) -> Result<Fr, String> {
+    let mut buf = *bytes;
+    buf.reverse();
+    Fr::deserialize_uncompressed(&buf[..])
+        .map_err(|_| "public input is not in the field".to_string())
+}
+
+fn fq2_from_be_bytes(bytes: &[u8]) -> Result<Fq2, String> {
+    Ok(Fq2::new(
+        fq_from_be_bytes(&bytes[32..])?,
+        fq_from_be_bytes(&bytes[..32])?,
+    ))
+}
+
+fn g1_from_be_bytes(bytes: &[u8]) -> Result<G1Affine, String> {
+    if bytes.len() != 64 {
+        return Err(format!(
+            "expected a 64-byte G1 point, got {} bytes",
+            bytes.len()
+        ));
+    }
+    let p = G1Affine::new_unchecked(
+        fq_from_be_bytes(&bytes[..32])?,
+        fq_from_be_bytes(&bytes[32..])?,
+    );
+    if !p.is_on_curve() || !p.is_in_correct_subgroup_assuming_on_curve() {
+        return Err("proof point is not on the G1 curve".to_string());
+    }
+    Ok(p)
+}
+
+fn g2_from_be_bytes(bytes: &[u8]) -> Result<G2Affine, String> {
+    if bytes.len() != 128 {
+        return Err(format!(
+            "expected a 128-byte G2 point, got {} bytes",
+            bytes.len()
+        ));
+    }
+    let p = G2Affine::new_unchecked(
+        fq2_from_be_bytes(&bytes[..64])?,
+        fq2_from_be_bytes(&bytes[64..])?,
+    );
+    if !p.is_on_curve() || !p.is_in_correct_subgroup_assuming_on_curve() {
+        return Err("proof point is not on the G2 curve".to_string());
+    }
+    Ok(p)
+}
@@ -0,0 +1,541 @@
+use anchor_client::{
+    solana_client::{
+        client_error::ClientError, nonblocking::rpc_client::RpcClient,
+        rpc_client::GetConfirmedSignaturesForAddress2Config, rpc_config::RpcTransactionConfig,
+    },
+    solana_sdk::{
+        commitment_config::{CommitmentConfig, CommitmentLevel},
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::Transaction,
+    },
+};
+use futures_util::future::BoxFuture;
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, TransactionConfirmationStatus, TransactionStatus,
+    UiTransactionEncoding,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Page size of `getSignaturesForAddress` (the RPC maximum).
+const SIGNATURES_PAGE_LIMIT: usize = 1000;
+const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A successful transaction that touched the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureInfo {
+    pub signature: String,
+    pub slot: u64,
+    /// How final the transaction was when listed.
+    pub status: CommitmentLevel,
+    /// Unix time of the block, if the node reports it.
+    pub block_time: Option<i64>,
+}
+
+/// Whether `level` is at least as final as `min`.
+pub fn is_at_least(level: CommitmentLevel, min: CommitmentLevel) -> bool {
+    let rank = |l: CommitmentLevel| match l {
+        CommitmentLevel::Processed => 0,
+        CommitmentLevel::Confirmed => 1,
+        CommitmentLevel::Finalized => 2,
+    };
+    rank(level) >= rank(min)
+}
+
+/// Commitment level of a reported status. Older nodes omit the status, in
+/// which case `fallback` (the requested level) is assumed.
+pub fn commitment_level(
+    status: Option<&TransactionConfirmationStatus>,
+    fallback: CommitmentLevel,
+) -> CommitmentLevel {
+    match status {
+        Some(TransactionConfirmationStatus::Processed) => CommitmentLevel::Processed,
+        Some(TransactionConfirmationStatus::Confirmed) => CommitmentLevel::Confirmed,
+        Some(TransactionConfirmationStatus::Finalized) => CommitmentLevel::Finalized,
+        None => fallback,
+    }
+}
+
+/// Where deposit history comes from, and where withdrawals go. Every code
+/// path that touches Solana goes through this trait so that tests can swap in
+/// [`FixtureSource`].
+pub trait DepositSource: Send + Sync {
+    /// Successful transactions that touched `program_id`, newest first,
+    /// stopping before `until` when it is given.
+    fn signatures<'a>(
+        &'a self,
+        program_id: &'a Pubkey,
+        until: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Vec<SignatureInfo>, String>>;
+
+    /// Log messages of one transaction.
+    fn transaction_logs<'a>(
+        &'a self,
+        signature: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<String>, String>>;
+
+    /// Data of the account at `address`.
+    fn account_data<'a>(&'a self, address: &'a Pubkey) -> BoxFuture<'a, Result<Vec<u8>, String>>;
+
+    fn latest_blockhash(&self) -> BoxFuture<'_, Result<Hash, String>>;
+
+    /// Sends a signed transaction.
+    fn send_transaction<'a>(
+        &'a self,
+        tx: &'a Transaction,
+    ) -> BoxFuture<'a, Result<Signature, String>>;
+
+    /// Priority fees, in micro-lamports per compute unit, paid in recent
+    /// slots by transactions writing to `accounts`.
+    fn recent_prioritization_fees<'a>(
+        &'a self,
+        accounts: &'a [Pubkey],
+    ) -> BoxFuture<'a, Result<Vec<u64>, String>>;
+
+    /// Status of a sent transaction, `None` while no node has seen it.
+    fn signature_status<'a>(
+        &'a self,
+        signature: &'a Signature,
+    ) -> BoxFuture<'a, Result<Option<TransactionStatus>, String>>;
+}
+
+/// How often and how long to retry a failing RPC call.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Rounds over all endpoints before giving up.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff after the `attempt`-th failed round (0-based).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay)
+    }
+}
+
+/// Spaces requests at least `interval` apart.
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: u32) -> Self {
+        let interval = match requests_per_second {
+            0 => Duration::ZERO,
+            n => Duration::from_secs(1) / n,
+        };
+        RateLimiter {
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn wait(&self) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            tokio::time::sleep_until(*next).await;
+        }
+        *next = (*next).max(now) + self.interval;
+    }
+}
+
+/// [`DepositSource`] backed by one or more JSON-RPC endpoints.
+///
+/// Each call is tried against every endpoint, starting with the last one that
+/// answered, and the whole round is retried with exponential backoff.
+pub struct RpcDepositSource {
+    endpoints: Vec<(String, RpcClient)>,
+    preferred: AtomicUsize,
+    retry: RetryPolicy,
+    limiter: RateLimiter,
+    commitment: CommitmentConfig,
+}
+
+impl RpcDepositSource {
+    pub fn new(urls: Vec<String>) -> Result<Self, String> {
+        let urls: Vec<String> = urls
+            .into_iter()
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty())
+            .collect();
+        if urls.is_empty() {
+            return Err("no Solana RPC URL configured".to_string());
+        }
+        let commitment = CommitmentConfig::confirmed();
+        Ok(RpcDepositSource {
+            endpoints: urls
+                .into_iter()
+                .map(|url| {
+                    let client = RpcClient::new_with_timeout_and_commitment(
+                        url.clone(),
+                        RPC_TIMEOUT,
+                        commitment,
+                    );
+                    (url, client)
+                })
+                .collect(),
+            preferred: AtomicUsize::new(0),
+            retry: RetryPolicy::default(),
+            limiter: RateLimiter::new(0),
+            commitment,
+        })
+    }
+
+    /// Parses a comma-separated list of RPC URLs.
+    pub fn from_url_list(urls: &str) -> Result<Self, String> {
+        Self::new(urls.split(',').map(str::to_string).collect())
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Caps the request rate across all endpoints. `0` disables the limit.
+    pub fn with_rate_limit(mut self, requests_per_second: u32) -> Self {
+        self.limiter = RateLimiter::new(requests_per_second);
+        self
+    }
+
+    async fn call<T>(
+        &self,
+        what: &str,
+        f: impl for<'a> Fn(&'a RpcClient) -> BoxFuture<'a, Result<T, ClientError>>,
+    ) -> Result<T, String> {
+        let n = self.endpoints.len();
+        let mut last_err = String::new();
+        for attempt in 0..self.retry.max_attempts.max(1) {
+            if attempt > 0 {
+                tokio::time::sleep(self.retry.delay(attempt - 1)).await;
+            }
+            let start = self.preferred.load(Ordering::Relaxed);
+            for i in 0..n {
+                let idx = (start + i) % n;
+                let (url, client) = &self.endpoints[idx];
+                self.limiter.wait().await;
+                match f(client).await {
+                    Ok(v) => {
+                        self.preferred.store(idx, Ordering::Relaxed);
+                        return Ok(v);
+                    }
+                    Err(e) => {
+                        tracing::warn!("{} via {} failed (attempt {}): {}", what, url, attempt, e);
+                        last_err = format!("{} via {}: {}", what, url, e);
+                    }
+                }
+            }
+        }
+        Err(last_err)
+    }
+}
+
+impl DepositSource for RpcDepositSource {
+    fn signatures<'a>(
+        &'a self,
+        program_id: &'a Pubkey,
+        until: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Vec<SignatureInfo>, String>> {
+        Box::pin(self.fetch_signatures(*program_id, until))
+    }
+
+    fn transaction_logs<'a>(
+        &'a self,
+        signature: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<String>, String>> {
+        Box::pin(self.fetch_transaction_logs(signature))
+    }
+
+    fn account_data<'a>(&'a self, address: &'a Pubkey) -> BoxFuture<'a, Result<Vec<u8>, String>> {
+        let address = *address;
+        Box::pin(self.call("getAccountInfo", move |rpc| {
+            Box::pin(async move { rpc.get_account_data(&address).await })
+        }))
+    }
+
+    fn latest_blockhash(&self) -> BoxFuture<'_, Result<Hash, String>> {
+        Box::pin(self.call("getLatestBlockhash", |rpc| {
+            Box::pin(async move { rpc.get_latest_blockhash().await })
+        }))
+    }
+
+    /// Resending the transaction to another endpoint after a failure is
+    /// safe, since the signature identifies it.
+    fn send_transaction<'a>(
+        &'a self,
+        tx: &'a Transaction,
+    ) -> BoxFuture<'a, Result<Signature, String>> {
+        let tx = tx.clone();
+        Box::pin(self.call("sendTransaction", move |rpc| {
+            let tx = tx.clone();
+            Box::pin(async move { rpc.send_transaction(&tx).await })
+        }))
+    }
+
+    fn recent_prioritization_fees<'a>(
+        &'a self,
+        accounts: &'a [Pubkey],
+    ) -> BoxFuture<'a, Result<Vec<u64>, String>> {
+        let accounts = accounts.to_vec();
+        Box::pin(async move {
+            let fees = self
+                .call("getRecentPrioritizationFees", move |rpc| {
+                    let accounts = accounts.clone();
+                    Box::pin(async move { rpc.get_recent_prioritization_fees(&accounts).await })
+                })
+                .await?;
+            Ok(fees.into_iter().map(|f| f.prioritization_fee).collect())
+        })
+    }
+
+    fn signature_status<'a>(
+        &'a self,
+        signature: &'a Signature,
+    ) -> BoxFuture<'a, Result<Option<TransactionStatus>, String>> {
+        let signature = *signature;
+        Box::pin(async move {
+            let mut statuses = self
+                .call("getSignatureStatuses", move |rpc| {
+                    Box::pin(async move { rpc.get_signature_statuses(&[signature]).await })
+                })
+                .await?;
+            Ok(statuses.value.pop().flatten())
+        })
+    }
+}
+
+impl RpcDepositSource {
+    async fn fetch_signatures(
+        &self,
+        program_id: Pubkey,
+        until: Option<&str>,
+    ) -> Result<Vec<SignatureInfo>, String> {
+        let until = until
+            .map(|s| Signature::from_str(s).map_err(|e| format!("invalid signature {}: {}", s, e)))
+            .transpose()?;
+        let commitment = self.commitment;
+        let mut out = Vec::new();
+        let mut before: Option<Signature> = None;
+        loop {
+            let page = self
+                .call("getSignaturesForAddress", move |rpc| {
+                    Box::pin(async move {
+                        rpc.get_signatures_for_address_with_config(
+                            &program_id,
+                            GetConfirmedSignaturesForAddress2Config {
+                                before,
+                                until,
+                                limit: Some(SIGNATURES_PAGE_LIMIT),
+                                commitment: Some(commitment),
+                            },
+                        )
+                        .await
+                    })
+                })
+                .await?;
+
+            let full = page.len() == SIGNATURES_PAGE_LIMIT;
+            if let Some(last) = page.last() {
+                before = Some(Signature::from_str(&last.signature).map_err(|e| e.to_string())?);
+            }
+            out.extend(
+                page.into_iter()
+                    .filter(|s| s.err.is_none())
+                    .map(|s| SignatureInfo {
+                        signature: s.signature,
+                        slot: s.slot,
+                        status: commitment_level(
+                            s.confirmation_status.as_ref(),
+                            CommitmentLevel::Confirmed,
+                        ),
+                        block_time: s.block_time,
+                    }),
+            );
+            if !full {
+                return Ok(out);
+            }
+        }
+    }
+
+    async fn fetch_transaction_logs(&self, signature: &str) -> Result<Vec<String>, String> {
+        let sig = Signature::from_str(signature).map_err(|e| e.to_string())?;
+        let commitment = self.commitment;
+        let tx = self
+            .call("getTransaction", move |rpc| {
+                Box::pin(async move {
+                    rpc.get_transaction_with_config(
+                        &sig,
+                        RpcTransactionConfig {
+                            encoding: Some(UiTransactionEncoding::Json),
+                            commitment: Some(commitment),
+                            max_supported_transaction_version: Some(0),
+                        },
+                    )
+                    .await
+                })
+            })
+            .await?;
+        Ok(match tx.transaction.meta.map(|m| m.log_messages) {
+            Some(OptionSerializer::Some(logs)) => logs,
+            _ => Vec::new(),
+        })
+    }
+}
+
+/// In-memory [`DepositSource`] for tests. Transactions are kept oldest first.
+/// Sent transactions are recorded and reported finalized in the slot after
+/// the newest one.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureSource {
+    txs: Vec<(SignatureInfo, Vec<String>)>,
+    index: HashMap<String, usize>,
+    accounts: HashMap<Pubkey, Vec<u8>>,
+    priority_fees: Vec<u64>,
+    sent: Arc<std::sync::Mutex<Vec<Transaction>>>,
+}
+
+impl FixtureSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_account(mut self, address: Pubkey, data: Vec<u8>) -> Self {
+        self.accounts.insert(address, data);
+        self
+    }
+
+    pub fn with_priority_fees(mut self, fees: Vec<u64>) -> Self {
+        self.priority_fees = fees;
+        self
+    }
+
+    /// Transactions sent so far, oldest first.
+    pub fn sent(&self) -> Vec<Transaction> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Adds a finalized transaction.
+    pub fn push(&mut self, signature: &str, slot: u64, logs: Vec<String>) {
+        self.push_with_status(signature, slot, CommitmentLevel::Finalized, logs);
+    }
+
+    pub fn push_with_status(
+        &mut self,
+        signature: &str,
+        slot: u64,
+        status: CommitmentLevel,
+        logs: Vec<String>,
+    ) {
+        self.index.insert(signature.to_string(), self.txs.len());
+        self.txs.push((
+            SignatureInfo {
+                signature: signature.to_string(),
+                slot,
+                status,
+                block_time: None,
+            },
+            logs,
+        ));
+    }
+}
+
+impl DepositSource for FixtureSource {
+    fn signatures<'a>(
+        &'a self,
+        _program_id: &'a Pubkey,
+        until: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Vec<SignatureInfo>, String>> {
+        let sigs = self
+            .txs
+            .iter()
+            .rev()
+            .map(|(s, _)| s.clone())
+            .take_while(|s| Some(s.signature.as_str()) != until)
+            .collect();
+        Box::pin(async move { Ok(sigs) })
+    }
+
+    fn transaction_logs<'a>(
+        &'a self,
+        signature: &'a str,
+    ) -> BoxFuture<'a, Result<Vec<String>, String>> {
+        let logs = self
+            .index
+            .get(signature)
+            .map(|&i| self.txs[i].1.clone())
+            .ok_or_else(|| format!("unknown transaction {}", signature));
+        Box::pin(async move { logs })
+    }
+
+    fn account_data<'a>(&'a self, address: &'a Pubkey) -> BoxFuture<'a, Result<Vec<u8>, String>> {
+        let data = self
+            .accounts
+            .get(address)
+            .cloned()
+            .ok_or_else(|| format!("account {} not found", address));
+        Box::pin(async move { data })
+    }
+
+    fn latest_blockhash(&self) -> BoxFuture<'_, Result<Hash, String>> {
+        Box::pin(async move { Ok(Hash::default()) })
+    }
+
+    fn send_transaction<'a>(
+        &'a self,
+        tx: &'a Transaction,
+    ) -> BoxFuture<'a, Result<Signature, String>> {
+        self.sent.lock().unwrap().push(tx.clone());
+        Box::pin(async move { Ok(tx.signatures[0]) })
+    }
+
+    fn recent_prioritization_fees<'a>(
+        &'a self,
+        _accounts: &'a [Pubkey],
+    ) -> BoxFuture<'a, Result<Vec<u64>, String>> {
+        let fees = self.priority_fees.clone();
+        Box::pin(async move { Ok(fees) })
+    }
+
+    fn signature_status<'a>(
+        &'a self,
+        signature: &'a Signature,
+    ) -> BoxFuture<'a, Result<Option<TransactionStatus>, String>> {
+        let sent = self.sent.lock().unwrap();
+        let status = sent
+            .iter()
+            .any(|tx| tx.signatures.first() == Some(signature))
+            .then(|| TransactionStatus {
+                slot: self.txs.last().map_or(0, |(info, _)| info.slot) + 1,
+                confirmations: None,
+                status: Ok(()),
+                err: None,
+                confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+            });
+        Box::pin(async move { Ok(status) })
+    }
+}
@@ -0,0 +1,172 @@
+use anchor_client::solana_sdk::{commitment_config::CommitmentLevel, pubkey::Pubkey};
+use num_bigint::BigUint;
+
+use serde::{Deserialize, Serialize};
+use solana_poseidon::{hashv, Endianness, Parameters};
+use std::str::FromStr;
+
+use crate::idl::EventDecoder;
+use crate::source::{is_at_least, DepositSource};
+
+pub use mixer_lib::tree::{compute_merkle_proof, TREE_DEPTH, ZERO_HASHES};
+
+#[derive(Serialize, Deserialize)]
+pub struct NoteState {
+    pub bump: u8,
+    pub administrator: String,
+    pub merkle: NoteStateMerkle,
+    pub current_root: [u8; 32],
+}
+#[derive(Serialize, Deserialize)]
+pub struct NoteStateMerkle {
+    pub next_index: u32,
+    pub filled_subtrees: Vec<[u8; 32]>,
+}
+
+pub fn get_pubkeys_utils(program_pubkey: String) -> String {
+    let (state_pubkey, _state_bump) = Pubkey::find_program_address(
+        &[crate::relayer::STATE_SEED],
+        &Pubkey::from_str(&program_pubkey).unwrap(),
+    );
+    state_pubkey.to_string()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoseidonHash(pub [u8; 32]);
+
+impl PoseidonHash {
+    pub fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> PoseidonHash {
+        let out = hashv(
+            Parameters::Bn254X5,
+            Endianness::LittleEndian,
+            &[&a[..], &b[..]],
+        )
+        .expect("poseidon failed")
+        .to_bytes();
+        PoseidonHash(out)
+    }
+    pub fn hash_single(a: &[u8; 32]) -> PoseidonHash {
+        let out = hashv(Parameters::Bn254X5, Endianness::LittleEndian, &[&a[..]])
+            .expect("poseidon failed")
+            .to_bytes();
+        PoseidonHash(out)
+    }
+
+    pub fn empty_leaf() -> PoseidonHash {
+        let zero = [0u8; 32];
+        let out = hashv(Parameters::Bn254X5, Endianness::LittleEndian, &[&zero])
+            .expect("poseidon failed")
+            .to_bytes();
+        PoseidonHash(out)
+    }
+}
+
+#[derive(Debug)]
+struct LeafEntry {
+    index: usize,
+    commitment: [u8; 32],
+}
+
+/// Result of replaying the deposit history: commitments sorted by leaf index,
+/// `(leaf_index, commitment)` pairs, the sorted leaf indices, the index of the
+/// searched commitment and whether it was found at all.
+pub type DepositScan = (
+    Vec<[u8; 32]>,
+    Vec<(usize, [u8; 32])>,
+    Vec<usize>,
+    usize,
+    bool,
+);
+
+/// Replays the deposit history, keeping only transactions at least as final
+/// as `min_status`.
+pub async fn fetch_deposits(
+    source: &dyn DepositSource,
+    events: &EventDecoder,
+    commitment_to_find: [u8; 32],
+    program_id: &str,
+    min_status: CommitmentLevel,
+) -> Result<DepositScan, String> {
+    let program_id = Pubkey::from_str(program_id).map_err(|e| e.to_string())?;
+
+    let sigs = source.signatures(&program_id, None).await?;
+
+    let mut my_index: Option<usize> = None;
+    let mut leaf_entries: Vec<LeafEntry> = Vec::new();
+
+    for sig_info in sigs {
+        if !is_at_least(sig_info.status, min_status) {
+            continue;
+        }
+        let logs = source.transaction_logs(&sig_info.signature).await?;
+        for event in events.deposits(&logs) {
+            let idx = event.leaf_index as usize;
+            leaf_entries.push(LeafEntry {
+                index: idx,
+                commitment: event.commitment,
+            });
+            if event.commitment == commitment_to_find {
+                my_index = Some(idx);
+            }
+        }
+    }
+
+    leaf_entries.sort_unstable_by_key(|e| e.index);
+
+    let (leaf_indices, commitments): (Vec<usize>, Vec<[u8; 32]>) =
+        leaf_entries.iter().map(|e| (e.index, e.commitment)).unzip();
+
+    let found = my_index.is_some();
+    let index = my_index.unwrap_or(0);
+    Ok((
+        commitments,
+        leaf_entries
+            .iter()
+            .map(|e| (e.index, e.commitment))
+            .collect(),
+        leaf_indices,
+        index,
+        found,
+    ))
+}
+
+pub fn merkle_check<const LEVEL: usize>(
+    root: [u8; 32],
+    leaf: [u8; 32],
+    siblings: &[[u8; 32]; LEVEL],
+    path_indices: &[u8; LEVEL],
+) {
+    let mut node = leaf;
+    for i in 0..LEVEL {
+        let (l, r) = if path_indices[i] == 0 {
+            (node, siblings[i])
+        } else {
+            (siblings[i], node)
+        };
+        node = PoseidonHash::hash_pair(&l, &r).0;
+    }
+    assert!(node == root, "Merkle proof did not match");
+}
+
+/// Convert a byte‐array or BigUint to hex string with `0x` and fixed length
+pub fn to_hex32(bytes: &[u8; 32]) -> String {
+    let mut s = hex::encode(bytes);
+    s.truncate(64);
+    while s.len() < 64 {
+        s.insert(0, '0');
+    }
+    format!("0x{}", s)
+}
+
+pub fn biguint_to_32_le_bytes(n: &BigUint) -> [u8; 32] {
+    let mut v = n.to_bytes_le();
+    if v.len() > 32 {
+        panic!("BigUint doesn’t fit in 32 bytes");
+    }
+    v.resize(32, 0);
+    v.try_into().unwrap()
+}
+
+pub fn to_hex_vec(v: &[[u8; 32]]) -> Vec<String> {
+    v.iter().map(mixer_lib::utils::to_hex32).collect()
+}
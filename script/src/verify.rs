@@ -0,0 +1,31 @@
+use sp1_verifier::{Groth16Verifier, GROTH16_VK_BYTES};
+
+/// Verifies an SP1 Groth16 proof (as returned by `proof.bytes()`) against the
+/// committed public values and the program vkey hash (`vk.bytes32()`).
+pub fn verify_groth16(proof: &[u8], public_values: &[u8], vkey_hash: &str) -> Result<(), String> {
+    if proof.is_empty() {
+        return Err("empty proof (mock proofs cannot be verified)".to_string());
+    }
+    let vkey_hash = normalize_vkey_hash(vkey_hash)?;
+    Groth16Verifier::verify(proof, public_values, &vkey_hash, &GROTH16_VK_BYTES)
+        .map_err(|e| e.to_string())
+}
+
+/// Accepts a vkey hash with or without `0x` and returns it in the `0x`-prefixed,
+/// 32-byte form that `vk.bytes32()` produces.
+pub fn normalize_vkey_hash(vkey_hash: &str) -> Result<String, String> {
+    let s = vkey_hash.strip_prefix("0x").unwrap_or(vkey_hash);
+    let bytes = hex::decode(s).map_err(|e| format!("invalid vkey hash hex: {}", e))?;
+    if bytes.len() != 32 {
+        return Err(format!("vkey hash length != 32: “{}”", vkey_hash));
+    }
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
+
+/// Whether `prove_mix` should skip checking the proof before returning it.
+/// Controlled by `SKIP_PROOF_VERIFICATION=true`; verification is on by default.
+pub fn skip_proof_verification() -> bool {
+    std::env::var("SKIP_PROOF_VERIFICATION")
+        .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+        .unwrap_or(false)
+}
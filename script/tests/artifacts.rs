@@ -0,0 +1,69 @@
+//! Verifier artifacts: the files `vkey generate` writes and the drift check.
+
+use mixer_script::artifacts::{self, VerifierManifest, MANIFEST_FILE};
+use mixer_script::solana;
+use sp1_verifier::GROTH16_VK_BYTES;
+
+const VKEY_HASH: &str = "0x00ab11111111111111111111111111111111111111111111111111111111cdef";
+
+#[test]
+fn written_artifacts_match_the_manifest_until_it_drifts() {
+    let dir = std::env::temp_dir().join(format!("artifacts-{}", std::process::id()));
+    let manifest = VerifierManifest::new(b"elf", VKEY_HASH, &GROTH16_VK_BYTES).unwrap();
+    artifacts::write_artifacts(&dir, &manifest, &GROTH16_VK_BYTES).unwrap();
+
+    let path = dir.join(MANIFEST_FILE);
+    assert_eq!(artifacts::read_manifest(&path).unwrap(), manifest);
+    assert!(artifacts::check(&path, &manifest).unwrap().is_empty());
+
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+    assert_eq!(read(&manifest.files.vkey_hash), format!("{}\n", VKEY_HASH));
+    // SHA-256("elf")
+    assert_eq!(
+        read(&manifest.files.elf_sha256),
+        "780d84b20d7ae7e6292919399348bdbf96025270136198083fc8a4da398b5ca9\n"
+    );
+    assert_eq!(
+        read(&manifest.files.sp1_version),
+        format!("{}\n", manifest.sp1_version)
+    );
+    let vk = solana::to_solana_vk(&GROTH16_VK_BYTES).unwrap();
+    assert_eq!(
+        read(&manifest.files.solana_vk),
+        solana::solana_vk_rust_source(&vk, VKEY_HASH).unwrap()
+    );
+    let solidity = read(&manifest.files.solidity_vk);
+    assert_eq!(solidity, artifacts::solidity_vk_source(&vk, VKEY_HASH));
+    assert!(solidity.contains(&format!(
+        "    bytes32 internal constant PROGRAM_VKEY = {};\n",
+        VKEY_HASH
+    )));
+    assert!(solidity.contains(&format!(
+        "    uint256 internal constant ALPHA_X = 0x{};\n",
+        hex::encode(&vk.vk_alpha_g1[..32])
+    )));
+    assert!(solidity.contains(&format!(
+        "    uint256 internal constant BETA_X_1 = 0x{};\n",
+        hex::encode(&vk.vk_beta_g2[..32])
+    )));
+    assert!(solidity.contains("IC2_Y") && !solidity.contains("IC3_X"));
+
+    // A rebuilt ELF, or one with a new vkey, drifts.
+    let rebuilt = VerifierManifest::new(b"elf2", VKEY_HASH, &GROTH16_VK_BYTES).unwrap();
+    let drift = artifacts::check(&path, &rebuilt).unwrap();
+    assert_eq!(drift.len(), 1);
+    assert!(drift[0].starts_with("elf_sha256: "), "{:?}", drift);
+    let rekeyed =
+        VerifierManifest::new(b"elf", &VKEY_HASH.replace("ab", "cd"), &GROTH16_VK_BYTES).unwrap();
+    let drift = artifacts::check(&path, &rekeyed).unwrap();
+    assert_eq!(drift.len(), 1);
+    assert!(drift[0].starts_with("vkey_hash: "), "{:?}", drift);
+    let both = VerifierManifest {
+        vkey_hash: rekeyed.vkey_hash.clone(),
+        ..rebuilt
+    };
+    assert_eq!(artifacts::check(&path, &both).unwrap().len(), 2);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(artifacts::check(&path, &manifest).is_err());
+}
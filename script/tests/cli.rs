@@ -0,0 +1,172 @@
+//! The `mixer` command line, run as a binary against mock RPC and proof API
+//! servers.
+mod common;
+
+use anchor_client::solana_sdk::commitment_config::CommitmentLevel::Finalized;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use common::{deposit_logs, registry_with_index, spawn_app_with_state, spawn_mock_rpc, RpcFixture};
+use mixer_lib::tree::MerklePath;
+use mixer_script::cli::{self, RemoteServer};
+use mixer_script::idl::EventDecoder;
+use mixer_script::indexer::DepositIndex;
+use mixer_script::note::Note;
+use mixer_script::pools::PoolConfig;
+use mixer_script::server::{AppState, MerklePathResponse, ServerConfig, MIXER_ELF};
+use mixer_script::utils::to_hex32;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::process::Command;
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+/// Runs `mixer` with `args` and a configuration of only `env`. Returns its
+/// stdout, or its stderr if it failed.
+async fn mixer(args: &[&str], env: &[(&str, &str)]) -> Result<String, String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_mixer"))
+        .args(args)
+        .env_clear()
+        .envs(env.iter().copied())
+        .current_dir(std::env::temp_dir())
+        .output()
+        .await
+        .unwrap();
+    if output.status.success() {
+        Ok(String::from_utf8(output.stdout).unwrap())
+    } else {
+        Err(String::from_utf8(output.stderr).unwrap())
+    }
+}
+
+fn sol_pool() -> PoolConfig {
+    PoolConfig {
+        id: "sol-0.1".to_string(),
+        denomination: Some(100_000_000),
+        ..PoolConfig::for_program(PROGRAM_ID)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn notes_and_trees_from_the_terminal() {
+    let details: Value = serde_json::from_str(
+        &mixer(&["note", "new", "--amount", "0.1"], &[])
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    let text = details["note"].as_str().unwrap();
+    let note = Note::parse(text).unwrap();
+    assert_eq!((note.mint, note.amount), (None, 100_000_000));
+    let (commitment, nullifier_hash) = note.hashes();
+    assert_eq!(details["commitment"], serde_json::json!(commitment));
+
+    // The note is read from `MIXER_NOTE` and its secrets are not printed.
+    let decoded = mixer(&["note", "decode"], &[("MIXER_NOTE", text)])
+        .await
+        .unwrap();
+    let decoded: Value = serde_json::from_str(&decoded).unwrap();
+    assert_eq!(decoded["amount"], "0.1");
+    assert_eq!(decoded["commitment"], to_hex32(&commitment));
+    assert_eq!(decoded["nullifier_hash"], to_hex32(&nullifier_hash));
+    assert!(!decoded
+        .to_string()
+        .contains(text.rsplit(':').next().unwrap()));
+    assert!(mixer(&["note", "decode", "--note", "solana-mixer-x"], &[])
+        .await
+        .is_err());
+
+    let leaves = vec![[1; 32], commitment, [3; 32]];
+    let rpc = spawn_mock_rpc(RpcFixture::with_deposits(&leaves)).await;
+    let env = [
+        ("MIXER_PROGRAM_ID", PROGRAM_ID),
+        ("SOLANA_RPC_URLS", rpc.as_str()),
+    ];
+    let hex = to_hex32(&commitment);
+    let served = mixer(&["tree", "proof", "--commitment", &hex], &env)
+        .await
+        .unwrap();
+    let served: MerklePathResponse = serde_json::from_str(&served).unwrap();
+    assert_eq!(served.path().unwrap(), MerklePath::new(&leaves, 1));
+    let missing = mixer(
+        &["tree", "proof", "--commitment", &to_hex32(&[9; 32])],
+        &env,
+    )
+    .await
+    .unwrap_err();
+    assert!(missing.contains("not found"), "{}", missing);
+
+    let out = std::env::temp_dir().join(format!(
+        "cli-{}.snapshot",
+        hex::encode(rand::random::<[u8; 8]>())
+    ));
+    let synced = mixer(&["tree", "sync", "--out", out.to_str().unwrap()], &env)
+        .await
+        .unwrap();
+    assert!(synced.contains("next_index:     3"), "{}", synced);
+    let inspected = mixer(&["tree", "inspect", out.to_str().unwrap()], &[])
+        .await
+        .unwrap();
+    assert!(inspected.contains(&to_hex32(&MerklePath::new(&leaves, 0).root)));
+    std::fs::remove_file(&out).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn remote_proving_seals_the_note() {
+    let note = Note::random(None, 100_000_000);
+    let (commitment, _) = note.hashes();
+    let index = Arc::new(DepositIndex::new(PROGRAM_ID.parse().unwrap()));
+    for (i, leaf) in [[1; 32], commitment].into_iter().enumerate() {
+        let logs = deposit_logs(leaf, i as u32);
+        index.apply_logs(&format!("sig-{}", i), 10, Finalized, &logs);
+    }
+    let app = spawn_app_with_state(AppState::new(
+        ServerConfig::default(),
+        EventDecoder::bundled(),
+        registry_with_index(sol_pool(), index),
+    ))
+    .await;
+
+    // The pool is picked by the note's denomination.
+    let remote = RemoteServer::new(&app);
+    let pools = remote.pools().await.unwrap();
+    let other = PoolConfig {
+        id: "sol-1".to_string(),
+        denomination: Some(1_000_000_000),
+        ..PoolConfig::for_program(PROGRAM_ID)
+    };
+    let both = [other, pools[0].clone()];
+    assert_eq!(
+        cli::select_pool(&both, None, Some(&note)).unwrap().id,
+        "sol-0.1"
+    );
+    assert!(cli::select_pool(&both, Some("sol-1"), Some(&note)).is_err());
+    assert!(cli::select_pool(&both, None, None).is_err());
+    let path = remote.merkle_path("sol-0.1", &commitment).await.unwrap();
+    assert_eq!(path, MerklePath::new(&[[1; 32], commitment], 1));
+
+    if MIXER_ELF.is_empty() {
+        eprintln!("skipping: mixer-program ELF was not built");
+        return;
+    }
+    let (recipient, relayer) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (recipient_arg, relayer_arg) = (recipient.to_string(), relayer.to_string());
+    let args = [
+        "prove",
+        "--recipient",
+        &recipient_arg,
+        "--relayer",
+        &relayer_arg,
+        "--backend",
+        "remote",
+        "--server",
+        &app,
+        "--dry-run",
+    ];
+    let text = note.encode();
+    let report = mixer(&args, &[("MIXER_NOTE", text.expose())])
+        .await
+        .unwrap();
+    let report: Value = serde_json::from_str(&report).unwrap();
+    assert_eq!(report["root"], to_hex32(&path.root));
+    assert_eq!(report["recipient"], to_hex32(&recipient.to_bytes()));
+    assert_eq!(report["relayer"], to_hex32(&relayer.to_bytes()));
+}
@@ -0,0 +1,175 @@
+//! Client-side proving: the server serves the Merkle path of a commitment, or
+//! a subtree to compute it from, and the client builds the program input from
+//! its note.
+mod common;
+
+use anchor_client::solana_sdk::commitment_config::CommitmentLevel::Finalized;
+use common::{deposit_logs, registry_with_index, spawn_app_with_state};
+use mixer_lib::mix::{Payout, PublicValues, WithdrawalInputs, NATIVE_MINT};
+use mixer_lib::tree::{
+    compute_merkle_proof, MerklePath, SubtreeSnapshot, SUBTREE_HEIGHT, TREE_DEPTH,
+};
+use mixer_script::idl::EventDecoder;
+use mixer_script::indexer::DepositIndex;
+use mixer_script::pools::PoolConfig;
+use mixer_script::prover::{withdrawal_stdin, MixerProver, ProverBackend};
+use mixer_script::server::{
+    AppState, MerklePathResponse, ServerConfig, SubtreeResponse, MIXER_ELF,
+};
+use mixer_script::utils::{biguint_to_32_le_bytes, to_hex32};
+use reqwest::StatusCode;
+use std::sync::Arc;
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+const PAYOUT: Payout = Payout {
+    recipient: [1; 32],
+    relayer: [2; 32],
+    fee: 0,
+    refund: 0,
+    mint: NATIVE_MINT,
+    amount: 100_000_000,
+};
+
+fn sol_pool() -> PoolConfig {
+    PoolConfig {
+        id: "sol-0.1".to_string(),
+        denomination: Some(100_000_000),
+        ..PoolConfig::for_program(PROGRAM_ID)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn merkle_path_builds_the_program_input() {
+    let mut leaves = Vec::new();
+    let mut notes = Vec::new();
+    let index = Arc::new(DepositIndex::new(PROGRAM_ID.parse().unwrap()));
+    for i in 0..3 {
+        let (nullifier, secret, _, commitment, _) = mixer_lib::utils::create_random_commitment();
+        index.apply_logs(
+            &format!("sig-{}", i),
+            10 + i as u64,
+            Finalized,
+            &deposit_logs(commitment, i),
+        );
+        leaves.push(commitment);
+        notes.push((
+            biguint_to_32_le_bytes(&nullifier),
+            biguint_to_32_le_bytes(&secret),
+        ));
+    }
+    let app = spawn_app_with_state(AppState::new(
+        ServerConfig::default(),
+        EventDecoder::bundled(),
+        registry_with_index(sol_pool(), index),
+    ))
+    .await;
+
+    let client = reqwest::Client::new();
+    let merkle_path = |commitment: String| {
+        client
+            .get(format!("{}/api/pools/sol-0.1/merkle-path", app))
+            .query(&[("commitment", commitment)])
+            .send()
+    };
+    let resp = merkle_path(to_hex32(&leaves[1])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let served: MerklePathResponse = resp.json().await.unwrap();
+    assert_eq!(served.leaf_index, 1);
+    let path = served.path().unwrap();
+    let (path_elements, path_indices, root) = compute_merkle_proof::<TREE_DEPTH>(&leaves, 1);
+    assert_eq!(path.root, root);
+    assert_eq!(path.path_elements, path_elements);
+    assert_eq!(path.path_indices, path_indices);
+    assert!(path.verify(leaves[1]));
+    assert!(!path.verify(leaves[0]));
+
+    let resp = merkle_path(to_hex32(&[9; 32])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = merkle_path("zz".to_string()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // Only the note at the path's leaf fits it.
+    let (nullifier, secret) = notes[0];
+    assert!(WithdrawalInputs::for_note(nullifier.into(), secret.into(), &path, PAYOUT).is_err());
+    let (nullifier, secret) = notes[1];
+    // Values above the field modulus are refused rather than panicking.
+    let err =
+        WithdrawalInputs::for_note([0xff; 32].into(), secret.into(), &path, PAYOUT).unwrap_err();
+    assert!(err.starts_with("hashing the note"), "{}", err);
+    let inputs =
+        WithdrawalInputs::for_note(nullifier.into(), secret.into(), &path, PAYOUT).unwrap();
+    assert_eq!(inputs.root, root);
+    assert_eq!(inputs.nullifier_hash, mixer_lib::utils::hash1(&nullifier));
+
+    if MIXER_ELF.is_empty() {
+        eprintln!("skipping: mixer-program ELF was not built");
+        return;
+    }
+    let stdin = withdrawal_stdin(&inputs);
+    let (public_values, _) = MixerProver::new(ProverBackend::Mock)
+        .unwrap()
+        .execute(MIXER_ELF, &stdin)
+        .unwrap();
+    assert_eq!(
+        PublicValues::decode(public_values.as_slice()),
+        Some(inputs.public_values())
+    );
+}
+
+#[test]
+fn subtree_snapshots_give_the_same_paths() {
+    let leaves: Vec<[u8; 32]> = (0..300u64)
+        .map(|i| mixer_lib::utils::hash1(&biguint_to_32_le_bytes(&i.into())))
+        .collect();
+    for leaf_index in [0, 255, 256, 299] {
+        let subtree = SubtreeSnapshot::index_of(leaf_index);
+        let snapshot = SubtreeSnapshot::new(&leaves, SUBTREE_HEIGHT, subtree).unwrap();
+        let path = snapshot.path(leaves[leaf_index as usize]).unwrap();
+        assert_eq!(path, MerklePath::new(&leaves, leaf_index as usize));
+    }
+    assert!(SubtreeSnapshot::new(&leaves, SUBTREE_HEIGHT, 2).is_none());
+
+    let mut snapshot = SubtreeSnapshot::new(&leaves, SUBTREE_HEIGHT, 1).unwrap();
+    assert_eq!(snapshot.leaves.len(), 44);
+    assert!(snapshot.path(leaves[3]).is_err());
+    snapshot.siblings[0] = leaves[0];
+    assert!(snapshot.path(leaves[260]).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn subtrees_are_served_from_the_index() {
+    let index = Arc::new(DepositIndex::new(PROGRAM_ID.parse().unwrap()));
+    let mut leaves = Vec::new();
+    for i in 0..260u32 {
+        let commitment = mixer_lib::utils::hash1(&biguint_to_32_le_bytes(&i.into()));
+        index.apply_logs(
+            &format!("sig-{}", i),
+            10,
+            Finalized,
+            &deposit_logs(commitment, i),
+        );
+        leaves.push(commitment);
+    }
+    let app = spawn_app_with_state(AppState::new(
+        ServerConfig::default(),
+        EventDecoder::bundled(),
+        registry_with_index(sol_pool(), index),
+    ))
+    .await;
+
+    let subtree = |index: u64| {
+        reqwest::Client::new()
+            .get(format!("{}/api/pools/sol-0.1/tree/subtree/{}", app, index))
+            .send()
+    };
+    let served: SubtreeResponse = subtree(1).await.unwrap().json().await.unwrap();
+    assert_eq!(served.height, SUBTREE_HEIGHT);
+    assert_eq!(served.next_index, 260);
+    assert_eq!(served.leaves.len(), 4);
+    let path = served.snapshot().unwrap().path(leaves[258]).unwrap();
+    assert_eq!(path, MerklePath::new(&leaves, 258));
+
+    let resp = subtree(2).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
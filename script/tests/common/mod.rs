@@ -0,0 +1,446 @@
+//! Offline test harness: a stand-in Solana JSON-RPC server replaying canned
+//! deposit history, and a helper that serves the proof API on a local port.
+#![allow(dead_code)]
+
+use anchor_client::solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use anchor_client::solana_sdk::{
+    hash::Hash, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+};
+use axum::{
+    extract::{
+        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::Engine;
+use mixer_script::idl::{event_discriminator, DEPOSIT_EVENT, WITHDRAWAL_EVENT};
+use mixer_script::indexer::DepositIndex;
+use mixer_script::pools::{PoolConfig, PoolRegistry};
+use mixer_script::server::{self, AppState, ServerConfig};
+use mixer_script::source::FixtureSource;
+use serde_json::{json, Value};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction,
+    EncodedTransactionWithStatusMeta, TransactionConfirmationStatus, TransactionStatusMeta,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+/// One program transaction: its signature, slot and log lines.
+#[derive(Clone)]
+pub struct FixtureTx {
+    pub signature: Signature,
+    pub slot: u64,
+    pub logs: Vec<String>,
+}
+
+/// Canned history for one program, oldest transaction first.
+#[derive(Clone, Default)]
+pub struct RpcFixture {
+    pub txs: Vec<FixtureTx>,
+    /// Account data served by `getAccountInfo`.
+    pub accounts: HashMap<Pubkey, Vec<u8>>,
+    /// Transactions received by `sendTransaction`, reported finalized.
+    pub sent: Vec<Transaction>,
+    /// Served by `getRecentPrioritizationFees`, one per slot.
+    pub priority_fees: Vec<u64>,
+}
+
+impl RpcFixture {
+    /// A history with one deposit transaction per commitment, in leaf order.
+    pub fn with_deposits(commitments: &[[u8; 32]]) -> Self {
+        let mut fixture = RpcFixture::default();
+        for c in commitments {
+            fixture.push_deposit(*c);
+        }
+        fixture
+    }
+
+    pub fn push_deposit(&mut self, commitment: [u8; 32]) {
+        let leaf_index = self.txs.len() as u32;
+        self.txs.push(FixtureTx {
+            signature: Signature::new_unique(),
+            slot: 100 + leaf_index as u64,
+            logs: deposit_logs(commitment, leaf_index),
+        });
+    }
+
+    /// The same history as an in-process [`FixtureSource`].
+    pub fn to_source(&self) -> FixtureSource {
+        let mut source = FixtureSource::new();
+        for tx in &self.txs {
+            source.push(&tx.signature.to_string(), tx.slot, tx.logs.clone());
+        }
+        source
+    }
+
+    fn get_signatures_for_address(&self, until: Option<&str>) -> Value {
+        // Newest first, like a real node.
+        let sigs: Vec<_> = self
+            .txs
+            .iter()
+            .rev()
+            .take_while(|tx| Some(tx.signature.to_string().as_str()) != until)
+            .map(|tx| RpcConfirmedTransactionStatusWithSignature {
+                signature: tx.signature.to_string(),
+                slot: tx.slot,
+                err: None,
+                memo: None,
+                block_time: None,
+                confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+            })
+            .collect();
+        json!(sigs)
+    }
+
+    fn get_account_info(&self, address: &str) -> Value {
+        let data = Pubkey::from_str(address)
+            .ok()
+            .and_then(|a| self.accounts.get(&a));
+        let value = data.map(|data| {
+            json!({
+                "data": [base64::engine::general_purpose::STANDARD.encode(data), "base64"],
+                "executable": false,
+                "lamports": 1_000_000,
+                "owner": Pubkey::default().to_string(),
+                "rentEpoch": 0,
+                "space": data.len(),
+            })
+        });
+        json!({ "context": { "slot": 1 }, "value": value })
+    }
+
+    fn send_transaction(&mut self, encoded: &str) -> Value {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        let tx: Transaction = bincode::deserialize(&bytes).unwrap();
+        let signature = tx.signatures[0].to_string();
+        self.sent.push(tx);
+        json!(signature)
+    }
+
+    fn get_signature_statuses(&self, signatures: &Value) -> Value {
+        let statuses: Vec<Value> = signatures
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|sig| {
+                let sent = self
+                    .sent
+                    .iter()
+                    .position(|tx| Some(tx.signatures[0].to_string().as_str()) == sig.as_str());
+                match sent {
+                    Some(i) => json!({
+                        "slot": 500 + i as u64,
+                        "confirmations": null,
+                        "err": null,
+                        "status": { "Ok": null },
+                        "confirmationStatus": "finalized",
+                    }),
+                    None => Value::Null,
+                }
+            })
+            .collect();
+        json!({ "context": { "slot": 1 }, "value": statuses })
+    }
+
+    fn get_transaction(&self, signature: &str) -> Value {
+        let Some(tx) = self
+            .txs
+            .iter()
+            .find(|tx| tx.signature.to_string() == signature)
+        else {
+            return Value::Null;
+        };
+        let meta = TransactionStatusMeta {
+            log_messages: Some(tx.logs.clone()),
+            ..Default::default()
+        };
+        json!(EncodedConfirmedTransactionWithStatusMeta {
+            slot: tx.slot,
+            transaction: EncodedTransactionWithStatusMeta {
+                transaction: EncodedTransaction::LegacyBinary(String::new()),
+                meta: Some(meta.into()),
+                version: None,
+            },
+            block_time: None,
+        })
+    }
+}
+
+/// Logs of a deposit transaction emitting one `DepositEvent`.
+pub fn deposit_logs(commitment: [u8; 32], leaf_index: u32) -> Vec<String> {
+    let mut data = event_discriminator(DEPOSIT_EVENT).to_vec();
+    data.extend_from_slice(&commitment);
+    data.extend_from_slice(&leaf_index.to_le_bytes());
+    data.extend_from_slice(&Pubkey::new_unique().to_bytes());
+    vec![
+        "Program log: Instruction: Deposit".to_string(),
+        format!(
+            "Program data: {}",
+            base64::engine::general_purpose::STANDARD.encode(&data)
+        ),
+    ]
+}
+
+/// Logs of a withdrawal transaction emitting one `WithdrawalEvent`.
+pub fn withdrawal_logs(nullifier_hash: [u8; 32], recipient: Pubkey, fee: u64) -> Vec<String> {
+    let mut data = event_discriminator(WITHDRAWAL_EVENT).to_vec();
+    data.extend_from_slice(&nullifier_hash);
+    data.extend_from_slice(&recipient.to_bytes());
+    data.extend_from_slice(&Pubkey::new_unique().to_bytes());
+    data.extend_from_slice(&fee.to_le_bytes());
+    vec![
+        "Program log: Instruction: Withdraw".to_string(),
+        format!(
+            "Program data: {}",
+            base64::engine::general_purpose::STANDARD.encode(&data)
+        ),
+    ]
+}
+
+struct MockRpc {
+    fixture: Mutex<RpcFixture>,
+    /// Requests still to be answered with `503` before serving the fixture.
+    failures: Mutex<usize>,
+    requests: AtomicUsize,
+}
+
+async fn handle_rpc(State(rpc): State<Arc<MockRpc>>, Json(req): Json<Value>) -> Response {
+    rpc.requests.fetch_add(1, Ordering::SeqCst);
+    {
+        let mut failures = rpc.failures.lock().unwrap();
+        if *failures > 0 {
+            *failures -= 1;
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+    }
+    let mut fixture = rpc.fixture.lock().unwrap();
+    let id = req["id"].clone();
+    let result = match req["method"].as_str() {
+        Some("getLatestBlockhash") => json!({
+            "context": { "slot": 1 },
+            "value": { "blockhash": Hash::new_unique().to_string(), "lastValidBlockHeight": 1000 },
+        }),
+        Some("sendTransaction") => {
+            fixture.send_transaction(req["params"][0].as_str().unwrap_or(""))
+        }
+        Some("getSignatureStatuses") => fixture.get_signature_statuses(&req["params"][0]),
+        Some("getRecentPrioritizationFees") => json!(fixture
+            .priority_fees
+            .iter()
+            .enumerate()
+            .map(|(i, fee)| json!({ "slot": 100 + i, "prioritizationFee": fee }))
+            .collect::<Vec<_>>()),
+        Some("getVersion") => json!({ "solana-core": "2.2.7", "feature-set": 0 }),
+        Some("getSignaturesForAddress") => {
+            fixture.get_signatures_for_address(req["params"][1]["until"].as_str())
+        }
+        Some("getTransaction") => fixture.get_transaction(req["params"][0].as_str().unwrap_or("")),
+        Some("getAccountInfo") => fixture.get_account_info(req["params"][0].as_str().unwrap_or("")),
+        other => {
+            return Json(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("method not found: {:?}", other) },
+            }))
+            .into_response()
+        }
+    };
+    Json(json!({ "jsonrpc": "2.0", "id": id, "result": result })).into_response()
+}
+
+/// Handle to a running mock RPC server.
+pub struct MockRpcHandle {
+    pub url: String,
+    state: Arc<MockRpc>,
+}
+
+impl MockRpcHandle {
+    /// Number of requests received so far, failed ones included.
+    pub fn requests(&self) -> usize {
+        self.state.requests.load(Ordering::SeqCst)
+    }
+
+    /// Transactions sent to the endpoint so far.
+    pub fn sent(&self) -> Vec<Transaction> {
+        self.state.fixture.lock().unwrap().sent.clone()
+    }
+
+    /// Adds a deposit to the served history and returns its transaction.
+    pub fn push_deposit(&self, commitment: [u8; 32]) -> FixtureTx {
+        let mut fixture = self.state.fixture.lock().unwrap();
+        fixture.push_deposit(commitment);
+        fixture.txs.last().unwrap().clone()
+    }
+}
+
+/// Serves `fixture` as a JSON-RPC endpoint and returns its URL.
+pub async fn spawn_mock_rpc(fixture: RpcFixture) -> String {
+    spawn_flaky_mock_rpc(fixture, 0).await.url
+}
+
+/// Like [`spawn_mock_rpc`], but the first `failures` requests get a `503`.
+pub async fn spawn_flaky_mock_rpc(fixture: RpcFixture, failures: usize) -> MockRpcHandle {
+    let state = Arc::new(MockRpc {
+        fixture: Mutex::new(fixture),
+        failures: Mutex::new(failures),
+        requests: AtomicUsize::new(0),
+    });
+    let app = Router::new()
+        .route("/", post(handle_rpc))
+        .with_state(state.clone());
+    MockRpcHandle {
+        url: format!("http://{}", serve(app).await),
+        state,
+    }
+}
+
+/// A URL nothing listens on.
+pub async fn dead_rpc_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    format!("http://{}", addr)
+}
+
+/// Serves the proof API and returns its base URL (`http://127.0.0.1:<port>`).
+pub async fn spawn_app(config: ServerConfig) -> String {
+    format!("http://{}", serve(server::router(config)).await)
+}
+
+/// A registry holding `pool`, served from `index`.
+pub fn registry_with_index(pool: PoolConfig, index: Arc<DepositIndex>) -> Arc<PoolRegistry> {
+    let mut pools = PoolRegistry::new(vec![pool]).unwrap();
+    pools.iter_mut().next().unwrap().index = Some(index);
+    Arc::new(pools)
+}
+
+/// Like [`spawn_app`], with a prepared [`AppState`] (e.g. a live index).
+pub async fn spawn_app_with_state(state: AppState) -> String {
+    format!("http://{}", serve(server::app(state)).await)
+}
+
+async fn serve(app: Router) -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        )
+        .await
+        .unwrap();
+    });
+    addr
+}
+
+#[derive(Clone, Debug)]
+enum PubsubEvent {
+    Notify(Value),
+    Disconnect,
+}
+
+/// Handle to a running mock PubSub server that speaks `logsSubscribe`.
+pub struct MockPubsub {
+    pub url: String,
+    events: broadcast::Sender<PubsubEvent>,
+    subscriptions: Arc<AtomicUsize>,
+}
+
+impl MockPubsub {
+    /// Number of `logsSubscribe` calls served so far.
+    pub fn subscriptions(&self) -> usize {
+        self.subscriptions.load(Ordering::SeqCst)
+    }
+
+    /// Pushes a `logsNotification` for `tx` to every subscriber.
+    pub fn notify(&self, tx: &FixtureTx) {
+        let _ = self.events.send(PubsubEvent::Notify(json!({
+            "context": { "slot": tx.slot },
+            "value": { "signature": tx.signature.to_string(), "err": null, "logs": tx.logs },
+        })));
+    }
+
+    /// Drops every open connection.
+    pub fn disconnect(&self) {
+        let _ = self.events.send(PubsubEvent::Disconnect);
+    }
+}
+
+pub async fn spawn_mock_pubsub() -> MockPubsub {
+    let (events, _) = broadcast::channel(64);
+    let subscriptions = Arc::new(AtomicUsize::new(0));
+    let state = (events.clone(), subscriptions.clone());
+    let app = Router::new()
+        .route(
+            "/",
+            get(
+                |State((events, subs)): State<(
+                    broadcast::Sender<PubsubEvent>,
+                    Arc<AtomicUsize>,
+                )>,
+                 ws: WebSocketUpgrade| async move {
+                    ws.on_upgrade(move |socket| handle_pubsub(socket, events.subscribe(), subs))
+                },
+            ),
+        )
+        .with_state(state);
+    MockPubsub {
+        url: format!("ws://{}", serve(app).await),
+        events,
+        subscriptions,
+    }
+}
+
+async fn handle_pubsub(
+    mut socket: WebSocket,
+    mut events: broadcast::Receiver<PubsubEvent>,
+    subscriptions: Arc<AtomicUsize>,
+) {
+    const SUBSCRIPTION_ID: u64 = 1;
+    loop {
+        tokio::select! {
+            msg = socket.recv() => {
+                let Some(Ok(WsMessage::Text(txt))) = msg else { return };
+                let req: Value = serde_json::from_str(&txt).unwrap();
+                let result = match req["method"].as_str() {
+                    Some("logsSubscribe") => {
+                        subscriptions.fetch_add(1, Ordering::SeqCst);
+                        json!(SUBSCRIPTION_ID)
+                    }
+                    _ => json!(true),
+                };
+                let reply = json!({ "jsonrpc": "2.0", "result": result, "id": req["id"] });
+                if socket.send(WsMessage::Text(reply.to_string())).await.is_err() {
+                    return;
+                }
+            }
+            event = events.recv() => match event {
+                Ok(PubsubEvent::Notify(result)) => {
+                    let msg = json!({
+                        "jsonrpc": "2.0",
+                        "method": "logsNotification",
+                        "params": { "result": result, "subscription": SUBSCRIPTION_ID },
+                    });
+                    if socket.send(WsMessage::Text(msg.to_string())).await.is_err() {
+                        return;
+                    }
+                }
+                _ => {
+                    let _ = socket.close().await;
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Tests of the deposit history sources: fixture-backed scanning and the
+//! RPC source's retries, failover and rate limiting.
+mod common;
+
+use anchor_client::solana_sdk::commitment_config::CommitmentLevel::Finalized;
+use common::{dead_rpc_url, deposit_logs, spawn_flaky_mock_rpc, RpcFixture};
+use mixer_script::idl::EventDecoder;
+use mixer_script::source::{FixtureSource, RetryPolicy, RpcDepositSource};
+use mixer_script::utils::fetch_deposits;
+use std::time::{Duration, Instant};
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+fn commitments(n: u8) -> Vec<[u8; 32]> {
+    (1..=n).map(|i| [i; 32]).collect()
+}
+
+fn fast_retry(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(5),
+    }
+}
+
+#[tokio::test]
+async fn fixture_source_orders_leaves_by_index() {
+    // Deposits land out of order; the scan must sort them by leaf index.
+    let mut source = FixtureSource::new();
+    source.push("sig-b", 11, deposit_logs([2; 32], 1));
+    source.push("sig-a", 10, deposit_logs([1; 32], 0));
+    source.push("sig-c", 12, deposit_logs([3; 32], 2));
+
+    let (leaves, _, indices, leaf_index, found) = fetch_deposits(
+        &source,
+        &EventDecoder::bundled(),
+        [2; 32],
+        PROGRAM_ID,
+        Finalized,
+    )
+    .await
+    .unwrap();
+    assert_eq!(leaves, commitments(3));
+    assert_eq!(indices, vec![0, 1, 2]);
+    assert!(found);
+    assert_eq!(leaf_index, 1);
+}
+
+#[tokio::test]
+async fn fixture_source_reports_missing_commitment() {
+    let source = RpcFixture::with_deposits(&commitments(2)).to_source();
+    let (leaves, .., found) = fetch_deposits(
+        &source,
+        &EventDecoder::bundled(),
+        [9; 32],
+        PROGRAM_ID,
+        Finalized,
+    )
+    .await
+    .unwrap();
+    assert_eq!(leaves.len(), 2);
+    assert!(!found);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rpc_source_retries_transient_errors() {
+    let rpc = spawn_flaky_mock_rpc(RpcFixture::with_deposits(&commitments(3)), 2).await;
+    let source = RpcDepositSource::new(vec![rpc.url.clone()])
+        .unwrap()
+        .with_retry(fast_retry(3));
+
+    let (leaves, .., found) = fetch_deposits(
+        &source,
+        &EventDecoder::bundled(),
+        [3; 32],
+        PROGRAM_ID,
+        Finalized,
+    )
+    .await
+    .unwrap();
+    assert_eq!(leaves, commitments(3));
+    assert!(found);
+    // Two failures, one page of signatures and three transactions.
+    assert_eq!(rpc.requests(), 6);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rpc_source_gives_up_after_max_attempts() {
+    let rpc = spawn_flaky_mock_rpc(RpcFixture::with_deposits(&commitments(1)), 10).await;
+    let source = RpcDepositSource::new(vec![rpc.url.clone()])
+        .unwrap()
+        .with_retry(fast_retry(3));
+
+    let err = fetch_deposits(
+        &source,
+        &EventDecoder::bundled(),
+        [1; 32],
+        PROGRAM_ID,
+        Finalized,
+    )
+    .await
+    .unwrap_err();
+    assert!(err.contains("getSignaturesForAddress"), "{}", err);
+    assert_eq!(rpc.requests(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rpc_source_fails_over_to_next_url() {
+    let rpc = spawn_flaky_mock_rpc(RpcFixture::with_deposits(&commitments(2)), 0).await;
+    let urls = format!("{},{}", dead_rpc_url().await, rpc.url);
+    let source = RpcDepositSource::from_url_list(&urls)
+        .unwrap()
+        .with_retry(fast_retry(1));
+
+    let (leaves, .., found) = fetch_deposits(
+        &source,
+        &EventDecoder::bundled(),
+        [1; 32],
+        PROGRAM_ID,
+        Finalized,
+    )
+    .await
+    .unwrap();
+    assert_eq!(leaves, commitments(2));
+    assert!(found);
+    // After the first failover every call goes straight to the healthy node.
+    assert_eq!(rpc.requests(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rpc_source_rate_limits_requests() {
+    let rpc = spawn_flaky_mock_rpc(RpcFixture::with_deposits(&commitments(3)), 0).await;
+    let source = RpcDepositSource::new(vec![rpc.url.clone()])
+        .unwrap()
+        .with_rate_limit(20);
+
+    let start = Instant::now();
+    fetch_deposits(
+        &source,
+        &EventDecoder::bundled(),
+        [1; 32],
+        PROGRAM_ID,
+        Finalized,
+    )
+    .await
+    .unwrap();
+    // Four requests at 20/s need at least three 50ms gaps.
+    assert!(start.elapsed() >= Duration::from_millis(150));
+    assert_eq!(rpc.requests(), 4);
+}
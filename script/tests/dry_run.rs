@@ -0,0 +1,98 @@
+//! Tests of the execute-only dry run of `mixer-program`.
+mod common;
+
+use common::spawn_app;
+use mixer_lib::tree::{compute_merkle_proof, TREE_DEPTH};
+use mixer_script::prover::ProverBackend;
+use mixer_script::server::{DryRunResponse, ProveRequest, ServerConfig, MIXER_ELF};
+use mixer_script::utils::{biguint_to_32_le_bytes, to_hex32, to_hex_vec};
+use reqwest::StatusCode;
+
+/// A valid request withdrawing the second of three synthetic deposits.
+fn withdrawal_request() -> ProveRequest {
+    let mut leaves = Vec::new();
+    let mut note = None;
+    for i in 0..3 {
+        let (nullifier, secret, _, commitment, nullifier_hash) =
+            mixer_lib::utils::create_random_commitment();
+        leaves.push(commitment);
+        if i == 1 {
+            note = Some((nullifier, secret, nullifier_hash));
+        }
+    }
+    let (nullifier, secret, nullifier_hash) = note.unwrap();
+    let (path_elements, path_indices, root) = compute_merkle_proof::<TREE_DEPTH>(&leaves, 1);
+
+    ProveRequest {
+        root: to_hex32(&root),
+        nullifier_hash: to_hex32(&nullifier_hash),
+        recipient: to_hex32(&[1; 32]),
+        relayer: to_hex32(&[2; 32]),
+        fee: 0,
+        refund: 0,
+        mint: None,
+        amount: 100_000_000,
+        nullifier: to_hex32(&biguint_to_32_le_bytes(&nullifier)).into(),
+        secret: to_hex32(&biguint_to_32_le_bytes(&secret)).into(),
+        sealed: None,
+        path_elements: to_hex_vec(&path_elements),
+        path_indices: path_indices.to_vec(),
+        format: Default::default(),
+        dry_run: false,
+    }
+}
+
+async fn dry_run(req: &ProveRequest) -> reqwest::Response {
+    let app = spawn_app(ServerConfig {
+        prover: ProverBackend::Network,
+        ..Default::default()
+    })
+    .await;
+    reqwest::Client::new()
+        .post(format!("{}/api/prove-mix/dry-run", app))
+        .json(req)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn dry_run_rejects_short_path() {
+    let mut req = withdrawal_request();
+    req.path_elements.truncate(3);
+
+    let resp = dry_run(&req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn dry_run_reports_cycles_and_public_values() {
+    if MIXER_ELF.is_empty() {
+        eprintln!("skipping: mixer-program ELF was not built");
+        return;
+    }
+    let req = withdrawal_request();
+
+    let resp = dry_run(&req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: DryRunResponse = resp.json().await.unwrap();
+    assert!(report.cycles > 0);
+    assert_eq!(report.root, req.root);
+    assert_eq!(report.nullifier_hash, req.nullifier_hash);
+    assert_eq!(report.recipient, req.recipient);
+    assert_eq!(report.mint, to_hex32(&[0; 32]));
+    assert_eq!(report.amount, req.amount);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn dry_run_catches_wrong_root() {
+    if MIXER_ELF.is_empty() {
+        eprintln!("skipping: mixer-program ELF was not built");
+        return;
+    }
+    let mut req = withdrawal_request();
+    req.root = to_hex32(&[7; 32]);
+
+    let resp = dry_run(&req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
@@ -0,0 +1,157 @@
+//! End-to-end tests of the deposit → withdrawal flow against a mocked Solana
+//! RPC and the SP1 mock prover. No network access or prover credits needed.
+mod common;
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use common::{spawn_app, spawn_mock_rpc, RpcFixture};
+use futures_util::{SinkExt, StreamExt};
+use mixer_lib::mix::PublicValues;
+use mixer_lib::tree::{compute_merkle_proof, TREE_DEPTH};
+use mixer_script::prover::ProverBackend;
+use mixer_script::server::{ServerConfig, MIXER_ELF};
+use num_bigint::BigUint;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+fn mock_config() -> ServerConfig {
+    ServerConfig {
+        prover: ProverBackend::Mock,
+        ..Default::default()
+    }
+}
+
+fn random_commitment() -> [u8; 32] {
+    mixer_lib::utils::create_random_commitment().3
+}
+
+struct Note {
+    note: String,
+    commitment: [u8; 32],
+    nullifier: String, // decimal
+    secret: String,    // decimal
+}
+
+async fn post(app: &str, path: &str, body: Value) -> Value {
+    let resp = reqwest::Client::new()
+        .post(format!("{}{}", app, path))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success(), "{} -> {}", path, resp.status());
+    resp.json().await.unwrap()
+}
+
+/// Creates a note through the API the way the frontend does.
+async fn new_note(app: &str) -> Note {
+    let details = post(
+        app,
+        "/api/generate-deposit-details",
+        json!({ "amount": 0.1 }),
+    )
+    .await;
+    let note = details["note"].as_str().unwrap().to_string();
+    let commitment: [u8; 32] = serde_json::from_value(details["commitment"].clone()).unwrap();
+
+    let decoded = post(
+        app,
+        "/api/decode-note-details",
+        json!({ "note": note, "program_pubkey": PROGRAM_ID }),
+    )
+    .await;
+    Note {
+        note,
+        commitment,
+        nullifier: decoded["nullifier_str"].as_str().unwrap().to_string(),
+        secret: decoded["secret_str"].as_str().unwrap().to_string(),
+    }
+}
+
+/// Sends one withdrawal request over the WebSocket and returns the reply.
+async fn compute_withdrawal(app: &str, rpc_url: &str, note: &Note, recipient: Pubkey) -> Value {
+    let ws_url = format!("{}/ws/compute_withdrawal", app.replacen("http", "ws", 1));
+    let (mut ws, _) = connect_async(ws_url).await.unwrap();
+    let req = json!({
+        "nullifier": note.nullifier,
+        "secret": note.secret,
+        "rpc_url": rpc_url,
+        "program_pubkey": PROGRAM_ID,
+        "new_withdrawal_recipient_address": recipient.to_string(),
+        "new_relayer_address": recipient.to_string(),
+        "server_url": app,
+    });
+    ws.send(Message::text(req.to_string())).await.unwrap();
+
+    loop {
+        match ws
+            .next()
+            .await
+            .expect("socket closed without a reply")
+            .unwrap()
+        {
+            Message::Text(txt) => return serde_json::from_str(&txt).unwrap(),
+            Message::Close(_) => panic!("socket closed without a reply"),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn note_commitment_matches_decoded_secrets() {
+    let app = spawn_app(mock_config()).await;
+    let note = new_note(&app).await;
+
+    assert!(note.note.starts_with("solana-mixer-sol-100000000-"));
+    let (commitment, _) = mixer_lib::utils::note_hashes(
+        &BigUint::from_str(&note.nullifier).unwrap(),
+        &BigUint::from_str(&note.secret).unwrap(),
+    );
+    assert_eq!(commitment, note.commitment);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn withdrawal_of_unknown_commitment_is_rejected() {
+    let app = spawn_app(mock_config()).await;
+    let note = new_note(&app).await;
+    let rpc = spawn_mock_rpc(RpcFixture::with_deposits(&[
+        random_commitment(),
+        random_commitment(),
+    ]))
+    .await;
+
+    let reply = compute_withdrawal(&app, &rpc, &note, Pubkey::new_unique()).await;
+    assert_eq!(reply["error"], "Commitment not found in on-chain history");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn deposit_to_withdrawal_with_mock_prover() {
+    if MIXER_ELF.is_empty() {
+        eprintln!("skipping: mixer-program ELF was not built");
+        return;
+    }
+    let app = spawn_app(mock_config()).await;
+    let note = new_note(&app).await;
+
+    let leaves = vec![random_commitment(), note.commitment, random_commitment()];
+    let rpc = spawn_mock_rpc(RpcFixture::with_deposits(&leaves)).await;
+    let recipient = Pubkey::new_unique();
+
+    let reply = compute_withdrawal(&app, &rpc, &note, recipient).await;
+    assert!(reply.get("error").is_none(), "{}", reply);
+
+    let public_values: Vec<u8> = serde_json::from_value(reply["public_inputs"].clone()).unwrap();
+    let public_values = PublicValues::decode(&public_values).expect("public values layout");
+
+    let (_, _, root) = compute_merkle_proof::<TREE_DEPTH>(&leaves, 1);
+    let (_, nullifier_hash) = mixer_lib::utils::note_hashes(
+        &BigUint::from_str(&note.nullifier).unwrap(),
+        &BigUint::from_str(&note.secret).unwrap(),
+    );
+    assert_eq!(public_values.root, root);
+    assert_eq!(public_values.nullifier_hash, nullifier_hash);
+    assert_eq!(public_values.recipient, recipient.to_bytes());
+    assert_eq!(public_values.fee, 0);
+}
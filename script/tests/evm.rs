@@ -0,0 +1,84 @@
+//! The `evm` fixture generator, run as a binary with the mock prover.
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use mixer_lib::mix::{PublicValues, NATIVE_MINT};
+use mixer_script::prover::{MixerProver, ProverBackend};
+use serde_json::Value;
+use sp1_sdk::{include_elf, HashableKey};
+use std::process::Command;
+
+/// The program the `evm` binary proves.
+const MIXER_ELF: &[u8] = include_elf!("mixer-program");
+
+fn unhex(value: &Value) -> Vec<u8> {
+    hex::decode(value.as_str().unwrap().trim_start_matches("0x")).unwrap()
+}
+
+#[test]
+fn fixtures_carry_the_proved_public_values() {
+    if MIXER_ELF.is_empty() {
+        eprintln!("skipping: mixer-program ELF was not built");
+        return;
+    }
+    let out = std::env::temp_dir().join(format!("evm-fixtures-{}", std::process::id()));
+    let (recipient, relayer) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let output = Command::new(env!("CARGO_BIN_EXE_evm"))
+        .args(["--prover", "mock", "--deposits", "4", "--leaf-index", "2"])
+        .args(["--recipient", &recipient.to_string()])
+        .args(["--relayer", &relayer.to_string()])
+        .args(["--fee", "5000", "--amount", "100000000"])
+        .arg("--out-dir")
+        .arg(&out)
+        .current_dir(std::env::temp_dir())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let read = |name: &str| -> Value {
+        serde_json::from_slice(&std::fs::read(out.join(name)).unwrap()).unwrap()
+    };
+    let (evm, solana) = (read("evm-fixture.json"), read("solana-fixture.json"));
+    std::fs::remove_dir_all(&out).unwrap();
+
+    let (_, vk) = MixerProver::new(ProverBackend::Mock)
+        .unwrap()
+        .setup(MIXER_ELF);
+    let public_values = PublicValues::decode(&unhex(&evm["publicValues"])).unwrap();
+    assert_eq!(
+        public_values,
+        PublicValues {
+            recipient: recipient.to_bytes(),
+            relayer: relayer.to_bytes(),
+            fee: 5000,
+            refund: 0,
+            mint: NATIVE_MINT,
+            amount: 100_000_000,
+            ..public_values
+        }
+    );
+    for fixture in [&evm, &solana] {
+        assert_eq!(fixture["vkey"], vk.bytes32());
+        assert_eq!(fixture["publicValues"], evm["publicValues"]);
+        // Mock proofs carry no Groth16 points.
+        assert!(unhex(&fixture["proof"]).is_empty());
+        assert_eq!(unhex(&fixture["root"]), public_values.root);
+        assert_eq!(
+            unhex(&fixture["nullifierHash"]),
+            public_values.nullifier_hash
+        );
+        assert_eq!(fixture["fee"], public_values.fee);
+        assert_eq!(fixture["amount"], public_values.amount);
+    }
+    assert_eq!(unhex(&evm["recipient"]), recipient.to_bytes());
+    assert_eq!(unhex(&evm["mint"]), NATIVE_MINT);
+    assert_eq!(solana["recipient"], recipient.to_string());
+    assert_eq!(solana["relayer"], relayer.to_string());
+    assert_eq!(
+        (&solana["solana"], &solana["mint"]),
+        (&Value::Null, &Value::Null)
+    );
+    assert_eq!(solana["leafIndex"], 2);
+}
@@ -0,0 +1,451 @@
+//! Relayer fee quotes: pricing, signing and their use by relayed withdrawals.
+mod common;
+
+use anchor_client::solana_sdk::{
+    commitment_config::CommitmentLevel::Finalized,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+};
+use axum::{routing::post, Json, Router};
+use common::{
+    deposit_logs, registry_with_index, spawn_app_with_state, spawn_flaky_mock_rpc, spawn_mock_rpc,
+    RpcFixture,
+};
+use futures_util::{SinkExt, StreamExt};
+use mixer_lib::mix::{PublicValues, NATIVE_MINT};
+use mixer_script::idl::EventDecoder;
+use mixer_script::indexer::DepositIndex;
+use mixer_script::note::Note;
+use mixer_script::pools::{PoolConfig, PoolRegistry};
+use mixer_script::quote::{
+    current_priority_fee, priority_fee_estimate, FeeBreakdown, FeeQuote, FeeSchedule, QuoteBook,
+    QuoteRequest, LAMPORTS_PER_SIGNATURE,
+};
+use mixer_script::relayer::{Relayer, WITHDRAW_COMPUTE_UNITS};
+use mixer_script::server::{AppState, ProveRequest, ServerConfig};
+use mixer_script::solana::SolanaProof;
+use mixer_script::source::FixtureSource;
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+const TENTH_SOL: u64 = 100_000_000;
+
+const SCHEDULE: FeeSchedule = FeeSchedule {
+    rate_bps: 30,
+    prover_cost: 1_000_000,
+};
+
+fn request(compute_unit_price: u64) -> QuoteRequest<'static> {
+    QuoteRequest {
+        pool_id: "sol-0.1",
+        program_id: PROGRAM_ID,
+        amount: TENTH_SOL,
+        sol: true,
+        compute_unit_price,
+    }
+}
+
+#[test]
+fn fees_add_rate_prover_and_network_costs() {
+    let fee = SCHEDULE.fee(TENTH_SOL, 1000, true);
+    assert_eq!(
+        fee,
+        FeeBreakdown {
+            rate: 300_000,
+            prover: 1_000_000,
+            network: LAMPORTS_PER_SIGNATURE + WITHDRAW_COMPUTE_UNITS as u64 / 1000,
+        }
+    );
+    // Token pools pay the rate alone.
+    assert_eq!(
+        SCHEDULE.fee(100_000_000, 1000, false),
+        FeeBreakdown {
+            rate: 300_000,
+            ..Default::default()
+        }
+    );
+
+    assert_eq!(priority_fee_estimate(&[]), 0);
+    assert_eq!(priority_fee_estimate(&[0, 50, 10, 20, 5000]), 50);
+}
+
+#[tokio::test]
+async fn priority_fee_comes_from_the_source() {
+    let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+    let source = FixtureSource::new().with_priority_fees(vec![0, 100, 2000, 1000, 1000]);
+    assert_eq!(
+        current_priority_fee(&source, &program_id).await.unwrap(),
+        1000
+    );
+    assert_eq!(
+        current_priority_fee(&FixtureSource::new(), &program_id)
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[test]
+fn quotes_are_signed_and_expire() {
+    let relayer = Relayer::new(Keypair::new());
+    let book = QuoteBook::new(SCHEDULE, Duration::from_secs(60));
+    let quote = book.issue(&relayer, request(1000)).unwrap();
+
+    assert_eq!(quote.relayer, relayer.pubkey().to_string());
+    assert_eq!(quote.fee, 300_000 + 1_000_000 + 5400);
+    assert_eq!(quote.net_amount, TENTH_SOL - quote.fee);
+    let signature = Signature::from_str(&quote.signature).unwrap();
+    assert!(signature.verify(&relayer.pubkey().to_bytes(), &quote.message()));
+    let tampered = FeeQuote {
+        fee: 1,
+        ..quote.clone()
+    };
+    assert!(!signature.verify(&relayer.pubkey().to_bytes(), &tampered.message()));
+    assert_eq!(book.get(&quote.id).unwrap(), quote);
+
+    quote.verify().unwrap();
+    assert!(tampered.verify().unwrap_err().contains("not signed"));
+    let resigned = FeeQuote {
+        relayer: Pubkey::new_unique().to_string(),
+        ..quote.clone()
+    };
+    assert!(resigned.verify().is_err());
+
+    let expiring = QuoteBook::new(SCHEDULE, Duration::ZERO);
+    let quote = expiring.issue(&relayer, request(0)).unwrap();
+    assert!(expiring.get(&quote.id).unwrap_err().contains("expired"));
+    assert!(quote.verify().unwrap_err().contains("expired"));
+    assert!(book.get("nope").is_err());
+
+    // A fee larger than the withdrawal is not quoted.
+    let greedy = QuoteBook::new(
+        FeeSchedule {
+            rate_bps: 10_000,
+            prover_cost: 0,
+        },
+        Duration::from_secs(60),
+    );
+    assert!(greedy.issue(&relayer, request(0)).is_err());
+}
+
+#[test]
+fn quote_books_are_bounded_and_rate_limited() {
+    let relayer = Relayer::new(Keypair::new());
+    let book = QuoteBook::new(SCHEDULE, Duration::from_secs(60)).with_capacity(2);
+    let first = book.issue(&relayer, request(0)).unwrap();
+    std::thread::sleep(Duration::from_millis(1100));
+    let second = book.issue(&relayer, request(0)).unwrap();
+    let third = book.issue(&relayer, request(0)).unwrap();
+    assert_eq!(book.len(), 2);
+    // The oldest quote made room; it still verifies when sent in full.
+    assert!(book.get(&first.id).unwrap_err().contains("unknown"));
+    assert!(book.get(&second.id).is_ok() && book.get(&third.id).is_ok());
+    first.verify().unwrap();
+
+    let book = QuoteBook::new(SCHEDULE, Duration::from_secs(60)).with_rate_limit(2);
+    let (alice, bob) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+    book.admit(alice).unwrap();
+    book.admit(alice).unwrap();
+    assert!(book.admit(alice).unwrap_err().contains("retry later"));
+    book.admit(bob).unwrap();
+}
+
+async fn spawn_relaying_app(rpc_url: &str, relayer: Option<Relayer>) -> String {
+    let pool = PoolConfig {
+        id: "sol-0.1".to_string(),
+        cluster: Some(rpc_url.to_string()),
+        denomination: Some(TENTH_SOL),
+        ..PoolConfig::for_program(PROGRAM_ID)
+    };
+    let config = ServerConfig {
+        relayer_fees: SCHEDULE,
+        ..Default::default()
+    };
+    let pools = Arc::new(PoolRegistry::new(vec![pool]).unwrap());
+    let mut state = AppState::new(config, EventDecoder::bundled(), pools);
+    if let Some(relayer) = relayer {
+        state = state.with_relayer(relayer);
+    }
+    spawn_app_with_state(state).await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn fee_quote_endpoint_uses_the_current_priority_fee() {
+    let rpc = spawn_mock_rpc(RpcFixture {
+        priority_fees: vec![0, 100, 2000, 1000, 1000],
+        ..Default::default()
+    })
+    .await;
+    let relayer = Relayer::new(Keypair::new());
+    let relayer_key = relayer.pubkey();
+    let app = spawn_relaying_app(&rpc, Some(relayer)).await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .get(format!("{}/api/pools/sol-0.1/fee-quote", app))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let quote: FeeQuote = resp.json().await.unwrap();
+    assert_eq!(quote.compute_unit_price, 1000);
+    assert_eq!(quote.breakdown, SCHEDULE.fee(TENTH_SOL, 1000, true));
+    assert_eq!(quote.relayer, relayer_key.to_string());
+    assert_eq!(quote.program_id, PROGRAM_ID);
+
+    let resp = client
+        .get(format!("{}/api/pools/nope/fee-quote", app))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let app = spawn_relaying_app(&rpc, None).await;
+    let resp = client
+        .get(format!("{}/api/pools/sol-0.1/fee-quote", app))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn fee_quote_endpoint_limits_quotes_per_client() {
+    let rpc = spawn_mock_rpc(RpcFixture::default()).await;
+    let pool = PoolConfig {
+        id: "sol-0.1".to_string(),
+        cluster: Some(rpc),
+        denomination: Some(TENTH_SOL),
+        ..PoolConfig::for_program(PROGRAM_ID)
+    };
+    let config = ServerConfig {
+        quotes_per_minute: 1,
+        ..Default::default()
+    };
+    let pools = Arc::new(PoolRegistry::new(vec![pool]).unwrap());
+    let state = AppState::new(config, EventDecoder::bundled(), pools)
+        .with_relayer(Relayer::new(Keypair::new()));
+    let app = spawn_app_with_state(state).await;
+
+    let url = format!("{}/api/pools/sol-0.1/fee-quote", app);
+    assert!(reqwest::get(&url).await.unwrap().status().is_success());
+    let resp = reqwest::get(&url).await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn relayed_withdrawals_need_a_live_quote() {
+    let rpc = spawn_mock_rpc(RpcFixture::default()).await;
+    let app = spawn_relaying_app(&rpc, Some(Relayer::new(Keypair::new()))).await;
+    let note = format!(
+        "solana-mixer-sol-{}-{}:{}",
+        TENTH_SOL,
+        "01".repeat(31),
+        "02".repeat(31)
+    );
+
+    let ws_url = format!(
+        "{}/api/pools/sol-0.1/compute_withdrawal",
+        app.replacen("http", "ws", 1)
+    );
+    for (quote_id, expected) in [
+        (Value::Null, "relayed withdrawals need a fee quote"),
+        (json!("0123"), "unknown fee quote 0123"),
+    ] {
+        let (mut ws, _) = connect_async(&ws_url).await.unwrap();
+        let req = json!({
+            "note": note,
+            "new_withdrawal_recipient_address": Pubkey::new_unique().to_string(),
+            "server_url": "",
+            "relay": true,
+            "quote_id": quote_id,
+        });
+        ws.send(Message::text(req.to_string())).await.unwrap();
+        let reply: Value = match ws.next().await.unwrap().unwrap() {
+            Message::Text(txt) => serde_json::from_str(&txt).unwrap(),
+            other => panic!("unexpected message {:?}", other),
+        };
+        let error = reply["error"].as_str().unwrap();
+        assert!(error.starts_with(expected), "{}", error);
+    }
+}
+
+async fn withdrawal_error(app: &str, req: Value) -> String {
+    let ws_url = format!(
+        "{}/api/pools/sol-0.1/compute_withdrawal",
+        app.replacen("http", "ws", 1)
+    );
+    let (mut ws, _) = connect_async(&ws_url).await.unwrap();
+    ws.send(Message::text(req.to_string())).await.unwrap();
+    match ws.next().await.unwrap().unwrap() {
+        Message::Text(txt) => {
+            let reply: Value = serde_json::from_str(&txt).unwrap();
+            reply["error"].as_str().unwrap().to_string()
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn signed_quotes_fix_the_relayer_and_fee() {
+    let rpc = spawn_mock_rpc(RpcFixture::default()).await;
+    let app = spawn_relaying_app(&rpc, None).await;
+    // A quote from a relayer elsewhere; this server only proves.
+    let theirs = Relayer::new(Keypair::new());
+    let quote = QuoteBook::new(SCHEDULE, Duration::from_secs(60))
+        .issue(&theirs, request(0))
+        .unwrap();
+    let note = format!(
+        "solana-mixer-sol-{}-{}:{}",
+        TENTH_SOL,
+        "01".repeat(31),
+        "02".repeat(31)
+    );
+    let req = |quote: &FeeQuote, relayer: &str| {
+        json!({
+            "note": note,
+            "new_withdrawal_recipient_address": Pubkey::new_unique().to_string(),
+            "new_relayer_address": relayer,
+            "server_url": "",
+            "quote": quote,
+        })
+    };
+
+    let frontend = Pubkey::new_unique().to_string();
+    let error = withdrawal_error(&app, req(&quote, &frontend)).await;
+    assert!(error.starts_with("fee quote pays relayer"), "{}", error);
+
+    let greedy = FeeQuote {
+        fee: quote.fee * 2,
+        net_amount: TENTH_SOL - quote.fee * 2,
+        ..quote.clone()
+    };
+    let error = withdrawal_error(&app, req(&greedy, "")).await;
+    assert!(error.contains("not signed by relayer"), "{}", error);
+
+    let elsewhere = QuoteBook::new(SCHEDULE, Duration::from_secs(60))
+        .issue(
+            &theirs,
+            QuoteRequest {
+                amount: 2 * TENTH_SOL,
+                ..request(0)
+            },
+        )
+        .unwrap();
+    let error = withdrawal_error(&app, req(&elsewhere, "")).await;
+    assert!(error.contains("not this withdrawal"), "{}", error);
+
+    // Relaying needs a quote from this server's own relayer.
+    let app = spawn_relaying_app(&rpc, Some(Relayer::new(Keypair::new()))).await;
+    let mut relayed = req(&quote, "");
+    relayed["relay"] = json!(true);
+    let error = withdrawal_error(&app, relayed).await;
+    assert!(error.starts_with("fee quote is from relayer"), "{}", error);
+}
+
+/// Stands in for the client's `/api/prove-mix`: answers with a proof of the
+/// requested withdrawal that commits `fee` instead of the requested one.
+async fn spawn_underpaying_prover(fee: u64) -> String {
+    let app = Router::new().route(
+        "/api/prove-mix",
+        post(move |Json(req): Json<ProveRequest>| async move {
+            let word = |hex: &str| -> [u8; 32] { hex::decode(hex).unwrap().try_into().unwrap() };
+            let public_values = PublicValues {
+                root: word(&req.root),
+                nullifier_hash: word(&req.nullifier_hash),
+                recipient: word(&req.recipient),
+                relayer: word(&req.relayer),
+                fee,
+                refund: req.refund,
+                mint: req.mint.as_deref().map_or(NATIVE_MINT, word),
+                amount: req.amount,
+            }
+            .encode();
+            let solana = SolanaProof {
+                proof_a: vec![3; 64],
+                proof_b: vec![4; 128],
+                proof_c: vec![5; 64],
+                public_inputs: vec![[0; 32]; 2],
+                public_values: public_values.clone(),
+            };
+            Json(json!({
+                "proof": "",
+                "public_inputs": { "buffer": { "data": public_values } },
+                "solana": solana,
+            }))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn proofs_below_the_quoted_fee_are_not_relayed() {
+    let rpc = spawn_flaky_mock_rpc(RpcFixture::default(), 0).await;
+    let note = format!(
+        "solana-mixer-sol-{}-{}:{}",
+        TENTH_SOL,
+        "01".repeat(31),
+        "02".repeat(31)
+    );
+    let (commitment, _) = Note::parse(&note).unwrap().hashes();
+    let index = Arc::new(DepositIndex::new(PROGRAM_ID.parse().unwrap()));
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs(commitment, 0));
+    let pool = PoolConfig {
+        id: "sol-0.1".to_string(),
+        cluster: Some(rpc.url.clone()),
+        denomination: Some(TENTH_SOL),
+        ..PoolConfig::for_program(PROGRAM_ID)
+    };
+    let config = ServerConfig {
+        relayer_fees: SCHEDULE,
+        ..Default::default()
+    };
+    let state = AppState::new(
+        config,
+        EventDecoder::bundled(),
+        registry_with_index(pool, index),
+    )
+    .with_relayer(Relayer::new(Keypair::new()))
+    .with_vkey_hash("0x00ab11111111111111111111111111111111111111111111111111111111cdef");
+    let app = spawn_app_with_state(state).await;
+
+    let quote: FeeQuote = reqwest::get(format!("{}/api/pools/sol-0.1/fee-quote", app))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let prover = spawn_underpaying_prover(quote.fee - 1).await;
+
+    let ws_url = format!(
+        "{}/api/pools/sol-0.1/compute_withdrawal",
+        app.replacen("http", "ws", 1)
+    );
+    let (mut ws, _) = connect_async(&ws_url).await.unwrap();
+    let req = json!({
+        "note": note,
+        "new_withdrawal_recipient_address": Pubkey::new_unique().to_string(),
+        "server_url": prover,
+        "relay": true,
+        "quote_id": quote.id,
+    });
+    ws.send(Message::text(req.to_string())).await.unwrap();
+    let mut replies = Vec::new();
+    while let Some(Ok(Message::Text(txt))) = ws.next().await {
+        replies.push(serde_json::from_str::<Value>(&txt).unwrap());
+    }
+    assert_eq!(replies.len(), 2, "{:?}", replies);
+    assert!(replies[0]["solana"].is_object(), "{:?}", replies);
+    assert_eq!(
+        replies[1]["error"],
+        "proof commits to another fee than the withdrawal"
+    );
+    assert!(rpc.sent().is_empty());
+}
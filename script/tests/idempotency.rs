@@ -0,0 +1,222 @@
+//! `Idempotency-Key` handling: joining jobs, key reuse and detached proving.
+mod common;
+
+use axum::http::StatusCode;
+use common::spawn_app;
+use futures_util::FutureExt;
+use mixer_lib::tree::{compute_merkle_proof, TREE_DEPTH};
+use mixer_script::jobs::{JobResult, ProofJobs, MAX_KEY_LEN};
+use mixer_script::prover::ProverBackend;
+use mixer_script::server::{
+    ProveRequest, ProveResponse, ServerConfig, IDEMPOTENCY_KEY_HEADER, MIXER_ELF,
+    PROOF_CACHE_HEADER, PROOF_JOB_HEADER,
+};
+use mixer_script::utils::{biguint_to_32_le_bytes, to_hex32, to_hex_vec};
+use sp1_sdk::SP1PublicValues;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn response(proof: &str) -> ProveResponse {
+    ProveResponse {
+        proof: proof.to_string(),
+        public_inputs: SP1PublicValues::new(),
+        solana: None,
+    }
+}
+
+/// A job that counts its runs and proves `proof` after a short delay.
+fn job(
+    runs: &Arc<AtomicUsize>,
+    result: JobResult,
+) -> impl FnOnce() -> futures_util::future::BoxFuture<'static, JobResult> {
+    let runs = runs.clone();
+    move || {
+        async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            result
+        }
+        .boxed()
+    }
+}
+
+#[test]
+fn keys_must_be_printable_ascii() {
+    assert!(ProofJobs::check_key("withdraw-7f3a").is_ok());
+    assert!(ProofJobs::check_key("").is_err());
+    assert!(ProofJobs::check_key("two words").is_err());
+    assert!(ProofJobs::check_key("ключ").is_err());
+    assert!(ProofJobs::check_key(&"k".repeat(MAX_KEY_LEN)).is_ok());
+    assert!(ProofJobs::check_key(&"k".repeat(MAX_KEY_LEN + 1)).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn same_key_joins_the_running_job() {
+    let jobs = ProofJobs::new(Duration::from_secs(60), 16);
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    let (first_id, first) = jobs
+        .submit(Some("k1"), [1; 32], job(&runs, Ok((response("aa"), false))))
+        .unwrap();
+    let (second_id, second) = jobs
+        .submit(Some("k1"), [1; 32], job(&runs, Ok((response("bb"), false))))
+        .unwrap();
+    assert_eq!(first_id, second_id);
+
+    let (first, second) = (first.await.unwrap(), second.await.unwrap());
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    assert_eq!(first.0.proof, "aa");
+    assert_eq!(second.0.proof, "aa");
+
+    // Other inputs under the same key are refused.
+    let err = jobs
+        .submit(Some("k1"), [2; 32], job(&runs, Ok((response("cc"), false))))
+        .unwrap_err();
+    assert_eq!(err.0, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Requests without a key always start their own job.
+    let (a, _) = jobs
+        .submit(None, [1; 32], job(&runs, Ok((response("dd"), false))))
+        .unwrap();
+    let (b, _) = jobs
+        .submit(None, [1; 32], job(&runs, Ok((response("dd"), false))))
+        .unwrap();
+    assert_ne!(a, b);
+    assert_eq!(jobs.len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn jobs_outlive_their_callers_and_failures_release_the_key() {
+    let jobs = ProofJobs::new(Duration::from_secs(60), 16);
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    // The first caller gives up before the job finishes.
+    let (id, handle) = jobs
+        .submit(Some("k1"), [1; 32], job(&runs, Ok((response("aa"), false))))
+        .unwrap();
+    drop(handle);
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let (retry_id, retry) = jobs
+        .submit(Some("k1"), [1; 32], job(&runs, Ok((response("bb"), false))))
+        .unwrap();
+    assert_eq!(id, retry_id);
+    assert_eq!(retry.await.unwrap().0.proof, "aa");
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    let failing = Err((StatusCode::INTERNAL_SERVER_ERROR, "boom".to_string()));
+    let (failed_id, failed) = jobs
+        .submit(Some("k2"), [1; 32], job(&runs, failing))
+        .unwrap();
+    assert!(failed.await.is_err());
+    let (retry_id, retry) = jobs
+        .submit(Some("k2"), [1; 32], job(&runs, Ok((response("cc"), false))))
+        .unwrap();
+    assert_ne!(failed_id, retry_id);
+    assert_eq!(retry.await.unwrap().0.proof, "cc");
+}
+
+#[tokio::test]
+async fn keys_expire() {
+    let jobs = ProofJobs::new(Duration::from_millis(50), 16);
+    let runs = Arc::new(AtomicUsize::new(0));
+    let (first, _) = jobs
+        .submit(Some("k1"), [1; 32], job(&runs, Ok((response("aa"), false))))
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let (second, _) = jobs
+        .submit(Some("k1"), [2; 32], job(&runs, Ok((response("bb"), false))))
+        .unwrap();
+    assert_ne!(first, second);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn full_job_maps_evict_finished_jobs_first() {
+    let jobs = ProofJobs::new(Duration::from_secs(60), 2);
+    let runs = Arc::new(AtomicUsize::new(0));
+    let (_, first) = jobs
+        .submit(Some("k1"), [1; 32], job(&runs, Ok((response("aa"), false))))
+        .unwrap();
+    first.await.unwrap();
+    jobs.submit(Some("k2"), [2; 32], job(&runs, Ok((response("bb"), false))))
+        .unwrap();
+
+    // The finished job makes room; then both remembered jobs are running.
+    jobs.submit(Some("k3"), [3; 32], job(&runs, Ok((response("cc"), false))))
+        .unwrap();
+    assert_eq!(jobs.len(), 2);
+    let err = jobs
+        .submit(Some("k4"), [4; 32], job(&runs, Ok((response("dd"), false))))
+        .unwrap_err();
+    assert_eq!(err.0, StatusCode::SERVICE_UNAVAILABLE);
+    // Known keys still join their jobs.
+    let (_, joined) = jobs
+        .submit(Some("k3"), [3; 32], job(&runs, Ok((response("ee"), false))))
+        .unwrap();
+    assert_eq!(joined.await.unwrap().0.proof, "cc");
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_requests_with_one_key_share_a_job() {
+    if MIXER_ELF.is_empty() {
+        eprintln!("skipping: mixer-program ELF was not built");
+        return;
+    }
+    let (nullifier, secret, _, commitment, nullifier_hash) =
+        mixer_lib::utils::create_random_commitment();
+    let (path_elements, path_indices, root) = compute_merkle_proof::<TREE_DEPTH>(&[commitment], 0);
+    let req = ProveRequest {
+        root: to_hex32(&root),
+        nullifier_hash: to_hex32(&nullifier_hash),
+        recipient: to_hex32(&[1; 32]),
+        relayer: to_hex32(&[2; 32]),
+        fee: 0,
+        refund: 0,
+        mint: None,
+        amount: 0,
+        nullifier: to_hex32(&biguint_to_32_le_bytes(&nullifier)).into(),
+        secret: to_hex32(&biguint_to_32_le_bytes(&secret)).into(),
+        sealed: None,
+        path_elements: to_hex_vec(&path_elements),
+        path_indices: path_indices.to_vec(),
+        format: Default::default(),
+        dry_run: false,
+    };
+    // Without the cache a second proof would have a different job id.
+    let app = spawn_app(ServerConfig {
+        prover: ProverBackend::Mock,
+        proof_cache_size: 0,
+        ..Default::default()
+    })
+    .await;
+
+    let client = reqwest::Client::new();
+    let send = |key: &str| {
+        client
+            .post(format!("{}/api/prove-mix", app))
+            .header(IDEMPOTENCY_KEY_HEADER, key)
+            .json(&req)
+            .send()
+    };
+    let (first, second) = tokio::join!(send("withdraw-1"), send("withdraw-1"));
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert!(first.status().is_success() && second.status().is_success());
+    assert_eq!(
+        first.headers()[PROOF_JOB_HEADER],
+        second.headers()[PROOF_JOB_HEADER]
+    );
+    assert_eq!(
+        first.headers()[PROOF_CACHE_HEADER].to_str().unwrap(),
+        "miss"
+    );
+    let first: ProveResponse = first.json().await.unwrap();
+    let second: ProveResponse = second.json().await.unwrap();
+    assert_eq!(first.proof, second.proof);
+
+    let other = send("withdraw-2").await.unwrap();
+    assert!(other.status().is_success());
+
+    let bad = send("not a key").await.unwrap();
+    assert_eq!(bad.status(), reqwest::StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,234 @@
+mod common;
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use base64::Engine;
+use common::{deposit_logs, spawn_mock_rpc, RpcFixture};
+use flate2::{write::ZlibEncoder, Compression};
+use mixer_script::idl::{
+    event_discriminator, idl_address, DepositEvent, EventDecoder, MixerEvent, WithdrawalEvent,
+    DEPOSIT_EVENT, WITHDRAWAL_EVENT,
+};
+use mixer_script::source::{FixtureSource, RpcDepositSource};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::str::FromStr;
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+fn program_data(name: &str, fields: &[&[u8]]) -> String {
+    let mut data = event_discriminator(name).to_vec();
+    for f in fields {
+        data.extend_from_slice(f);
+    }
+    format!(
+        "Program data: {}",
+        base64::engine::general_purpose::STANDARD.encode(&data)
+    )
+}
+
+/// An IDL in the current spec with the given event field lists.
+fn idl(deposit_fields: Value, withdrawal_fields: Option<Value>) -> Vec<u8> {
+    let mut events = vec![json!({
+        "name": DEPOSIT_EVENT,
+        "discriminator": event_discriminator(DEPOSIT_EVENT),
+    })];
+    let mut types = vec![json!({
+        "name": DEPOSIT_EVENT,
+        "type": { "kind": "struct", "fields": deposit_fields },
+    })];
+    if let Some(fields) = withdrawal_fields {
+        events.push(json!({
+            "name": WITHDRAWAL_EVENT,
+            "discriminator": event_discriminator(WITHDRAWAL_EVENT),
+        }));
+        types.push(json!({
+            "name": WITHDRAWAL_EVENT,
+            "type": { "kind": "struct", "fields": fields },
+        }));
+    }
+    serde_json::to_vec(&json!({
+        "address": PROGRAM_ID,
+        "metadata": { "name": "mixer", "version": "0.2.0", "spec": "0.1.0" },
+        "instructions": [],
+        "events": events,
+        "types": types,
+    }))
+    .unwrap()
+}
+
+fn withdrawal_fields() -> Value {
+    json!([
+        { "name": "nullifier_hash", "type": { "array": ["u8", 32] } },
+        { "name": "recipient", "type": "pubkey" },
+        { "name": "relayer", "type": "pubkey" },
+        { "name": "fee", "type": "u64" },
+    ])
+}
+
+#[test]
+fn upgraded_idl_with_extra_fields_still_decodes_deposits() {
+    // The upgraded program puts a pool key in front and a memo at the end.
+    let decoder = EventDecoder::from_json(&idl(
+        json!([
+            { "name": "pool", "type": "pubkey" },
+            { "name": "commitment", "type": { "array": ["u8", 32] } },
+            { "name": "leaf_index", "type": "u32" },
+            { "name": "depositor", "type": "pubkey" },
+            { "name": "memo", "type": { "option": "string" } },
+        ]),
+        None,
+    ))
+    .unwrap();
+
+    let depositor = Pubkey::new_unique();
+    let memo = b"hello";
+    let logs = vec![
+        "Program log: Instruction: Deposit".to_string(),
+        program_data(
+            DEPOSIT_EVENT,
+            &[
+                &Pubkey::new_unique().to_bytes(),
+                &[7; 32],
+                &5u32.to_le_bytes(),
+                &depositor.to_bytes(),
+                &[1],
+                &(memo.len() as u32).to_le_bytes(),
+                memo,
+            ],
+        ),
+    ];
+    assert_eq!(
+        decoder.deposits(&logs),
+        vec![DepositEvent {
+            commitment: [7; 32],
+            leaf_index: 5,
+            depositor,
+        }]
+    );
+    // The old layout misreads the new event.
+    assert_ne!(
+        EventDecoder::bundled().deposits(&logs)[0].commitment,
+        [7; 32]
+    );
+}
+
+#[test]
+fn legacy_idl_is_converted() {
+    let legacy = json!({
+        "version": "0.1.0",
+        "name": "mixer",
+        "instructions": [],
+        "events": [
+            {
+                "name": "DepositEvent",
+                "fields": [
+                    { "name": "commitment", "type": { "array": ["u8", 32] }, "index": false },
+                    { "name": "leafIndex", "type": "u32", "index": false },
+                    { "name": "depositor", "type": "publicKey", "index": false },
+                ],
+            },
+        ],
+        "metadata": { "address": PROGRAM_ID },
+    });
+    let decoder = EventDecoder::from_json(&serde_json::to_vec(&legacy).unwrap()).unwrap();
+    assert_eq!(
+        decoder.discriminators(),
+        ([120, 248, 61, 83, 31, 142, 107, 144], None)
+    );
+    let events = decoder.deposits(&deposit_logs([3; 32], 2));
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].leaf_index, 2);
+}
+
+#[test]
+fn idl_without_required_fields_is_rejected() {
+    let err = EventDecoder::from_json(&idl(
+        json!([
+            { "name": "commitment", "type": { "array": ["u8", 32] } },
+            { "name": "depositor", "type": "pubkey" },
+        ]),
+        None,
+    ))
+    .unwrap_err();
+    assert!(err.contains("leaf_index"), "{}", err);
+
+    let err = EventDecoder::from_json(&idl(
+        json!([
+            { "name": "commitment", "type": { "array": ["u8", 32] } },
+            { "name": "leaf_index", "type": "u64" },
+            { "name": "depositor", "type": "pubkey" },
+        ]),
+        None,
+    ))
+    .unwrap_err();
+    assert!(err.contains("DepositEvent.leaf_index"), "{}", err);
+}
+
+#[tokio::test]
+async fn decoder_loads_idl_from_program_idl_account() {
+    let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+    let json = idl(
+        json!([
+            { "name": "commitment", "type": { "array": ["u8", 32] } },
+            { "name": "leaf_index", "type": "u32" },
+            { "name": "depositor", "type": "pubkey" },
+        ]),
+        Some(withdrawal_fields()),
+    );
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&json).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut account = Sha256::digest("account:IdlAccount")[..8].to_vec();
+    account.extend_from_slice(&Pubkey::new_unique().to_bytes());
+    account.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+    account.extend_from_slice(&compressed);
+
+    let mut fixture = RpcFixture::default();
+    fixture
+        .accounts
+        .insert(idl_address(&program_id), account.clone());
+    let url = spawn_mock_rpc(fixture).await;
+    let source = RpcDepositSource::new(vec![url]).unwrap();
+    let decoder = EventDecoder::fetch(&source, &program_id).await.unwrap();
+
+    // Any source serving the account will do.
+    let fixture = FixtureSource::new().with_account(idl_address(&program_id), account);
+    let from_fixture = EventDecoder::fetch(&fixture, &program_id).await.unwrap();
+    assert!(EventDecoder::fetch(&FixtureSource::new(), &program_id)
+        .await
+        .is_err());
+
+    let (recipient, relayer) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut logs = deposit_logs([1; 32], 0);
+    logs.push(program_data(
+        WITHDRAWAL_EVENT,
+        &[
+            &[4; 32],
+            &recipient.to_bytes(),
+            &relayer.to_bytes(),
+            &5000u64.to_le_bytes(),
+        ],
+    ));
+    let events = decoder.decode(&logs);
+    assert_eq!(events.len(), 2);
+    assert!(matches!(events[0], MixerEvent::Deposit(ref d) if d.commitment == [1; 32]));
+    assert_eq!(
+        events[1],
+        MixerEvent::Withdrawal(WithdrawalEvent {
+            nullifier_hash: [4; 32],
+            recipient,
+            relayer,
+            fee: 5000,
+        })
+    );
+
+    assert_eq!(from_fixture.decode(&logs), events);
+
+    // No IDL account for another program.
+    let err = EventDecoder::fetch(&source, &Pubkey::new_unique())
+        .await
+        .unwrap_err();
+    assert!(err.contains("getAccountInfo"), "{}", err);
+}
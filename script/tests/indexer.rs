@@ -0,0 +1,175 @@
+//! Tests of the live deposit index: backfill, `logsSubscribe` ingestion and
+//! gap reconciliation after a reconnect.
+mod common;
+
+use anchor_client::solana_sdk::commitment_config::CommitmentLevel::{Confirmed, Finalized};
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use common::{deposit_logs, spawn_flaky_mock_rpc, spawn_mock_pubsub, RpcFixture};
+use mixer_script::indexer::{self, DepositIndex, ReconcileReport};
+use mixer_script::source::{FixtureSource, RpcDepositSource};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+fn program_id() -> Pubkey {
+    Pubkey::from_str(PROGRAM_ID).unwrap()
+}
+
+/// Polls `cond` until it holds or five seconds pass.
+async fn eventually(what: &str, cond: impl Fn() -> bool) {
+    for _ in 0..100 {
+        if cond() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("timed out waiting for {}", what);
+}
+
+#[tokio::test]
+async fn backfill_skips_transactions_already_applied() {
+    let mut source = FixtureSource::new();
+    source.push("sig-0", 10, deposit_logs([1; 32], 0));
+    source.push("sig-1", 11, deposit_logs([2; 32], 1));
+
+    let index = DepositIndex::new(program_id());
+    // The subscription delivered the second deposit before the backfill ran.
+    assert_eq!(
+        index.apply_logs("sig-1", 11, Finalized, &deposit_logs([2; 32], 1)),
+        1
+    );
+    assert_eq!(index.backfill(&source).await.unwrap(), 1);
+    assert_eq!(index.backfill(&source).await.unwrap(), 0);
+
+    assert_eq!(index.leaves(), vec![[1; 32], [2; 32]]);
+    let (leaves, idx) = index.find(&[2; 32]).unwrap();
+    assert_eq!((leaves.len(), idx), (2, 1));
+}
+
+#[tokio::test]
+async fn leaves_after_a_gap_are_held_back() {
+    let index = DepositIndex::new(program_id());
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs([1; 32], 0));
+    index.apply_logs("sig-2", 12, Finalized, &deposit_logs([3; 32], 2));
+
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.leaves(), vec![[1; 32]]);
+    assert!(index.find(&[3; 32]).is_none());
+
+    index.apply_logs("sig-1", 11, Finalized, &deposit_logs([2; 32], 1));
+    assert_eq!(index.leaves(), vec![[1; 32], [2; 32], [3; 32]]);
+    assert_eq!(index.find(&[3; 32]).unwrap().1, 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn subscription_ingests_live_deposits_and_backfills_on_reconnect() {
+    let rpc = spawn_flaky_mock_rpc(RpcFixture::with_deposits(&[[1; 32], [2; 32]]), 0).await;
+    let pubsub = spawn_mock_pubsub().await;
+    let source = Arc::new(RpcDepositSource::new(vec![rpc.url.clone()]).unwrap());
+    // Live deposits arrive at `confirmed`; serve them without waiting for finality.
+    let index = Arc::new(DepositIndex::new(program_id()).with_min_commitment(Confirmed));
+    tokio::spawn(indexer::run_log_subscription(
+        index.clone(),
+        pubsub.url.clone(),
+        source,
+    ));
+
+    // Initial backfill.
+    eventually("backfill", || index.len() == 2).await;
+    assert_eq!(pubsub.subscriptions(), 1);
+
+    // A live deposit arrives over the subscription only.
+    let tx = rpc.push_deposit([3; 32]);
+    pubsub.notify(&tx);
+    eventually("live deposit", || index.find(&[3; 32]).is_some()).await;
+
+    // A deposit lands while the socket is down; the reconnect backfill finds it.
+    pubsub.disconnect();
+    rpc.push_deposit([4; 32]);
+    eventually("reconnect", || pubsub.subscriptions() == 2).await;
+    eventually("gap backfill", || index.find(&[4; 32]).is_some()).await;
+    assert_eq!(index.leaves(), vec![[1; 32], [2; 32], [3; 32], [4; 32]]);
+}
+
+#[tokio::test]
+async fn confirmed_deposits_are_served_once_finalized() {
+    let index = DepositIndex::new(program_id());
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs([1; 32], 0));
+    index.apply_logs("sig-1", 11, Confirmed, &deposit_logs([2; 32], 1));
+    assert_eq!(index.leaves(), vec![[1; 32]]);
+    assert!(index.find(&[2; 32]).is_none());
+    assert_eq!(index.deposit(&[2; 32]).unwrap().status, Confirmed);
+
+    let mut source = FixtureSource::new();
+    source.push("sig-0", 10, deposit_logs([1; 32], 0));
+    source.push("sig-1", 11, deposit_logs([2; 32], 1));
+    let report = index.reconcile(&source).await.unwrap();
+    assert_eq!(report.promoted, 1);
+    assert_eq!(index.find(&[2; 32]).unwrap().1, 1);
+}
+
+#[tokio::test]
+async fn dropped_deposit_rolls_back_later_leaves() {
+    let index = DepositIndex::new(program_id()).with_min_commitment(Confirmed);
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs([1; 32], 0));
+    index.apply_logs("sig-1", 11, Confirmed, &deposit_logs([2; 32], 1));
+    index.apply_logs("sig-2", 12, Confirmed, &deposit_logs([3; 32], 2));
+    assert_eq!(index.leaves().len(), 3);
+
+    // The fork holding sig-1 and sig-2 was abandoned; on the canonical chain
+    // another deposit took leaf 1.
+    let mut source = FixtureSource::new();
+    source.push("sig-0", 10, deposit_logs([1; 32], 0));
+    source.push_with_status("sig-x", 13, Confirmed, deposit_logs([9; 32], 1));
+
+    let report = index.reconcile(&source).await.unwrap();
+    assert_eq!(
+        report,
+        ReconcileReport {
+            promoted: 0,
+            rolled_back: 2,
+            dropped_withdrawals: 0,
+            reapplied: 1,
+        }
+    );
+    assert_eq!(index.leaves(), vec![[1; 32], [9; 32]]);
+    assert!(index.deposit(&[2; 32]).is_none());
+    assert!(index.deposit(&[3; 32]).is_none());
+}
+
+#[tokio::test]
+async fn reconcile_lists_only_the_history_after_the_last_finalized_event() {
+    let fixture = RpcFixture::with_deposits(&[[1; 32], [2; 32]]);
+    let rpc = spawn_flaky_mock_rpc(fixture.clone(), 0).await;
+    let source = RpcDepositSource::new(vec![rpc.url.clone()]).unwrap();
+    let [first, second] = &fixture.txs[..] else {
+        unreachable!()
+    };
+    let index = DepositIndex::new(program_id());
+    index.apply_logs(
+        &first.signature.to_string(),
+        first.slot,
+        Finalized,
+        &first.logs,
+    );
+
+    // Nothing is pending, so there is nothing to ask the node.
+    assert_eq!(
+        index.reconcile(&source).await.unwrap(),
+        ReconcileReport::default()
+    );
+    assert_eq!(rpc.requests(), 0);
+
+    index.apply_logs(
+        &second.signature.to_string(),
+        second.slot,
+        Confirmed,
+        &second.logs,
+    );
+    let report = index.reconcile(&source).await.unwrap();
+    assert_eq!(report.promoted, 1);
+    assert_eq!(report.rolled_back, 0);
+    assert_eq!(index.leaves(), vec![[1; 32], [2; 32]]);
+}
@@ -0,0 +1,125 @@
+//! Withdrawal indexing and `GET /api/pools/{program_id}/stats`.
+mod common;
+
+use anchor_client::solana_sdk::commitment_config::CommitmentLevel::{Confirmed, Finalized};
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use common::{deposit_logs, registry_with_index, spawn_app_with_state, withdrawal_logs};
+use mixer_script::idl::EventDecoder;
+use mixer_script::indexer::DepositIndex;
+use mixer_script::pools::PoolConfig;
+use mixer_script::server::{AppState, PoolStatsResponse, ServerConfig};
+use mixer_script::source::FixtureSource;
+use mixer_script::utils::{compute_merkle_proof, to_hex32, TREE_DEPTH};
+use std::str::FromStr;
+use std::sync::Arc;
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+fn program_id() -> Pubkey {
+    Pubkey::from_str(PROGRAM_ID).unwrap()
+}
+
+#[test]
+fn index_records_withdrawals() {
+    let index = DepositIndex::new(program_id());
+    let recipient = Pubkey::new_unique();
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs([1; 32], 0));
+    assert_eq!(
+        index.apply_logs(
+            "sig-1",
+            11,
+            Finalized,
+            &withdrawal_logs([7; 32], recipient, 50)
+        ),
+        1
+    );
+
+    let w = index.withdrawal(&[7; 32]).unwrap();
+    assert_eq!((w.recipient, w.fee, w.slot), (recipient, 50, 11));
+    let stats = index.stats();
+    assert_eq!(
+        (stats.deposits, stats.withdrawals, stats.anonymity_set),
+        (1, 1, 0)
+    );
+}
+
+#[tokio::test]
+async fn dropped_withdrawal_is_removed_on_reconcile() {
+    let index = DepositIndex::new(program_id());
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs([1; 32], 0));
+    index.apply_logs(
+        "sig-1",
+        11,
+        Confirmed,
+        &withdrawal_logs([7; 32], Pubkey::new_unique(), 0),
+    );
+
+    let mut source = FixtureSource::new();
+    source.push("sig-0", 10, deposit_logs([1; 32], 0));
+    source.push("sig-2", 12, deposit_logs([2; 32], 1));
+    let report = index.reconcile(&source).await.unwrap();
+    assert_eq!(report.dropped_withdrawals, 1);
+    assert_eq!(report.reapplied, 1);
+    assert!(index.withdrawal(&[7; 32]).is_none());
+    assert_eq!(index.stats().anonymity_set, 2);
+}
+
+#[tokio::test]
+async fn stats_endpoint_reports_pool_activity() {
+    let index = Arc::new(DepositIndex::new(program_id()));
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs([1; 32], 0));
+    index.apply_logs("sig-1", 11, Finalized, &deposit_logs([2; 32], 1));
+    index.apply_logs("sig-2", 12, Confirmed, &deposit_logs([3; 32], 2));
+    index.apply_logs(
+        "sig-3",
+        13,
+        Finalized,
+        &withdrawal_logs([7; 32], Pubkey::new_unique(), 0),
+    );
+
+    let url = spawn_app_with_state(AppState::new(
+        ServerConfig::default(),
+        EventDecoder::bundled(),
+        registry_with_index(PoolConfig::for_program(PROGRAM_ID), index.clone()),
+    ))
+    .await;
+    let client = reqwest::Client::new();
+    let stats: PoolStatsResponse = client
+        .get(format!("{}/api/pools/{}/stats", url, PROGRAM_ID))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(stats.deposit_count, 3);
+    assert_eq!(stats.withdrawal_count, 1);
+    assert!(stats.seconds_since_last_deposit.unwrap() < 60);
+    // The confirmed deposit is not part of the served tree yet, nor of the
+    // anonymity set.
+    assert_eq!(stats.anonymity_set, 1);
+    let (_, _, root) = compute_merkle_proof::<TREE_DEPTH>(&[[1; 32], [2; 32]], 0);
+    assert_eq!(stats.root, Some(to_hex32(&root)));
+
+    // Once it finalizes, it is.
+    index.apply_logs("sig-2", 12, Finalized, &deposit_logs([3; 32], 2));
+    let stats: PoolStatsResponse = client
+        .get(format!("{}/api/pools/{}/stats", url, PROGRAM_ID))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats.anonymity_set, 2);
+    let (_, _, root) = compute_merkle_proof::<TREE_DEPTH>(&[[1; 32], [2; 32], [3; 32]], 0);
+    assert_eq!(stats.root, Some(to_hex32(&root)));
+
+    let resp = client
+        .get(format!("{}/api/pools/{}/stats", url, Pubkey::new_unique()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,217 @@
+//! Pool registry, denomination checks and the `/api/pools/{pool_id}/...` routes.
+mod common;
+
+use anchor_client::solana_sdk::commitment_config::CommitmentLevel::Finalized;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use common::{deposit_logs, registry_with_index, spawn_app_with_state, spawn_mock_rpc, RpcFixture};
+use futures_util::{SinkExt, StreamExt};
+use mixer_script::idl::EventDecoder;
+use mixer_script::indexer::DepositIndex;
+use mixer_script::note::{format_units, parse_units, Note};
+use mixer_script::pools::{PoolConfig, PoolRegistry};
+use mixer_script::server::{AppState, ServerConfig};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+const TENTH_SOL: u64 = 100_000_000;
+
+fn sol_pool(id: &str, program_id: &str, denomination: u64) -> PoolConfig {
+    PoolConfig {
+        id: id.to_string(),
+        denomination: Some(denomination),
+        ..PoolConfig::for_program(program_id)
+    }
+}
+
+fn note(amount: &str) -> String {
+    format!(
+        "solana-mixer-{}-{}:{}",
+        amount,
+        "01".repeat(31),
+        "02".repeat(31)
+    )
+}
+
+#[test]
+fn registry_rejects_invalid_pools() {
+    let other = Pubkey::new_unique().to_string();
+    let err = PoolRegistry::new(vec![
+        sol_pool("sol-0.1", PROGRAM_ID, TENTH_SOL),
+        sol_pool("sol-0.1", &other, TENTH_SOL),
+    ])
+    .unwrap_err();
+    assert!(err.contains("duplicate pool id"), "{}", err);
+
+    let err = PoolRegistry::new(vec![PoolConfig {
+        tree_depth: 16,
+        ..sol_pool("sol-0.1", PROGRAM_ID, TENTH_SOL)
+    }])
+    .unwrap_err();
+    assert!(err.contains("tree depth 16"), "{}", err);
+
+    let err = PoolRegistry::from_json(&format!(
+        r#"[{{ "id": "sol", "program_id": "{}" }}]"#,
+        PROGRAM_ID
+    ))
+    .unwrap_err();
+    assert!(err.contains("no denomination"), "{}", err);
+
+    let err =
+        PoolRegistry::from_json(r#"[{ "id": "sol", "program_id": "nope", "denomination": 1 }]"#)
+            .unwrap_err();
+    assert!(err.contains("invalid program id"), "{}", err);
+
+    let pools = PoolRegistry::from_json(&format!(
+        r#"[{{ "id": "sol-1", "program_id": "{}", "cluster": "devnet", "denomination": 1000000000, "hasher": "poseidon" }}]"#,
+        PROGRAM_ID
+    ))
+    .unwrap();
+    let pool = &pools.get("sol-1").unwrap().config;
+    assert_eq!(pool.rpc_url().unwrap(), "https://api.devnet.solana.com");
+    assert_eq!(pools.get(PROGRAM_ID).unwrap().config.id, "sol-1");
+
+    let example = PoolRegistry::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../pools.example.json"
+    ))
+    .unwrap();
+    assert_eq!(example.iter().count(), 3);
+}
+
+#[test]
+fn note_amount_must_match_denomination() {
+    assert_eq!(parse_units("0.1", 9).unwrap(), TENTH_SOL);
+    assert_eq!(parse_units("10", 6).unwrap(), 10_000_000);
+    assert!(parse_units("0.0000001", 6).is_err());
+    assert_eq!(format_units(TENTH_SOL, 9), "0.1");
+    assert_eq!(format_units(5_000_000_000, 9), "5");
+
+    let pool = sol_pool("sol-0.1", PROGRAM_ID, TENTH_SOL);
+    pool.check_note(&Note::parse(&note("0.1")).unwrap())
+        .unwrap();
+    pool.check_note(&Note::parse(&note("0.100")).unwrap())
+        .unwrap();
+    let err = pool
+        .check_note(&Note::parse(&note("1")).unwrap())
+        .unwrap_err();
+    assert_eq!(err, "note is for 1 but pool sol-0.1 takes deposits of 0.1");
+
+    // The MIXER_PROGRAM_ID shorthand takes any amount.
+    PoolConfig::for_program(PROGRAM_ID)
+        .check_note(&Note::parse(&note("1")).unwrap())
+        .unwrap();
+}
+
+async fn spawn_pools_app(rpc_urls: Vec<String>) -> String {
+    let index = Arc::new(DepositIndex::new(PROGRAM_ID.parse().unwrap()));
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs([1; 32], 0));
+    spawn_app_with_state(AppState::new(
+        ServerConfig {
+            rpc_urls,
+            ..Default::default()
+        },
+        EventDecoder::bundled(),
+        registry_with_index(sol_pool("sol-0.1", PROGRAM_ID, TENTH_SOL), index),
+    ))
+    .await
+}
+
+#[tokio::test]
+async fn pool_routes_validate_notes() {
+    let app = spawn_pools_app(Vec::new()).await;
+    let client = reqwest::Client::new();
+
+    let pools: Vec<PoolConfig> = client
+        .get(format!("{}/api/pools", app))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(pools.len(), 1);
+    assert_eq!(pools[0].denomination, Some(TENTH_SOL));
+
+    let decode = |pool: &str, note: String| {
+        client
+            .post(format!("{}/api/pools/{}/decode-note", app, pool))
+            .json(&json!({ "note": note }))
+            .send()
+    };
+    let resp = decode("sol-0.1", note("0.1")).await.unwrap();
+    assert!(resp.status().is_success());
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["amount"], 0.1);
+
+    let resp = decode("sol-0.1", note("10")).await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(resp.text().await.unwrap().contains("takes deposits of 0.1"));
+
+    let resp = decode("sol-0.1", "garbage".to_string()).await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let resp = decode("sol-5", note("5")).await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let resp = client
+        .get(format!("{}/api/pools/sol-0.1/stats", app))
+        .send()
+        .await
+        .unwrap();
+    let stats: Value = resp.json().await.unwrap();
+    assert_eq!(stats["pool_id"], "sol-0.1");
+    assert_eq!(stats["deposit_count"], 1);
+}
+
+async fn pool_withdrawal(app: &str, req: Value) -> Value {
+    let url = format!(
+        "{}/api/pools/sol-0.1/compute_withdrawal",
+        app.replacen("http", "ws", 1)
+    );
+    let (mut ws, _) = connect_async(url).await.unwrap();
+    ws.send(Message::text(req.to_string())).await.unwrap();
+    loop {
+        match ws.next().await.expect("socket closed").unwrap() {
+            Message::Text(txt) => return serde_json::from_str(&txt).unwrap(),
+            Message::Close(_) => panic!("socket closed without a reply"),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test]
+async fn pool_withdrawal_checks_the_note_and_uses_the_pool() {
+    let rpc = spawn_mock_rpc(RpcFixture::with_deposits(&[[1; 32]])).await;
+    let app = spawn_pools_app(vec![rpc]).await;
+    let recipient = Pubkey::new_unique().to_string();
+    let base = json!({
+        "new_withdrawal_recipient_address": recipient,
+        "new_relayer_address": recipient,
+        "server_url": app,
+    });
+    let with = |extra: Value| {
+        let mut req = base.clone();
+        req.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        req
+    };
+
+    let reply = pool_withdrawal(&app, with(json!({ "nullifier": "1", "secret": "2" }))).await;
+    assert_eq!(
+        reply["error"],
+        "withdrawals from pool sol-0.1 need the note"
+    );
+
+    let reply = pool_withdrawal(&app, with(json!({ "note": note("1") }))).await;
+    assert_eq!(
+        reply["error"],
+        "note is for 1 but pool sol-0.1 takes deposits of 0.1"
+    );
+
+    // Neither the index nor the pool's RPC know this note's deposit.
+    let reply = pool_withdrawal(&app, with(json!({ "note": note("0.1") }))).await;
+    assert_eq!(reply["error"], "Commitment not found in on-chain history");
+}
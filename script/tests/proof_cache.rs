@@ -0,0 +1,171 @@
+//! The proof cache: keys, expiry, eviction and coalescing of retries.
+mod common;
+
+use common::spawn_app;
+use mixer_lib::mix::{PublicValues, WithdrawalInputs, NATIVE_MINT};
+use mixer_lib::tree::{compute_merkle_proof, TREE_DEPTH};
+use mixer_script::cache::ProofCache;
+use mixer_script::prover::{withdrawal_stdin, ProverBackend};
+use mixer_script::server::{
+    ProveRequest, ProveResponse, ServerConfig, MIXER_ELF, PROOF_CACHE_HEADER,
+};
+use mixer_script::solana::ProofFormat;
+use mixer_script::utils::{biguint_to_32_le_bytes, to_hex32, to_hex_vec};
+use sp1_sdk::SP1PublicValues;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn inputs(secret: u8) -> WithdrawalInputs {
+    WithdrawalInputs {
+        root: [1; 32],
+        nullifier_hash: [2; 32],
+        recipient: [3; 32],
+        relayer: [4; 32],
+        fee: 0,
+        refund: 0,
+        mint: NATIVE_MINT,
+        amount: 100_000_000,
+        nullifier: [5; 32].into(),
+        secret: [secret; 32].into(),
+        path_elements: [[0; 32]; TREE_DEPTH],
+        path_indices: [0; TREE_DEPTH],
+    }
+}
+
+fn response(proof: &str, public_values: &PublicValues) -> ProveResponse {
+    ProveResponse {
+        proof: proof.to_string(),
+        public_inputs: SP1PublicValues::from(&public_values.encode()),
+        solana: None,
+    }
+}
+
+#[test]
+fn key_covers_private_inputs_and_format() {
+    let key = |secret, format| ProofCache::key(&withdrawal_stdin(&inputs(secret)), format);
+    assert_eq!(key(6, ProofFormat::Sp1), key(6, ProofFormat::Sp1));
+    assert_ne!(key(6, ProofFormat::Sp1), key(7, ProofFormat::Sp1));
+    assert_ne!(key(6, ProofFormat::Sp1), key(6, ProofFormat::Solana));
+}
+
+#[tokio::test]
+async fn entries_expire_and_must_match_public_values() {
+    let cache = ProofCache::new(Duration::from_millis(100), 4);
+    let pv = inputs(6).public_values();
+    let key = ProofCache::key(&withdrawal_stdin(&inputs(6)), ProofFormat::Sp1);
+    cache.insert(key, pv, response("aa", &pv));
+
+    assert_eq!(cache.get(&key, &pv).unwrap().proof, "aa");
+    let other = PublicValues { fee: 1, ..pv };
+    assert!(cache.get(&key, &other).is_none());
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(cache.get(&key, &pv).is_none());
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn oldest_entries_are_evicted() {
+    let cache = ProofCache::new(Duration::from_secs(60), 2);
+    let pv = inputs(0).public_values();
+    for k in 0..3u8 {
+        cache.insert([k; 32], pv, response(&k.to_string(), &pv));
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&[0; 32], &pv).is_none());
+    assert_eq!(cache.get(&[2; 32], &pv).unwrap().proof, "2");
+
+    let disabled = ProofCache::new(Duration::from_secs(60), 0);
+    disabled.insert([0; 32], pv, response("aa", &pv));
+    assert!(disabled.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_retries_share_one_proof() {
+    let cache = Arc::new(ProofCache::new(Duration::from_secs(60), 4));
+    let proofs = Arc::new(AtomicUsize::new(0));
+    let pv = inputs(6).public_values();
+
+    let attempt = || {
+        let cache = cache.clone();
+        let proofs = proofs.clone();
+        tokio::spawn(async move {
+            cache
+                .get_or_prove([9; 32], pv, || async {
+                    proofs.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    Ok::<_, String>(response("aa", &pv))
+                })
+                .await
+                .unwrap()
+        })
+    };
+    let (first, second) = tokio::join!(attempt(), attempt());
+    let (first, second) = (first.unwrap(), second.unwrap());
+
+    assert_eq!(proofs.load(Ordering::SeqCst), 1);
+    assert_eq!(first.0.proof, second.0.proof);
+    assert!(first.1 != second.1, "exactly one caller hits the cache");
+
+    // Failures are not cached.
+    let err = cache
+        .get_or_prove([8; 32], pv, || async { Err::<ProveResponse, _>("boom") })
+        .await
+        .unwrap_err();
+    assert_eq!(err, "boom");
+    assert!(cache.get(&[8; 32], &pv).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn repeated_prove_request_is_served_from_cache() {
+    if MIXER_ELF.is_empty() {
+        eprintln!("skipping: mixer-program ELF was not built");
+        return;
+    }
+    let (nullifier, secret, _, commitment, nullifier_hash) =
+        mixer_lib::utils::create_random_commitment();
+    let (path_elements, path_indices, root) = compute_merkle_proof::<TREE_DEPTH>(&[commitment], 0);
+    let req = ProveRequest {
+        root: to_hex32(&root),
+        nullifier_hash: to_hex32(&nullifier_hash),
+        recipient: to_hex32(&[1; 32]),
+        relayer: to_hex32(&[2; 32]),
+        fee: 0,
+        refund: 0,
+        mint: None,
+        amount: 0,
+        nullifier: to_hex32(&biguint_to_32_le_bytes(&nullifier)).into(),
+        secret: to_hex32(&biguint_to_32_le_bytes(&secret)).into(),
+        sealed: None,
+        path_elements: to_hex_vec(&path_elements),
+        path_indices: path_indices.to_vec(),
+        format: Default::default(),
+        dry_run: false,
+    };
+    let app = spawn_app(ServerConfig {
+        prover: ProverBackend::Mock,
+        ..Default::default()
+    })
+    .await;
+
+    let client = reqwest::Client::new();
+    let mut seen = Vec::new();
+    for _ in 0..2 {
+        let resp = client
+            .post(format!("{}/api/prove-mix", app))
+            .json(&req)
+            .send()
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        seen.push(
+            resp.headers()[PROOF_CACHE_HEADER]
+                .to_str()
+                .unwrap()
+                .to_string(),
+        );
+    }
+    assert_eq!(seen, ["miss", "hit"]);
+}
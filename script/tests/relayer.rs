@@ -0,0 +1,257 @@
+//! The relayer: the `withdraw` instruction it builds, and submission and
+//! confirmation against a mocked Solana RPC.
+mod common;
+
+use anchor_client::solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use common::{spawn_app, spawn_app_with_state, spawn_flaky_mock_rpc, RpcFixture};
+use futures_util::{SinkExt, StreamExt};
+use mixer_lib::mix::{PublicValues, NATIVE_MINT};
+use mixer_script::idl::EventDecoder;
+use mixer_script::prover::ProverBackend;
+use mixer_script::relayer::{
+    check_proof, instruction_discriminator, withdraw_instruction, Relayer, NULLIFIER_SEED,
+    STATE_SEED, TOKEN_PROGRAM_ID,
+};
+use mixer_script::server::{AppState, ServerConfig};
+use mixer_script::solana::SolanaProof;
+use mixer_script::source::{FixtureSource, RpcDepositSource};
+use serde_json::{json, Value};
+use spl_associated_token_account_client::address::get_associated_token_address;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+fn program_id() -> Pubkey {
+    Pubkey::from_str(PROGRAM_ID).unwrap()
+}
+
+/// A proof with placeholder points paying its fee to `relayer`.
+fn fake_proof(relayer: Pubkey, mint: [u8; 32]) -> (SolanaProof, PublicValues) {
+    let public_values = PublicValues {
+        root: [1; 32],
+        nullifier_hash: [2; 32],
+        recipient: Pubkey::new_unique().to_bytes(),
+        relayer: relayer.to_bytes(),
+        fee: 5000,
+        refund: 0,
+        mint,
+        amount: 100_000_000,
+    };
+    let proof = SolanaProof {
+        proof_a: vec![3; 64],
+        proof_b: vec![4; 128],
+        proof_c: vec![5; 64],
+        public_inputs: vec![[0; 32]; 2],
+        public_values: public_values.encode(),
+    };
+    (proof, public_values)
+}
+
+#[test]
+fn withdraw_instruction_layout() {
+    let payer = Pubkey::new_unique();
+    let (proof, public_values) = fake_proof(payer, NATIVE_MINT);
+    let ix = withdraw_instruction(&program_id(), &payer, &proof).unwrap();
+
+    assert_eq!(ix.program_id, program_id());
+    assert_eq!(ix.data[..8], instruction_discriminator("withdraw"));
+    assert_eq!(
+        ix.data.len(),
+        8 + 64 + 128 + 64 + 4 + public_values.encode().len()
+    );
+    assert_eq!(ix.data[8..72], proof.proof_a[..]);
+    assert_eq!(ix.data[ix.data.len() - 184..], proof.public_values[..]);
+
+    let (state, _) = Pubkey::find_program_address(&[STATE_SEED], &program_id());
+    let (nullifier, _) = Pubkey::find_program_address(&[NULLIFIER_SEED, &[2; 32]], &program_id());
+    let keys: Vec<Pubkey> = ix.accounts.iter().map(|a| a.pubkey).collect();
+    assert_eq!(
+        keys[..5],
+        [
+            payer,
+            state,
+            nullifier,
+            Pubkey::new_from_array(public_values.recipient),
+            payer
+        ]
+    );
+    assert!(ix.accounts[0].is_signer && ix.accounts[0].is_writable);
+    assert!(ix.accounts[1..].iter().all(|a| !a.is_signer));
+    assert_eq!(ix.accounts.len(), 6);
+
+    let mint = Pubkey::new_unique();
+    let (token_proof, _) = fake_proof(payer, mint.to_bytes());
+    let ix = withdraw_instruction(&program_id(), &payer, &token_proof).unwrap();
+    let keys: Vec<Pubkey> = ix.accounts.iter().map(|a| a.pubkey).collect();
+    assert_eq!(
+        keys[6..],
+        [
+            mint,
+            get_associated_token_address(&state, &mint),
+            TOKEN_PROGRAM_ID
+        ]
+    );
+}
+
+#[test]
+fn proofs_for_another_relayer_are_refused() {
+    let relayer = Relayer::new(Keypair::new());
+    let blockhash = Default::default();
+
+    let (theirs, _) = fake_proof(Pubkey::new_unique(), NATIVE_MINT);
+    let err = relayer
+        .withdraw_transaction(&program_id(), &theirs, 0, blockhash)
+        .unwrap_err();
+    assert!(err.contains("not to this relayer"), "{}", err);
+
+    // Token fees go to the relayer's associated token account.
+    let mint = Pubkey::new_unique();
+    let (wallet, _) = fake_proof(relayer.pubkey(), mint.to_bytes());
+    assert!(relayer
+        .withdraw_transaction(&program_id(), &wallet, 0, blockhash)
+        .is_err());
+    let (ata, _) = fake_proof(relayer.fee_account(&mint.to_bytes()), mint.to_bytes());
+    assert!(relayer
+        .withdraw_transaction(&program_id(), &ata, 0, blockhash)
+        .is_ok());
+}
+
+#[test]
+fn relayed_proofs_must_match_the_withdrawal_and_verify() {
+    const VKEY_HASH: &str = "0x00ab11111111111111111111111111111111111111111111111111111111cdef";
+    let (proof, expected) = fake_proof(Pubkey::new_unique(), NATIVE_MINT);
+
+    for (field, changed) in [
+        ("fee", PublicValues { fee: 0, ..expected }),
+        (
+            "refund",
+            PublicValues {
+                refund: 1,
+                ..expected
+            },
+        ),
+        (
+            "root",
+            PublicValues {
+                root: [9; 32],
+                ..expected
+            },
+        ),
+        (
+            "amount",
+            PublicValues {
+                amount: 1,
+                ..expected
+            },
+        ),
+    ] {
+        let err = check_proof(&proof, &changed, VKEY_HASH).unwrap_err();
+        assert_eq!(
+            err,
+            format!("proof commits to another {} than the withdrawal", field)
+        );
+    }
+    // The placeholder points do not verify.
+    let err = check_proof(&proof, &expected, VKEY_HASH).unwrap_err();
+    assert!(err.starts_with("proof does not verify"), "{}", err);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn withdrawal_is_submitted_and_confirmed() {
+    // The first request fails over to a retry.
+    let rpc = spawn_flaky_mock_rpc(RpcFixture::default(), 1).await;
+    let source = RpcDepositSource::new(vec![rpc.url.clone()]).unwrap();
+    let relayer = Relayer::new(Keypair::new()).with_poll_interval(Duration::from_millis(10));
+    let (proof, _) = fake_proof(relayer.pubkey(), NATIVE_MINT);
+
+    let signature = relayer
+        .submit(&source, &program_id(), &proof, 0)
+        .await
+        .unwrap();
+    let sent = rpc.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].signatures[0], signature);
+    assert_eq!(sent[0].message.account_keys[0], relayer.pubkey());
+    sent[0].verify().unwrap();
+
+    assert_eq!(relayer.confirm(&source, &signature).await.unwrap(), 500);
+
+    // A signature the cluster never saw times out.
+    let relayer = relayer.with_confirm_timeout(Duration::from_millis(50));
+    let unknown = Keypair::new().sign_message(b"never sent");
+    assert!(relayer.confirm(&source, &unknown).await.is_err());
+}
+
+#[tokio::test]
+async fn fixture_source_backs_the_relayer() {
+    let mut source = FixtureSource::new();
+    source.push("sig-0", 41, Vec::new());
+    let relayer = Relayer::new(Keypair::new()).with_poll_interval(Duration::from_millis(10));
+    let (proof, _) = fake_proof(relayer.pubkey(), NATIVE_MINT);
+
+    let signature = relayer
+        .submit(&source, &program_id(), &proof, 0)
+        .await
+        .unwrap();
+    let sent = source.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].signatures[0], signature);
+    sent[0].verify().unwrap();
+    assert_eq!(relayer.confirm(&source, &signature).await.unwrap(), 42);
+
+    let relayer = relayer.with_confirm_timeout(Duration::from_millis(50));
+    let unknown = Keypair::new().sign_message(b"never sent");
+    assert!(relayer.confirm(&source, &unknown).await.is_err());
+}
+
+async fn ws_request(app: &str, req: Value) -> Value {
+    let ws_url = format!("{}/ws/compute_withdrawal", app.replacen("http", "ws", 1));
+    let (mut ws, _) = connect_async(ws_url).await.unwrap();
+    ws.send(Message::text(req.to_string())).await.unwrap();
+    loop {
+        match ws.next().await.expect("socket closed").unwrap() {
+            Message::Text(txt) => return serde_json::from_str(&txt).unwrap(),
+            Message::Close(_) => panic!("socket closed without a reply"),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn relay_requests_need_this_servers_relayer() {
+    let config = ServerConfig {
+        prover: ProverBackend::Mock,
+        ..Default::default()
+    };
+    let (nullifier, secret, ..) = mixer_lib::utils::create_random_commitment();
+    let req = |relayer: &str| {
+        json!({
+            "nullifier": nullifier.to_string(),
+            "secret": secret.to_string(),
+            "program_pubkey": PROGRAM_ID,
+            "new_withdrawal_recipient_address": Pubkey::new_unique().to_string(),
+            "new_relayer_address": relayer,
+            "server_url": "",
+            "relay": true,
+        })
+    };
+
+    let app = spawn_app(config.clone()).await;
+    let reply = ws_request(&app, req("")).await;
+    assert_eq!(reply["error"], "this server does not relay withdrawals");
+
+    let state = AppState::new(config, EventDecoder::bundled(), Default::default())
+        .with_relayer(Relayer::new(Keypair::new()));
+    let app = spawn_app_with_state(state).await;
+    let reply = ws_request(&app, req(&Pubkey::new_unique().to_string())).await;
+    assert!(
+        reply["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("relayed withdrawals pay this server's relayer"),
+        "{}",
+        reply
+    );
+}
@@ -0,0 +1,195 @@
+//! The relayer directory: signed registrations, persistence and health checks.
+mod common;
+
+use anchor_client::solana_sdk::signature::Keypair;
+use common::{spawn_app_with_state, spawn_mock_rpc, RpcFixture};
+use mixer_script::directory::{Registration, RelayerDirectory, RelayerEntry};
+use mixer_script::idl::EventDecoder;
+use mixer_script::pools::{PoolConfig, PoolRegistry};
+use mixer_script::quote::FeeSchedule;
+use mixer_script::relayer::Relayer;
+use mixer_script::server::{AppState, ServerConfig};
+use std::sync::Arc;
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+const FEES: FeeSchedule = FeeSchedule {
+    rate_bps: 30,
+    prover_cost: 1_000_000,
+};
+
+/// `registration` re-signed as of `registered_at`.
+fn dated(relayer: &Relayer, registration: &Registration, registered_at: u64) -> Registration {
+    let mut registration = Registration {
+        registered_at,
+        ..registration.clone()
+    };
+    registration.signature = relayer.sign(&registration.message()).to_string();
+    registration
+}
+
+fn pools(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[test]
+fn registrations_are_signed_and_ordered() {
+    let relayer = Relayer::new(Keypair::new());
+    let registration = Registration::sign(&relayer, "https://relay.example/", FEES, pools(&["a"]));
+    assert_eq!(registration.endpoint, "https://relay.example");
+    registration.verify().unwrap();
+
+    let hijacked = Registration {
+        endpoint: "https://evil.example".to_string(),
+        ..registration.clone()
+    };
+    assert!(hijacked.verify().unwrap_err().contains("not signed"));
+    let future = dated(&relayer, &registration, registration.registered_at + 3600);
+    assert!(future.verify().is_err());
+    let ftp = Registration::sign(&relayer, "ftp://relay.example", FEES, pools(&["a"]));
+    assert!(ftp.verify().is_err());
+
+    let directory = RelayerDirectory::new();
+    directory.register(registration.clone()).unwrap();
+    // Replays are refused, and so are updates signed with another key.
+    assert!(directory.register(registration.clone()).is_err());
+    let update = dated(
+        &Relayer::new(Keypair::new()),
+        &registration,
+        registration.registered_at + 1,
+    );
+    assert!(directory.register(update).is_err());
+
+    let update = Registration {
+        pools: pools(&["a", "b"]),
+        ..registration.clone()
+    };
+    directory
+        .register(dated(&relayer, &update, registration.registered_at + 1))
+        .unwrap();
+    assert_eq!(directory.list(Some("b")).len(), 1);
+    assert!(directory.list(Some("c")).is_empty());
+
+    // Registering without pools removes the relayer for good.
+    let removal = Registration {
+        pools: Vec::new(),
+        ..registration.clone()
+    };
+    directory
+        .register(dated(&relayer, &removal, registration.registered_at + 2))
+        .unwrap();
+    assert!(directory.is_empty());
+    assert!(directory.register(registration).is_err());
+    assert!(directory.is_empty());
+}
+
+#[test]
+fn directory_survives_restarts() {
+    let path = std::env::temp_dir().join(format!(
+        "relayers-{}.json",
+        hex::encode(rand::random::<[u8; 8]>())
+    ));
+    let relayer = Relayer::new(Keypair::new());
+    let registration = Registration::sign(&relayer, "https://relay.example", FEES, pools(&["a"]));
+
+    let directory = RelayerDirectory::open(&path).unwrap();
+    assert!(directory.is_empty());
+    directory.register(registration.clone()).unwrap();
+
+    let reopened = RelayerDirectory::open(&path).unwrap();
+    let list = reopened.list(None);
+    assert_eq!(list.len(), 1);
+    assert_eq!(list[0].registration, registration);
+    assert!(reopened.register(registration).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn pool(rpc_url: &str) -> Arc<PoolRegistry> {
+    let pool = PoolConfig {
+        id: "sol-0.1".to_string(),
+        cluster: Some(rpc_url.to_string()),
+        denomination: Some(100_000_000),
+        ..PoolConfig::for_program(PROGRAM_ID)
+    };
+    Arc::new(PoolRegistry::new(vec![pool]).unwrap())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn relayers_are_listed_with_their_health() {
+    let rpc = spawn_mock_rpc(RpcFixture::default()).await;
+    let config = ServerConfig {
+        relayer_fees: FEES,
+        ..Default::default()
+    };
+
+    // A relayer serving quotes, and one whose server is gone.
+    let keypair = Keypair::new();
+    let live = Relayer::new(keypair.insecure_clone());
+    let live_app = spawn_app_with_state(
+        AppState::new(config.clone(), EventDecoder::bundled(), pool(&rpc))
+            .with_relayer(Relayer::new(keypair)),
+    )
+    .await;
+    let live_registration = Registration::sign(&live, &live_app, FEES, pools(&["sol-0.1"]));
+    let dead = Relayer::new(Keypair::new());
+    let dead_registration =
+        Registration::sign(&dead, "http://127.0.0.1:1", FEES, pools(&["sol-0.1"]));
+
+    let directory = Arc::new(RelayerDirectory::new());
+    let app = spawn_app_with_state(
+        AppState::new(config, EventDecoder::bundled(), pool(&rpc))
+            .with_directory(directory.clone()),
+    )
+    .await;
+
+    let client = reqwest::Client::new();
+    for registration in [&live_registration, &dead_registration] {
+        let resp = client
+            .post(format!("{}/api/relayers", app))
+            .json(registration)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::NO_CONTENT);
+    }
+    let elsewhere = Registration::sign(&dead, "http://127.0.0.1:1", FEES, pools(&["nope"]));
+    let resp = client
+        .post(format!("{}/api/relayers", app))
+        .json(&elsewhere)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+
+    directory.check_all().await.unwrap();
+    let list: Vec<RelayerEntry> = client
+        .get(format!("{}/api/relayers?pool=sol-0.1", app))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[0].registration.relayer, live.pubkey().to_string());
+    assert!(list[0].health.healthy);
+    assert_eq!(list[0].uptime, Some(1.0));
+    assert!(!list[1].health.healthy);
+    assert_eq!(list[1].uptime, Some(0.0));
+    assert!(list[1].health.last_error.is_some());
+
+    let resp = client
+        .get(format!("{}/api/relayers?pool=nope", app))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // Servers that are no directory do not list relayers.
+    let resp = client
+        .get(format!("{}/api/relayers", live_app))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,148 @@
+//! Private inputs sealed to the server's input key.
+mod common;
+
+use common::spawn_app;
+use mixer_lib::tree::{compute_merkle_proof, TREE_DEPTH};
+use mixer_script::prover::ProverBackend;
+use mixer_script::sealed::{InputKey, PrivateInputs, SealedInputs};
+use mixer_script::server::{
+    DryRunResponse, InputKeyResponse, ProveRequest, ServerConfig, MIXER_ELF,
+};
+use mixer_script::utils::{biguint_to_32_le_bytes, to_hex32, to_hex_vec};
+use reqwest::StatusCode;
+
+fn inputs() -> PrivateInputs {
+    PrivateInputs {
+        nullifier: rand::random::<[u8; 32]>().into(),
+        secret: rand::random::<[u8; 32]>().into(),
+    }
+}
+
+#[test]
+fn sealed_inputs_open_only_with_the_key() {
+    let key = InputKey::generate();
+    let private = inputs();
+    let sealed = SealedInputs::seal(&key.public_key(), &private).unwrap();
+    assert_eq!(key.open(&sealed).unwrap(), private);
+    assert_eq!(
+        format!("{:?}", private),
+        "PrivateInputs { nullifier: [REDACTED], secret: [REDACTED] }"
+    );
+
+    assert!(InputKey::generate().open(&sealed).is_err());
+    let mut ciphertext = hex::decode(&sealed.ciphertext).unwrap();
+    ciphertext[0] ^= 1;
+    let tampered = SealedInputs {
+        ciphertext: hex::encode(ciphertext),
+        ..sealed.clone()
+    };
+    assert!(key.open(&tampered).is_err());
+
+    let path = std::env::temp_dir().join(format!(
+        "input-key-{}.hex",
+        hex::encode(rand::random::<[u8; 8]>())
+    ));
+    std::fs::write(&path, hex::encode(rand::random::<[u8; 32]>())).unwrap();
+    let loaded = InputKey::from_file(path.to_str().unwrap()).unwrap();
+    let reloaded = InputKey::from_file(path.to_str().unwrap()).unwrap();
+    assert_eq!(loaded.public_key(), reloaded.public_key());
+    let sealed = SealedInputs::seal(&loaded.public_key(), &private).unwrap();
+    assert_eq!(reloaded.open(&sealed).unwrap(), private);
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// A request for the only deposit of a tree, with its inputs sealed to `key`.
+fn sealed_request(key: &[u8; 32]) -> ProveRequest {
+    let (nullifier, secret, _, commitment, nullifier_hash) =
+        mixer_lib::utils::create_random_commitment();
+    let (path_elements, path_indices, root) = compute_merkle_proof::<TREE_DEPTH>(&[commitment], 0);
+    let private = PrivateInputs {
+        nullifier: biguint_to_32_le_bytes(&nullifier).into(),
+        secret: biguint_to_32_le_bytes(&secret).into(),
+    };
+    ProveRequest {
+        root: to_hex32(&root),
+        nullifier_hash: to_hex32(&nullifier_hash),
+        recipient: to_hex32(&[1; 32]),
+        relayer: to_hex32(&[2; 32]),
+        fee: 0,
+        refund: 0,
+        mint: None,
+        amount: 100_000_000,
+        nullifier: Default::default(),
+        secret: Default::default(),
+        sealed: Some(SealedInputs::seal(key, &private).unwrap()),
+        path_elements: to_hex_vec(&path_elements),
+        path_indices: path_indices.to_vec(),
+        format: Default::default(),
+        dry_run: false,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn dry_run_opens_sealed_inputs() {
+    let app = spawn_app(ServerConfig {
+        prover: ProverBackend::Mock,
+        ..Default::default()
+    })
+    .await;
+    let client = reqwest::Client::new();
+    let published: InputKeyResponse = client
+        .get(format!("{}/api/input-key", app))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let key: [u8; 32] = hex::decode(&published.public_key)
+        .unwrap()
+        .try_into()
+        .unwrap();
+    let dry_run = |req: ProveRequest| {
+        client
+            .post(format!("{}/api/prove-mix/dry-run", app))
+            .json(&req)
+            .send()
+    };
+
+    // Sealed to another key, for another note, or sent in the clear as well.
+    let foreign = sealed_request(&InputKey::generate().public_key());
+    let resp = dry_run(foreign).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let mut mismatched = sealed_request(&key);
+    mismatched.nullifier_hash = sealed_request(&key).nullifier_hash;
+    let resp = dry_run(mismatched).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(resp.text().await.unwrap().contains("nullifier_hash"));
+    // A sealed nullifier above the field modulus cannot be hashed.
+    let mut unhashable = sealed_request(&key);
+    unhashable.sealed = Some(
+        SealedInputs::seal(
+            &key,
+            &PrivateInputs {
+                nullifier: [0xff; 32].into(),
+                secret: [1; 32].into(),
+            },
+        )
+        .unwrap(),
+    );
+    let resp = dry_run(unhashable).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(resp.text().await.unwrap().contains("field modulus"));
+    let mut both = sealed_request(&key);
+    both.nullifier = to_hex32(&[3; 32]).into();
+    let resp = dry_run(both).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    if MIXER_ELF.is_empty() {
+        eprintln!("skipping: mixer-program ELF was not built");
+        return;
+    }
+    let req = sealed_request(&key);
+    let nullifier_hash = req.nullifier_hash.clone();
+    let resp = dry_run(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let report: DryRunResponse = resp.json().await.unwrap();
+    assert_eq!(report.nullifier_hash, nullifier_hash);
+}
@@ -0,0 +1,161 @@
+//! Note material stays out of logs, `Debug` output and error messages.
+mod common;
+
+use anchor_client::solana_sdk::commitment_config::CommitmentLevel::Finalized;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use common::{deposit_logs, registry_with_index, spawn_app_with_state};
+use futures_util::{SinkExt, StreamExt};
+use mixer_script::idl::EventDecoder;
+use mixer_script::indexer::DepositIndex;
+use mixer_script::note::Note;
+use mixer_script::pools::PoolConfig;
+use mixer_script::server::{AppState, ProveRequest, ServerConfig};
+use mixer_script::utils::to_hex32;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+/// Collects everything the subscriber writes.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn ws_reply(url: String, req: Value) -> Value {
+    let (mut ws, _) = connect_async(url).await.unwrap();
+    ws.send(Message::text(req.to_string())).await.unwrap();
+    loop {
+        match ws.next().await.expect("socket closed").unwrap() {
+            Message::Text(txt) => return serde_json::from_str(&txt).unwrap(),
+            Message::Close(_) => panic!("socket closed without a reply"),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn logs_never_contain_note_secrets() {
+    let logs = Captured::default();
+    let writer = logs.clone();
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(move || writer.clone())
+            .finish(),
+    )
+    .unwrap();
+
+    let client = reqwest::Client::new();
+    let pool = PoolConfig {
+        id: "sol-0.1".to_string(),
+        denomination: Some(100_000_000),
+        ..PoolConfig::for_program(PROGRAM_ID)
+    };
+    let index = Arc::new(DepositIndex::new(PROGRAM_ID.parse().unwrap()));
+    let app = spawn_app_with_state(AppState::new(
+        ServerConfig::default(),
+        EventDecoder::bundled(),
+        registry_with_index(pool, index.clone()),
+    ))
+    .await;
+
+    let details: Value = client
+        .post(format!("{}/api/generate-deposit-details", app))
+        .json(&json!({ "units": 100_000_000 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let note_text = details["note"].as_str().unwrap().to_string();
+    let note = Note::parse(&note_text).unwrap();
+    let (commitment, nullifier_hash) = note.hashes();
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs(commitment, 0));
+
+    let decoded: Value = client
+        .post(format!("{}/api/pools/sol-0.1/decode-note", app))
+        .json(&json!({ "note": note_text }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    // The withdrawal is computed up to the prover, which is unreachable.
+    let reply = ws_reply(
+        format!(
+            "{}/api/pools/sol-0.1/compute_withdrawal",
+            app.replacen("http", "ws", 1)
+        ),
+        json!({
+            "note": note_text,
+            "new_withdrawal_recipient_address": Pubkey::new_unique().to_string(),
+            "new_relayer_address": Pubkey::new_unique().to_string(),
+            "server_url": "http://127.0.0.1:1",
+        }),
+    )
+    .await;
+    assert!(reply["error"].is_string());
+
+    // Invalid proof requests fail without echoing their inputs.
+    let invalid = ProveRequest {
+        root: to_hex32(&[0; 32]),
+        nullifier_hash: to_hex32(&nullifier_hash),
+        recipient: to_hex32(&[1; 32]),
+        relayer: to_hex32(&[2; 32]),
+        fee: 0,
+        refund: 0,
+        mint: None,
+        amount: note.amount,
+        nullifier: format!("{}zz", details["nullifier"].as_str().unwrap()).into(),
+        secret: details["secret"].as_str().unwrap().to_string().into(),
+        sealed: None,
+        path_elements: Vec::new(),
+        path_indices: Vec::new(),
+        format: Default::default(),
+        dry_run: false,
+    };
+    let resp = client
+        .post(format!("{}/api/prove-mix/dry-run", app))
+        .json(&invalid)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    let error = resp.text().await.unwrap();
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("generated deposit note"), "{}", logs);
+    assert!(logs.contains("proving withdrawal"), "{}", logs);
+    let secrets = [
+        note_text.as_str(),
+        details["nullifier"].as_str().unwrap(),
+        details["secret"].as_str().unwrap(),
+        decoded["nullifier_str"].as_str().unwrap(),
+        decoded["secret_str"].as_str().unwrap(),
+    ];
+    for secret in secrets {
+        assert!(!logs.contains(secret), "logs contain {}", secret);
+        assert!(!error.contains(secret), "error contains {}", secret);
+    }
+
+    let debug = format!("{:?}", note);
+    assert!(debug.contains("[REDACTED]"), "{}", debug);
+    for secret in secrets {
+        assert!(!debug.contains(secret), "{}", debug);
+    }
+}
@@ -0,0 +1,157 @@
+//! The alt_bn128 layout of proofs and verifying keys: fixed bytes for known
+//! points, and a round trip through `groth16-solana`'s verifier.
+
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::Groth16;
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_snark::{CircuitSpecificSetupSNARK, SNARK};
+use groth16_solana::groth16::{Groth16Verifier, Groth16Verifyingkey};
+use mixer_script::solana::{self, SolanaProof, SolanaVerifyingKey};
+use sp1_verifier::{load_ark_groth16_verifying_key_from_bytes, GROTH16_VK_BYTES};
+
+const VKEY_HASH: &str = "0x0011111111111111111111111111111111111111111111111111111111111111";
+
+/// SP1's `proof.bytes()` for the points `a`, `b` and `c`: a 4-byte vkey hash
+/// prefix, then gnark's uncompressed encoding with the imaginary part first.
+fn sp1_proof_bytes(a: &G1Affine, b: &G2Affine, c: &G1Affine) -> Vec<u8> {
+    let mut bytes = vec![0xab; 4];
+    for f in [a.x, a.y, b.x.c1, b.x.c0, b.y.c1, b.y.c0, c.x, c.y] {
+        bytes.extend(f.into_bigint().to_bytes_be());
+    }
+    bytes
+}
+
+fn fq(bytes: &[u8]) -> Fq {
+    Fq::from_be_bytes_mod_order(bytes)
+}
+
+#[test]
+fn proofs_match_the_alt_bn128_layout() {
+    let g1 = G1Affine::generator();
+    let two_g1 = (g1 * Fr::from(2u64)).into_affine();
+    let bytes = sp1_proof_bytes(&g1, &G2Affine::generator(), &two_g1);
+    let proof = solana::to_solana_proof(&bytes, b"abc", VKEY_HASH).unwrap();
+
+    // -G1 = (1, p - 2).
+    assert_eq!(
+        hex::encode(&proof.proof_a),
+        "0000000000000000000000000000000000000000000000000000000000000001\
+         30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd45"
+    );
+    // The EIP-197 G2 generator: x.c1, x.c0, y.c1, y.c0.
+    assert_eq!(
+        hex::encode(&proof.proof_b),
+        "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2\
+         1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed\
+         090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b\
+         12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa"
+    );
+    // 2 * G1, not negated.
+    assert_eq!(
+        hex::encode(&proof.proof_c),
+        "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3\
+         15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4"
+    );
+    // The vkey hash, then SHA-256("abc") with its top three bits cleared.
+    let inputs: Vec<String> = proof.public_inputs.iter().map(hex::encode).collect();
+    assert_eq!(
+        inputs,
+        [
+            &VKEY_HASH[2..],
+            "1a7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        ]
+    );
+    assert_eq!(proof.public_values, b"abc");
+
+    let unprefixed = solana::to_solana_proof(&bytes, b"abc", &VKEY_HASH[2..]).unwrap();
+    assert_eq!(unprefixed.public_inputs, proof.public_inputs);
+    assert!(solana::to_solana_proof(&bytes[..259], b"abc", VKEY_HASH).is_err());
+    assert!(solana::to_solana_proof(&bytes, b"abc", "0x0011").is_err());
+}
+
+#[test]
+fn sp1_verifying_key_decodes_to_curve_points() {
+    let vk = solana::to_solana_vk(&GROTH16_VK_BYTES).unwrap();
+    assert_eq!((vk.nr_pubinputs, vk.vk_ic.len()), (2, 3));
+
+    let g1 = |b: &[u8]| G1Affine::new(fq(&b[..32]), fq(&b[32..]));
+    let fq2 = |b: &[u8]| Fq2::new(fq(&b[32..]), fq(&b[..32]));
+    let g2 = |b: &[u8]| G2Affine::new(fq2(&b[..64]), fq2(&b[64..]));
+    let ark = load_ark_groth16_verifying_key_from_bytes(&GROTH16_VK_BYTES).unwrap();
+    assert_eq!(g1(&vk.vk_alpha_g1), ark.alpha_g1);
+    assert_eq!(g2(&vk.vk_beta_g2), ark.beta_g2);
+    assert_eq!(g2(&vk.vk_gamma_g2), ark.gamma_g2);
+    assert_eq!(g2(&vk.vk_delta_g2), ark.delta_g2);
+    for (ic, point) in vk.vk_ic.iter().zip(&ark.gamma_abc_g1) {
+        assert_eq!(g1(ic), *point);
+    }
+
+    let source = solana::solana_vk_rust_source(&vk, VKEY_HASH).unwrap();
+    assert!(source.contains(&format!(
+        "pub const MIXER_VKEY_HASH: [u8; 32] = [0, {}];\n",
+        ["17"; 31].join(", ")
+    )));
+    assert!(source.contains("    nr_pubinputs: 2,\n"));
+    let alpha: Vec<String> = vk.vk_alpha_g1.iter().map(|b| b.to_string()).collect();
+    assert!(source.contains(&format!("    vk_alpha_g1: [{}],\n", alpha.join(", "))));
+    assert!(source.contains("    vk_gamme_g2: ["));
+    assert_eq!(source.matches("\n        [").count(), 3);
+}
+
+/// `a * b = c` with `a` and `b` public, the shape of SP1's two public inputs.
+struct Product {
+    a: Fr,
+    b: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for Product {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let a = cs.new_input_variable(|| Ok(self.a))?;
+        let b = cs.new_input_variable(|| Ok(self.b))?;
+        let c = cs.new_witness_variable(|| Ok(self.a * self.b))?;
+        cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)
+    }
+}
+
+fn verify_on_solana(proof: &SolanaProof, vk: &SolanaVerifyingKey) -> bool {
+    let vk = Groth16Verifyingkey {
+        nr_pubinputs: vk.nr_pubinputs,
+        vk_alpha_g1: vk.vk_alpha_g1,
+        vk_beta_g2: vk.vk_beta_g2,
+        vk_gamme_g2: vk.vk_gamma_g2,
+        vk_delta_g2: vk.vk_delta_g2,
+        vk_ic: &vk.vk_ic,
+    };
+    let public_inputs: [[u8; 32]; 2] = proof.public_inputs.clone().try_into().unwrap();
+    let mut verifier = Groth16Verifier::new(
+        proof.proof_a.as_slice().try_into().unwrap(),
+        proof.proof_b.as_slice().try_into().unwrap(),
+        proof.proof_c.as_slice().try_into().unwrap(),
+        &public_inputs,
+        &vk,
+    )
+    .unwrap();
+    verifier.verify().is_ok()
+}
+
+#[test]
+fn groth16_solana_verifies_converted_proofs() {
+    let mut rng = ark_std::test_rng();
+    let inputs = solana::groth16_public_inputs(b"abc", VKEY_HASH).unwrap();
+    let [a, b] = inputs.map(|i| Fr::from_be_bytes_mod_order(&i));
+    let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(Product { a, b }, &mut rng).unwrap();
+    let proof = Groth16::<Bn254>::prove(&pk, Product { a, b }, &mut rng).unwrap();
+    let bytes = sp1_proof_bytes(&proof.a, &proof.b, &proof.c);
+    let vk = SolanaVerifyingKey::from(&vk);
+
+    let converted = solana::to_solana_proof(&bytes, b"abc", VKEY_HASH).unwrap();
+    assert!(verify_on_solana(&converted, &vk));
+    let tampered = solana::to_solana_proof(&bytes, b"abd", VKEY_HASH).unwrap();
+    assert!(!verify_on_solana(&tampered, &vk));
+    let mut unnegated = converted.clone();
+    unnegated.proof_a = bytes[4..68].to_vec();
+    assert!(!verify_on_solana(&unnegated, &vk));
+}
@@ -0,0 +1,167 @@
+//! SPL token pools: token notes, mint checks and associated token accounts in
+//! the withdrawal inputs.
+mod common;
+
+use anchor_client::solana_sdk::commitment_config::CommitmentLevel::Finalized;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use axum::{extract::State, routing::post, Json, Router};
+use common::{deposit_logs, registry_with_index, spawn_app_with_state};
+use futures_util::{SinkExt, StreamExt};
+use mixer_lib::secret::Secret;
+use mixer_script::idl::EventDecoder;
+use mixer_script::indexer::DepositIndex;
+use mixer_script::note::Note;
+use mixer_script::pools::PoolConfig;
+use mixer_script::server::{AppState, ProveRequest, ServerConfig};
+use mixer_script::utils::to_hex32;
+use serde_json::{json, Value};
+use spl_associated_token_account_client::address::get_associated_token_address;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+const HUNDRED_USDC: u64 = 100_000_000;
+
+fn usdc_pool() -> PoolConfig {
+    PoolConfig {
+        id: "usdc-100".to_string(),
+        denomination: Some(HUNDRED_USDC),
+        mint: Some(USDC.to_string()),
+        decimals: 6,
+        ..PoolConfig::for_program(PROGRAM_ID)
+    }
+}
+
+fn le32(value: u8) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    bytes[0] = value;
+    bytes
+}
+
+fn usdc_note(amount: u64) -> Note {
+    Note {
+        mint: Some(Pubkey::from_str(USDC).unwrap()),
+        amount,
+        nullifier: Secret::new(le32(11)),
+        secret: Secret::new(le32(22)),
+    }
+}
+
+#[test]
+fn token_notes_carry_mint_and_units() {
+    let note = usdc_note(HUNDRED_USDC);
+    let text = note.encode().expose().clone();
+    assert_eq!(text, format!("solana-mixer-{}-100000000-0b:16", USDC));
+    assert_eq!(Note::parse(&text).unwrap(), note);
+
+    let sol = Note::parse("solana-mixer-sol-100000000-0b:16").unwrap();
+    assert_eq!(sol.mint, None);
+    assert_eq!(sol.amount, 100_000_000);
+    // Old notes name whole SOL.
+    assert_eq!(Note::parse("solana-mixer-0.1-0b:16").unwrap(), sol);
+    assert_eq!(
+        Note::parse(&format!(
+            "solana-mixer-sol-100000000-{}:16",
+            "ff".repeat(32)
+        ))
+        .unwrap_err(),
+        "nullifier in note is not below the field modulus"
+    );
+
+    let pool = usdc_pool();
+    pool.check_note(&note).unwrap();
+    let err = pool.check_note(&sol).unwrap_err();
+    assert_eq!(
+        err,
+        format!("note is for SOL but pool usdc-100 holds {}", USDC)
+    );
+    let err = pool.check_note(&usdc_note(10_000_000)).unwrap_err();
+    assert_eq!(
+        err,
+        "note is for 10 but pool usdc-100 takes deposits of 100"
+    );
+}
+
+/// Stands in for `/api/prove-mix` and records what the WebSocket flow sends it.
+async fn spawn_capturing_prover() -> (String, Arc<Mutex<Option<ProveRequest>>>) {
+    let seen = Arc::new(Mutex::new(None));
+    let app = Router::new()
+        .route(
+            "/api/prove-mix",
+            post(
+                |State(seen): State<Arc<Mutex<Option<ProveRequest>>>>,
+                 Json(req): Json<ProveRequest>| async move {
+                    *seen.lock().unwrap() = Some(req);
+                    Json(json!({ "error": "not proving in tests" }))
+                },
+            ),
+        )
+        .with_state(seen.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", addr), seen)
+}
+
+#[tokio::test]
+async fn token_withdrawal_pays_associated_token_accounts() {
+    let note = usdc_note(HUNDRED_USDC);
+    let (commitment, _) = note.hashes();
+    let index = Arc::new(DepositIndex::new(PROGRAM_ID.parse().unwrap()));
+    index.apply_logs("sig-0", 10, Finalized, &deposit_logs(commitment, 0));
+    let app = spawn_app_with_state(AppState::new(
+        ServerConfig::default(),
+        EventDecoder::bundled(),
+        registry_with_index(usdc_pool(), index),
+    ))
+    .await;
+    let (prover, seen) = spawn_capturing_prover().await;
+
+    let decoded: Value = reqwest::Client::new()
+        .post(format!("{}/api/pools/usdc-100/decode-note", app))
+        .json(&json!({ "note": note.encode() }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(decoded["amount"], 100.0);
+    assert_eq!(decoded["units"], HUNDRED_USDC);
+    assert_eq!(decoded["mint"], USDC);
+
+    let recipient = Pubkey::new_unique();
+    let relayer = Pubkey::new_unique();
+    let url = format!(
+        "{}/api/pools/usdc-100/compute_withdrawal",
+        app.replacen("http", "ws", 1)
+    );
+    let (mut ws, _) = connect_async(url).await.unwrap();
+    let req = json!({
+        "note": note.encode(),
+        "new_withdrawal_recipient_address": recipient.to_string(),
+        "new_relayer_address": relayer.to_string(),
+        "server_url": prover,
+    });
+    ws.send(Message::text(req.to_string())).await.unwrap();
+    while let Some(Ok(msg)) = ws.next().await {
+        if matches!(msg, Message::Text(_) | Message::Close(_)) {
+            break;
+        }
+    }
+
+    let sent = seen.lock().unwrap().take().expect("no prove request");
+    let mint = Pubkey::from_str(USDC).unwrap();
+    assert_eq!(
+        sent.recipient,
+        to_hex32(&get_associated_token_address(&recipient, &mint).to_bytes())
+    );
+    assert_eq!(
+        sent.relayer,
+        to_hex32(&get_associated_token_address(&relayer, &mint).to_bytes())
+    );
+    assert_eq!(sent.mint, Some(to_hex32(&mint.to_bytes())));
+    assert_eq!(sent.amount, HUNDRED_USDC);
+}
@@ -0,0 +1,186 @@
+//! Tree snapshots: encodings, the snapshot route and indexer bootstrap.
+mod common;
+
+use anchor_client::solana_sdk::commitment_config::CommitmentLevel::{Confirmed, Finalized};
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use common::{deposit_logs, registry_with_index, spawn_app_with_state, withdrawal_logs};
+use mixer_lib::tree::{TREE_DEPTH, ZERO_HASHES};
+use mixer_lib::utils::hash2;
+use mixer_script::idl::EventDecoder;
+use mixer_script::indexer::DepositIndex;
+use mixer_script::pools::PoolConfig;
+use mixer_script::server::{AppState, ServerConfig};
+use mixer_script::snapshot::{SnapshotFormat, SnapshotJson, TreeSnapshot};
+use mixer_script::source::FixtureSource;
+use mixer_script::utils::biguint_to_32_le_bytes;
+use std::sync::Arc;
+
+const PROGRAM_ID: &str = "B7odahygLXdwCYmJteVyBFXXe9qEW5hyvCXieRGBoTTz";
+
+fn leaf(i: u32) -> [u8; 32] {
+    mixer_lib::utils::hash1(&biguint_to_32_le_bytes(&i.into()))
+}
+
+/// Root and filled subtrees after inserting `leaves` one by one, the way the
+/// on-chain incremental tree does.
+fn incremental(leaves: &[[u8; 32]]) -> ([u8; 32], [[u8; 32]; TREE_DEPTH]) {
+    let mut filled = ZERO_HASHES;
+    let mut root = [0; 32];
+    for (index, leaf) in leaves.iter().enumerate() {
+        let mut node = *leaf;
+        for (d, filled) in filled.iter_mut().enumerate() {
+            node = if (index >> d) & 1 == 0 {
+                *filled = node;
+                hash2(&node, &ZERO_HASHES[d])
+            } else {
+                hash2(filled, &node)
+            };
+        }
+        root = node;
+    }
+    (root, filled)
+}
+
+#[test]
+fn snapshots_round_trip_and_match_the_incremental_tree() {
+    let program_id: Pubkey = PROGRAM_ID.parse().unwrap();
+    let leaves: Vec<[u8; 32]> = (0..5).map(leaf).collect();
+    let snapshot = TreeSnapshot::new(program_id, leaves.clone(), Some("sig-4".to_string()))
+        .with_nullifier_hashes(vec![[8; 32], [7; 32], [8; 32]]);
+    assert_eq!(snapshot.nullifier_hashes, [[7; 32], [8; 32]]);
+    assert_eq!(
+        (snapshot.root, snapshot.filled_subtrees),
+        incremental(&leaves)
+    );
+    let empty = TreeSnapshot::new(program_id, Vec::new(), None);
+    assert_eq!(empty.filled_subtrees, ZERO_HASHES);
+
+    let bytes = snapshot.to_bytes();
+    assert_eq!(
+        bytes.len(),
+        8 + 32 + 8 + 32 + 32 * TREE_DEPTH + 6 + 32 * 5 + 8 + 32 * 2 + 32
+    );
+    assert_eq!(TreeSnapshot::from_bytes(&bytes).unwrap(), snapshot);
+    let json = snapshot.to_json();
+    assert_eq!(json.checksum, hex::encode(snapshot.checksum()));
+    assert_eq!(TreeSnapshot::from_json(&json).unwrap(), snapshot);
+
+    let mut corrupt = bytes.clone();
+    corrupt[100] ^= 1;
+    assert!(TreeSnapshot::from_bytes(&corrupt)
+        .unwrap_err()
+        .contains("checksum"));
+    let mut forged = snapshot.clone();
+    forged.root = leaf(9);
+    assert!(TreeSnapshot::from_bytes(&forged.to_bytes())
+        .unwrap_err()
+        .contains("root"));
+    let dropped = SnapshotJson {
+        leaves: json.leaves[..4].to_vec(),
+        next_index: 4,
+        ..json
+    };
+    assert!(TreeSnapshot::from_json(&dropped).is_err());
+    let mut unsorted = snapshot.clone();
+    unsorted.nullifier_hashes.reverse();
+    assert!(TreeSnapshot::from_bytes(&unsorted.to_bytes())
+        .unwrap_err()
+        .contains("ascending"));
+
+    let path = std::env::temp_dir().join(format!(
+        "tree-{}.snapshot",
+        hex::encode(rand::random::<[u8; 8]>())
+    ));
+    for format in [SnapshotFormat::Binary, SnapshotFormat::Json] {
+        snapshot.write(&path, format).unwrap();
+        assert_eq!(TreeSnapshot::read(&path).unwrap(), snapshot);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn indexer_bootstraps_from_a_served_snapshot() {
+    let program_id: Pubkey = PROGRAM_ID.parse().unwrap();
+    let index = Arc::new(DepositIndex::new(program_id));
+    for i in 0..4u32 {
+        let status = if i < 3 { Finalized } else { Confirmed };
+        index.apply_logs(
+            &format!("sig-{}", i),
+            10 + i as u64,
+            status,
+            &deposit_logs(leaf(i), i),
+        );
+    }
+    // A finalized withdrawal the snapshot carries.
+    index.apply_logs(
+        "sig-w",
+        11,
+        Finalized,
+        &withdrawal_logs([8; 32], Pubkey::new_unique(), 0),
+    );
+    let pool = PoolConfig {
+        id: "sol-0.1".to_string(),
+        denomination: Some(100_000_000),
+        ..PoolConfig::for_program(PROGRAM_ID)
+    };
+    let app = spawn_app_with_state(AppState::new(
+        ServerConfig::default(),
+        EventDecoder::bundled(),
+        registry_with_index(pool, index),
+    ))
+    .await;
+
+    // Only finalized deposits are exported.
+    let url = format!("{}/api/pools/sol-0.1/tree/snapshot", app);
+    let json: SnapshotJson = reqwest::get(&url).await.unwrap().json().await.unwrap();
+    let snapshot = TreeSnapshot::from_json(&json).unwrap();
+    assert_eq!(snapshot.next_index(), 3);
+    assert_eq!(snapshot.last_signature.as_deref(), Some("sig-2"));
+    assert_eq!(snapshot.nullifier_hashes, [[8; 32]]);
+    let binary = reqwest::get(format!("{}?format=binary", url))
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(TreeSnapshot::from_bytes(&binary).unwrap(), snapshot);
+    let resp = reqwest::get(format!("{}/api/pools/usdc-10/tree/snapshot", app))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // A withdrawal hidden before the snapshot shows whether a backfill
+    // replays history the snapshot already covers; the snapshot's own
+    // withdrawals are restored.
+    let mut source = FixtureSource::new();
+    source.push(
+        "sig-0",
+        10,
+        withdrawal_logs([7; 32], Pubkey::new_unique(), 0),
+    );
+    for i in 1..6u32 {
+        source.push(
+            &format!("sig-{}", i),
+            10 + i as u64,
+            deposit_logs(leaf(i), i),
+        );
+    }
+    let restored = DepositIndex::new(program_id);
+    restored.restore(&snapshot).unwrap();
+    assert!(restored.restore(&snapshot).is_err());
+    assert_eq!(restored.snapshot(), snapshot);
+    assert_eq!(restored.backfill(&source).await.unwrap(), 3);
+    assert!(restored.withdrawal(&[7; 32]).is_none());
+    assert!(restored.withdrawal(&[8; 32]).is_some());
+    assert_eq!(restored.stats().withdrawals, 1);
+    let leaves: Vec<[u8; 32]> = (0..6).map(leaf).collect();
+    assert_eq!(restored.leaves(), leaves);
+    assert_eq!(
+        restored.snapshot(),
+        TreeSnapshot::new(program_id, leaves, Some("sig-5".to_string()))
+            .with_nullifier_hashes(vec![[8; 32]])
+    );
+
+    let other = DepositIndex::new(Pubkey::new_unique());
+    assert!(other.restore(&snapshot).is_err());
+}
@@ -0,0 +1,137 @@
+//! Tests of local Groth16 verification and `/api/verify`.
+mod common;
+
+use common::spawn_app;
+use mixer_script::server::ServerConfig;
+use mixer_script::solana::{to_solana_proof, verify_solana_proof};
+use mixer_script::verify::{normalize_vkey_hash, skip_proof_verification, verify_groth16};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+const VKEY_HASH: &str = "0x00ab11111111111111111111111111111111111111111111111111111111cdef";
+
+/// A Groth16 proof written by the `evm` binary (`--prover cpu`), from
+/// `MIXER_PROOF_FIXTURE`. Returns the proof, public values and vkey hash.
+fn proof_fixture() -> Option<(String, String, String)> {
+    let path = std::env::var("MIXER_PROOF_FIXTURE").ok()?;
+    let fixture: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    let field = |name: &str| fixture[name].as_str().unwrap().to_string();
+    Some((field("proof"), field("publicValues"), field("vkey")))
+}
+
+fn decode(s: &str) -> Vec<u8> {
+    hex::decode(s.strip_prefix("0x").unwrap_or(s)).unwrap()
+}
+
+async fn post_verify(app: &str, body: Value) -> (StatusCode, String) {
+    let resp = reqwest::Client::new()
+        .post(format!("{}/api/verify", app))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    (resp.status(), resp.text().await.unwrap())
+}
+
+fn request(proof: &str, public_values: &str, vkey_hash: &str) -> Value {
+    json!({
+        "proof": proof,
+        "public_values": public_values,
+        "vkey_hash": vkey_hash,
+    })
+}
+
+#[test]
+fn vkey_hashes_are_normalized() {
+    let unprefixed = &VKEY_HASH[2..];
+    assert_eq!(normalize_vkey_hash(VKEY_HASH).unwrap(), VKEY_HASH);
+    assert_eq!(normalize_vkey_hash(unprefixed).unwrap(), VKEY_HASH);
+    assert_eq!(
+        normalize_vkey_hash(&unprefixed.to_uppercase()).unwrap(),
+        VKEY_HASH
+    );
+    assert!(normalize_vkey_hash(&VKEY_HASH[..64])
+        .unwrap_err()
+        .contains("length"));
+    assert!(normalize_vkey_hash(&format!("{}00", VKEY_HASH))
+        .unwrap_err()
+        .contains("length"));
+    assert!(normalize_vkey_hash(&format!("0x{}", "zz".repeat(32)))
+        .unwrap_err()
+        .contains("hex"));
+}
+
+#[test]
+fn invalid_proofs_are_rejected() {
+    assert!(verify_groth16(&[], b"abc", VKEY_HASH)
+        .unwrap_err()
+        .contains("mock"));
+    assert!(verify_groth16(&[7; 260], b"abc", VKEY_HASH).is_err());
+    assert!(verify_groth16(&[7; 260], b"abc", "0x00ab").is_err());
+
+    let Some((proof, public_values, vkey_hash)) = proof_fixture() else {
+        eprintln!("skipping: MIXER_PROOF_FIXTURE is not set");
+        return;
+    };
+    let (proof, mut public_values) = (decode(&proof), decode(&public_values));
+    verify_groth16(&proof, &public_values, &vkey_hash).unwrap();
+    verify_groth16(&proof, &public_values, &vkey_hash[2..]).unwrap();
+    let solana = to_solana_proof(&proof, &public_values, &vkey_hash).unwrap();
+    verify_solana_proof(&solana, &vkey_hash).unwrap();
+    *public_values.last_mut().unwrap() ^= 1;
+    assert!(verify_groth16(&proof, &public_values, &vkey_hash).is_err());
+    let tampered = to_solana_proof(&proof, &public_values, &vkey_hash).unwrap();
+    assert!(verify_solana_proof(&tampered, &vkey_hash).is_err());
+    let mut relabeled = solana.clone();
+    relabeled.public_values = public_values;
+    assert!(verify_solana_proof(&relabeled, &vkey_hash).is_err());
+}
+
+#[test]
+fn skip_proof_verification_reads_the_environment() {
+    for (value, skip) in [("true", true), ("TRUE", true), ("1", true), ("0", false)] {
+        std::env::set_var("SKIP_PROOF_VERIFICATION", value);
+        assert_eq!(skip_proof_verification(), skip, "{}", value);
+    }
+    std::env::remove_var("SKIP_PROOF_VERIFICATION");
+    assert!(!skip_proof_verification());
+}
+
+#[tokio::test]
+async fn verify_endpoint_checks_proofs() {
+    let app = spawn_app(ServerConfig::default()).await;
+    let garbage = hex::encode([7; 260]);
+
+    let (status, body) = post_verify(&app, request("0xzz", "616263", VKEY_HASH)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("invalid proof hex"), "{}", body);
+    let (status, body) = post_verify(&app, request(&garbage, "abc", VKEY_HASH)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("invalid public values hex"), "{}", body);
+    let (status, body) = post_verify(&app, request(&garbage, "616263", "0x00ab")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("length"), "{}", body);
+
+    // An unprefixed vkey hash is accepted; the proof itself is not.
+    let (status, body) = post_verify(&app, request(&garbage, "616263", &VKEY_HASH[2..])).await;
+    assert_eq!(status, StatusCode::OK);
+    let resp: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(resp["valid"], false);
+    assert!(resp["error"].is_string());
+
+    let Some((proof, public_values, vkey_hash)) = proof_fixture() else {
+        eprintln!("skipping: MIXER_PROOF_FIXTURE is not set");
+        return;
+    };
+    let (status, body) = post_verify(&app, request(&proof, &public_values, &vkey_hash)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        serde_json::from_str::<Value>(&body).unwrap(),
+        json!({ "valid": true })
+    );
+    let mut tampered = decode(&public_values);
+    *tampered.last_mut().unwrap() ^= 1;
+    let body = request(&proof, &hex::encode(tampered), &vkey_hash);
+    let resp: Value = serde_json::from_str(&post_verify(&app, body).await.1).unwrap();
+    assert_eq!(resp["valid"], false);
+}